@@ -0,0 +1,668 @@
+//! A Debug Adapter Protocol server for `stylus dap`, so editors can drive the core debugger.
+
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use stylus_core::{Breakpoint, Debugger, Recording, StopReason, Trap, Val};
+use stylus_harness::StylusRunner;
+
+const THREAD_ID: i64 = 1;
+const GLOBALS_REFERENCE: i64 = 1;
+const STACK_REFERENCE: i64 = 2;
+/// Locals of wasm frame `n` (innermost first) use reference `LOCALS_REFERENCE + n`.
+const LOCALS_REFERENCE: i64 = 1000;
+
+struct Session {
+    debugger: Debugger,
+    stop_on_entry: bool,
+    source_breakpoints: HashMap<String, Vec<usize>>,
+    function_breakpoints: Vec<usize>,
+    /// The wasm frame each stack frame id reported to the client belongs to.
+    frames: Vec<usize>,
+    trap_reported: bool,
+}
+
+pub struct Server<W: Write> {
+    output: W,
+    seq: i64,
+    wasm_path: PathBuf,
+    session: Option<Session>,
+}
+
+impl<W: Write> Server<W> {
+    /// `wasm_path` is used when a launch request does not name a program.
+    pub fn new(output: W, wasm_path: PathBuf) -> Self {
+        Self {
+            output,
+            seq: 1,
+            wasm_path,
+            session: None,
+        }
+    }
+
+    /// Handles requests from `input` until the client disconnects.
+    pub fn serve<R: BufRead>(&mut self, mut input: R) -> Result<()> {
+        while let Some(message) = read_message(&mut input)? {
+            if message["type"] != "request" {
+                continue;
+            }
+            let command = message["command"].as_str().unwrap_or_default().to_string();
+            let arguments = message.get("arguments").cloned().unwrap_or(Value::Null);
+            let seq = message["seq"].as_i64().unwrap_or(0);
+
+            match self.handle(&command, &arguments) {
+                Ok(Reply { body, after }) => {
+                    self.respond(seq, &command, true, None, body)?;
+                    match after {
+                        After::Nothing => {}
+                        After::Initialized { step_back } => {
+                            if step_back {
+                                let capabilities = json!({ "supportsStepBack": true });
+                                self.event("capabilities", json!({ "capabilities": capabilities }))?;
+                            }
+                            self.event("initialized", json!({}))?
+                        }
+                        After::Stop(reason) => self.report(reason)?,
+                        After::Entry => self.stopped("entry", None, json!({}))?,
+                        After::Disconnect => return Ok(()),
+                    }
+                }
+                Err(e) => self.respond(seq, &command, false, Some(e.to_string()), Value::Null)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, command: &str, arguments: &Value) -> Result<Reply> {
+        match command {
+            "initialize" => Ok(Reply::body(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsFunctionBreakpoints": true,
+                "supportsTerminateRequest": true,
+            }))),
+            // Only recordings can step backwards, which is known once the launch names one.
+            "launch" => {
+                let step_back = self.launch(arguments)?;
+                Ok(Reply::then(After::Initialized { step_back }))
+            }
+            "setBreakpoints" => self.set_breakpoints(arguments).map(Reply::body),
+            "setFunctionBreakpoints" => self.set_function_breakpoints(arguments).map(Reply::body),
+            "setExceptionBreakpoints" => Ok(Reply::body(json!({ "breakpoints": [] }))),
+            "configurationDone" => {
+                if self.session()?.stop_on_entry {
+                    return Ok(Reply::then(After::Entry));
+                }
+                let reason = self.session()?.debugger.resume();
+                Ok(Reply::then(After::Stop(reason)))
+            }
+            "threads" => Ok(Reply::body(json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] }))),
+            "stackTrace" => self.stack_trace().map(Reply::body),
+            "scopes" => self.scopes(arguments).map(Reply::body),
+            "variables" => self.variables(arguments).map(Reply::body),
+            "continue" => {
+                let reason = self.session()?.debugger.resume();
+                Ok(Reply {
+                    body: json!({ "allThreadsContinued": true }),
+                    after: After::Stop(reason),
+                })
+            }
+            "next" => {
+                let reason = self.session()?.debugger.step_line(true);
+                Ok(Reply::then(After::Stop(reason)))
+            }
+            "stepIn" => {
+                let reason = self.session()?.debugger.step_line(false);
+                Ok(Reply::then(After::Stop(reason)))
+            }
+            "stepOut" => {
+                let reason = self.session()?.debugger.step_out();
+                Ok(Reply::then(After::Stop(reason)))
+            }
+            "stepBack" | "reverseContinue" => {
+                let session = self.session()?;
+                let reason = if command == "stepBack" {
+                    session.debugger.step_back()?
+                } else {
+                    session.debugger.reverse_resume()?
+                };
+                session.trap_reported = false;
+                Ok(Reply::then(After::Stop(reason)))
+            }
+            // Execution only advances on request, so it is always paused already.
+            "pause" => Ok(Reply::body(Value::Null)),
+            "disconnect" | "terminate" => Ok(Reply::then(After::Disconnect)),
+            other => Err(anyhow!("Unsupported request '{}'", other)),
+        }
+    }
+
+    fn session(&mut self) -> Result<&mut Session> {
+        self.session.as_mut().ok_or_else(|| anyhow!("No program has been launched"))
+    }
+
+    /// Launches `function` of `program`, or replays a `recording` file made by `stylus run --record`.
+    /// Returns whether the session replays a recording.
+    fn launch(&mut self, arguments: &Value) -> Result<bool> {
+        let stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or(false);
+        if let Some(recording) = arguments["recording"].as_str() {
+            let debugger = Recording::load(recording)?.replay()?;
+            self.start(debugger, stop_on_entry);
+            return Ok(true);
+        }
+
+        let program = match arguments["program"].as_str() {
+            Some(program) => PathBuf::from(program),
+            None => self.wasm_path.clone(),
+        };
+        let function = arguments["function"].as_str().unwrap_or("user_main");
+        let args: Vec<i64> = arguments["args"]
+            .as_array()
+            .map(|args| args.iter().filter_map(Value::as_i64).collect())
+            .unwrap_or_default();
+
+        let mut runner = StylusRunner::from_file(find_wasm(&program)?)?;
+        let debugger = runner.runtime_mut().debug(function, &args)?;
+        self.start(debugger, stop_on_entry);
+        Ok(false)
+    }
+
+    fn start(&mut self, debugger: Debugger, stop_on_entry: bool) {
+        self.session = Some(Session {
+            debugger,
+            stop_on_entry,
+            source_breakpoints: HashMap::new(),
+            function_breakpoints: Vec::new(),
+            frames: Vec::new(),
+            trap_reported: false,
+        });
+    }
+
+    fn set_breakpoints(&mut self, arguments: &Value) -> Result<Value> {
+        let path = arguments["source"]["path"]
+            .as_str()
+            .ok_or_else(|| anyhow!("setBreakpoints needs a source path"))?
+            .to_string();
+        let session = self.session()?;
+        for id in session.source_breakpoints.remove(&path).unwrap_or_default() {
+            session.debugger.remove_breakpoint(id);
+        }
+
+        let mut ids = Vec::new();
+        let mut breakpoints = Vec::new();
+        for requested in arguments["breakpoints"].as_array().into_iter().flatten() {
+            let line = requested["line"].as_u64().unwrap_or(0) as u32;
+            match session.debugger.add_line_breakpoint(&path, line) {
+                Ok((resolved, added)) => {
+                    breakpoints.push(json!({ "id": added[0], "verified": true, "line": resolved }));
+                    ids.extend(added);
+                }
+                Err(e) => breakpoints.push(json!({ "verified": false, "line": line, "message": e.to_string() })),
+            }
+        }
+        session.source_breakpoints.insert(path, ids);
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    fn set_function_breakpoints(&mut self, arguments: &Value) -> Result<Value> {
+        let session = self.session()?;
+        for id in std::mem::take(&mut session.function_breakpoints) {
+            session.debugger.remove_breakpoint(id);
+        }
+
+        let mut breakpoints = Vec::new();
+        for requested in arguments["breakpoints"].as_array().into_iter().flatten() {
+            let name = requested["name"].as_str().unwrap_or_default();
+            let added = session
+                .debugger
+                .function_index(name)
+                .ok_or_else(|| anyhow!("Function '{}' not found", name))
+                .and_then(|index| session.debugger.add_breakpoint(Breakpoint::Function(index)));
+            match added {
+                Ok(id) => {
+                    session.function_breakpoints.push(id);
+                    breakpoints.push(json!({ "id": id, "verified": true }));
+                }
+                Err(e) => breakpoints.push(json!({ "verified": false, "message": e.to_string() })),
+            }
+        }
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    fn stack_trace(&mut self) -> Result<Value> {
+        let session = self.session()?;
+        session.frames.clear();
+        let mut frames = Vec::new();
+        for (wasm_frame, frame) in session.debugger.backtrace().frames.iter().enumerate() {
+            let name = frame
+                .function
+                .clone()
+                .unwrap_or_else(|| format!("<func {}>", frame.func_index));
+            let pointer = format!("0x{:x}", frame.code_offset.unwrap_or(frame.module_offset));
+            if frame.source.is_empty() {
+                frames.push(json!({
+                    "id": session.frames.len(),
+                    "name": name,
+                    "line": 0,
+                    "column": 0,
+                    "instructionPointerReference": pointer,
+                }));
+                session.frames.push(wasm_frame);
+            }
+            // Inlined functions get frames of their own, all sharing the wasm frame's locals.
+            for source in &frame.source {
+                let mut entry = json!({
+                    "id": session.frames.len(),
+                    "name": source.function.clone().unwrap_or_else(|| name.clone()),
+                    "line": 0,
+                    "column": 0,
+                    "instructionPointerReference": pointer,
+                });
+                if let Some(location) = &source.location {
+                    let file = Path::new(&location.file);
+                    entry["source"] = json!({
+                        "name": file.file_name().map(|name| name.to_string_lossy()).unwrap_or_default(),
+                        "path": location.file,
+                    });
+                    entry["line"] = json!(location.line);
+                    entry["column"] = json!(location.column.max(1));
+                }
+                frames.push(entry);
+                session.frames.push(wasm_frame);
+            }
+        }
+        Ok(json!({ "stackFrames": frames, "totalFrames": frames.len() }))
+    }
+
+    fn scopes(&mut self, arguments: &Value) -> Result<Value> {
+        let frame_id = arguments["frameId"].as_u64().unwrap_or(0) as usize;
+        let session = self.session()?;
+        let wasm_frame = session.frames.get(frame_id).copied().unwrap_or(0);
+        let mut scopes = vec![json!({
+            "name": "Locals",
+            "variablesReference": LOCALS_REFERENCE + wasm_frame as i64,
+            "expensive": false,
+        })];
+        if wasm_frame == 0 {
+            scopes.push(json!({ "name": "Stack", "variablesReference": STACK_REFERENCE, "expensive": false }));
+        }
+        scopes.push(json!({ "name": "Globals", "variablesReference": GLOBALS_REFERENCE, "expensive": false }));
+        Ok(json!({ "scopes": scopes }))
+    }
+
+    fn variables(&mut self, arguments: &Value) -> Result<Value> {
+        let reference = arguments["variablesReference"].as_i64().unwrap_or(0);
+        let debugger = &self.session()?.debugger;
+        let (prefix, values): (&str, Vec<Val>) = match reference {
+            GLOBALS_REFERENCE => ("global", debugger.globals().to_vec()),
+            STACK_REFERENCE => ("stack", debugger.stack().iter().rev().copied().collect()),
+            LOCALS_REFERENCE.. => {
+                let frames = debugger.machine().frames();
+                let depth = (reference - LOCALS_REFERENCE) as usize;
+                let locals = frames
+                    .len()
+                    .checked_sub(depth + 1)
+                    .map(|index| frames[index].locals.clone())
+                    .unwrap_or_default();
+                ("$", locals)
+            }
+            _ => return Err(anyhow!("Unknown variables reference {}", reference)),
+        };
+        let variables: Vec<Value> = values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| {
+                let name = if prefix == "$" { format!("${}", index) } else { format!("{}[{}]", prefix, index) };
+                json!({ "name": name, "value": value.to_string(), "type": value.type_name(), "variablesReference": 0 })
+            })
+            .collect();
+        Ok(json!({ "variables": variables }))
+    }
+
+    fn report(&mut self, reason: StopReason) -> Result<()> {
+        match reason {
+            StopReason::Breakpoint(id) => self.stopped("breakpoint", None, json!({ "hitBreakpointIds": [id] })),
+            StopReason::Step => self.stopped("step", None, json!({})),
+            StopReason::Finished(results) => {
+                self.print_console()?;
+                let results: Vec<String> = results.iter().map(ToString::to_string).collect();
+                let gas = self.session()?.debugger.gas_used();
+                self.output("console", &format!("Returned [{}] (gas used: {})\n", results.join(", "), gas))?;
+                self.event("exited", json!({ "exitCode": 0 }))?;
+                self.event("terminated", json!({}))
+            }
+            StopReason::Trapped(trap) => self.trapped(trap),
+        }
+    }
+
+    /// Pauses on the first report of a trap so its state can be inspected; ends the session after.
+    fn trapped(&mut self, trap: Trap) -> Result<()> {
+        let session = self.session()?;
+        if session.trap_reported {
+            self.event("exited", json!({ "exitCode": 1 }))?;
+            return self.event("terminated", json!({}));
+        }
+        session.trap_reported = true;
+        self.print_console()?;
+        self.output("stderr", &format!("{}\n", trap))?;
+        self.stopped("exception", Some(&trap.message), json!({}))
+    }
+
+    fn print_console(&mut self) -> Result<()> {
+        let lines: Vec<String> = self.session()?.debugger.console().iter().map(ToString::to_string).collect();
+        for line in lines {
+            self.output("stdout", &format!("{}\n", line))?;
+        }
+        Ok(())
+    }
+
+    fn stopped(&mut self, reason: &str, text: Option<&str>, mut body: Value) -> Result<()> {
+        body["reason"] = json!(reason);
+        body["threadId"] = json!(THREAD_ID);
+        body["allThreadsStopped"] = json!(true);
+        if let Some(text) = text {
+            body["text"] = json!(text);
+            body["description"] = json!(text);
+        }
+        self.event("stopped", body)
+    }
+
+    fn output(&mut self, category: &str, text: &str) -> Result<()> {
+        self.event("output", json!({ "category": category, "output": text }))
+    }
+
+    fn respond(&mut self, request_seq: i64, command: &str, success: bool, message: Option<String>, body: Value) -> Result<()> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request_seq,
+            "command": command,
+            "success": success,
+        });
+        if let Some(message) = message {
+            response["message"] = json!(message);
+        }
+        if !body.is_null() {
+            response["body"] = body;
+        }
+        self.send(response)
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    fn send(&mut self, mut message: Value) -> Result<()> {
+        message["seq"] = json!(self.seq);
+        self.seq += 1;
+        let payload = serde_json::to_vec(&message)?;
+        write!(self.output, "Content-Length: {}\r\n\r\n", payload.len())?;
+        self.output.write_all(&payload)?;
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+/// What to send once a request's response is out.
+enum After {
+    Nothing,
+    /// Announces `supportsStepBack` first when the launched session can step backwards.
+    Initialized { step_back: bool },
+    Entry,
+    Stop(StopReason),
+    Disconnect,
+}
+
+struct Reply {
+    body: Value,
+    after: After,
+}
+
+impl Reply {
+    fn body(body: Value) -> Self {
+        Self { body, after: After::Nothing }
+    }
+
+    fn then(after: After) -> Self {
+        Self { body: Value::Null, after }
+    }
+}
+
+/// Reads one `Content-Length` framed message, or `None` at end of input.
+fn read_message<R: BufRead>(input: &mut R) -> Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            if length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = Some(value.trim().parse::<usize>()?);
+        }
+    }
+    let mut payload = vec![0u8; length.unwrap_or(0)];
+    input.read_exact(&mut payload)?;
+    Ok(Some(serde_json::from_slice(&payload)?))
+}
+
+/// A `.wasm` file itself, or the first one in a directory.
+fn find_wasm(path: &Path) -> Result<PathBuf> {
+    if path.is_file() {
+        return Ok(path.to_path_buf());
+    }
+    std::fs::read_dir(path)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| path.extension().and_then(|ext| ext.to_str()) == Some("wasm"))
+        .ok_or_else(|| anyhow!("No WASM files found in {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gimli::write::{Address, DwarfUnit, EndianVec, LineProgram, LineString, Sections};
+    use gimli::LittleEndian;
+    use stylus_core::StylusRuntime;
+
+    const WAT: &str = r#"
+        (module
+            (func $double (param $x i64) (result i64)
+                (i64.add (local.get $x) (local.get $x)))
+            (func (export "run") (param $n i64) (result i64)
+                (i64.add (call $double (local.get $n)) (i64.const 1)))
+        )
+    "#;
+
+    fn push_leb128(out: &mut Vec<u8>, mut value: usize) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                out.push(byte);
+                return;
+            }
+            out.push(byte | 0x80);
+        }
+    }
+
+    /// `WAT` with a line table for `/work/src/lib.rs` that puts `double` on line 3
+    /// and each instruction of `run` on its own line from 10.
+    fn program_with_lines() -> Vec<u8> {
+        let mut wasm = wat::parse_str(WAT).unwrap();
+        let mut debugger = StylusRuntime::new(&wasm).unwrap().debug("run", &[1]).unwrap();
+        let double = debugger.function_index("double").unwrap();
+        let mut rows = Vec::new();
+        let mut next_line = 10;
+        loop {
+            let location = debugger.location().unwrap();
+            let offset = location.code_offset.unwrap();
+            if !rows.iter().any(|&(seen, _)| seen == offset) {
+                let line = if location.func_index == double { 3 } else { next_line };
+                next_line += (location.func_index != double) as u64;
+                rows.push((offset, line));
+            }
+            if debugger.step_in() != StopReason::Step {
+                break;
+            }
+        }
+        rows.sort();
+
+        let encoding = gimli::Encoding { format: gimli::Format::Dwarf32, version: 4, address_size: 4 };
+        let mut dwarf = DwarfUnit::new(encoding);
+        let mut program = LineProgram::new(
+            encoding,
+            gimli::LineEncoding::default(),
+            LineString::String(b"/work".to_vec()),
+            LineString::String(b"src/lib.rs".to_vec()),
+            None,
+        );
+        let file = program.add_file(LineString::String(b"src/lib.rs".to_vec()), program.default_directory(), None);
+        let start = rows[0].0;
+        program.begin_sequence(Some(Address::Constant(start)));
+        for &(offset, line) in &rows {
+            program.row().address_offset = offset - start;
+            program.row().file = file;
+            program.row().line = line;
+            program.generate_row();
+        }
+        program.end_sequence(rows.last().unwrap().0 + 1 - start);
+        dwarf.unit.line_program = program;
+        let root = dwarf.unit.root();
+        dwarf.unit.get_mut(root).set(gimli::DW_AT_comp_dir, gimli::write::AttributeValue::String(b"/work".to_vec()));
+
+        let mut sections = Sections::new(EndianVec::new(LittleEndian));
+        dwarf.write(&mut sections).unwrap();
+        sections
+            .for_each(|id, data| {
+                let mut payload = Vec::new();
+                push_leb128(&mut payload, id.name().len());
+                payload.extend_from_slice(id.name().as_bytes());
+                payload.extend_from_slice(data.slice());
+                wasm.push(0);
+                push_leb128(&mut wasm, payload.len());
+                wasm.extend_from_slice(&payload);
+                Ok::<(), gimli::Error>(())
+            })
+            .unwrap();
+        wasm
+    }
+
+    fn request(seq: usize, command: &str, arguments: Value) -> Vec<u8> {
+        let message = json!({ "seq": seq, "type": "request", "command": command, "arguments": arguments });
+        let payload = serde_json::to_vec(&message).unwrap();
+        let mut framed = format!("Content-Length: {}\r\n\r\n", payload.len()).into_bytes();
+        framed.extend_from_slice(&payload);
+        framed
+    }
+
+    /// Runs `requests` through a server and returns everything it sent back.
+    fn exchange(requests: &[(&str, Value)]) -> Vec<Value> {
+        let input: Vec<u8> = requests
+            .iter()
+            .enumerate()
+            .flat_map(|(seq, (command, arguments))| request(seq + 1, command, arguments.clone()))
+            .collect();
+        let mut server = Server::new(Vec::new(), PathBuf::new());
+        server.serve(input.as_slice()).unwrap();
+
+        let mut output = server.output.as_slice();
+        std::iter::from_fn(|| read_message(&mut output).unwrap()).collect()
+    }
+
+    fn response<'a>(messages: &'a [Value], command: &str) -> &'a Value {
+        messages
+            .iter()
+            .find(|message| message["type"] == "response" && message["command"] == command)
+            .unwrap_or_else(|| panic!("no response to {}", command))
+    }
+
+    fn events(messages: &[Value]) -> Vec<&str> {
+        messages.iter().filter_map(|message| message["event"].as_str()).collect()
+    }
+
+    #[test]
+    fn test_session_stops_at_a_line_breakpoint_and_runs_to_the_end() {
+        let path = std::env::temp_dir().join("stylus-dap-test.wasm");
+        std::fs::write(&path, program_with_lines()).unwrap();
+        let messages = exchange(&[
+            ("initialize", json!({ "adapterID": "stylus" })),
+            ("launch", json!({ "program": path, "function": "run", "args": [5] })),
+            ("setBreakpoints", json!({ "source": { "path": "/work/src/lib.rs" }, "breakpoints": [{ "line": 3 }] })),
+            ("configurationDone", json!({})),
+            ("stackTrace", json!({ "threadId": THREAD_ID })),
+            ("scopes", json!({ "frameId": 0 })),
+            ("variables", json!({ "variablesReference": LOCALS_REFERENCE })),
+            ("variables", json!({ "variablesReference": 999 })),
+            ("continue", json!({ "threadId": THREAD_ID })),
+            ("disconnect", json!({})),
+        ]);
+        std::fs::remove_file(&path).ok();
+
+        let capabilities = &response(&messages, "initialize")["body"];
+        assert_eq!(capabilities["supportsConfigurationDoneRequest"], true);
+        assert!(capabilities.get("supportsStepBack").is_none());
+
+        let breakpoints = &response(&messages, "setBreakpoints")["body"]["breakpoints"];
+        assert_eq!((breakpoints[0]["verified"].clone(), breakpoints[0]["line"].clone()), (json!(true), json!(3)));
+        let id = breakpoints[0]["id"].clone();
+
+        let stopped = messages.iter().find(|message| message["event"] == "stopped").unwrap();
+        assert_eq!(stopped["body"]["reason"], "breakpoint");
+        assert_eq!(stopped["body"]["hitBreakpointIds"], json!([id]));
+
+        let frames = &response(&messages, "stackTrace")["body"]["stackFrames"];
+        assert_eq!(frames.as_array().unwrap().len(), 2);
+        assert_eq!(frames[0]["name"], "double");
+        assert_eq!(frames[0]["line"], 3);
+        assert_eq!(frames[0]["source"]["path"], "/work/src/lib.rs");
+        assert_eq!(frames[1]["line"], 11);
+
+        let scopes = &response(&messages, "scopes")["body"]["scopes"];
+        assert_eq!(scopes[0]["variablesReference"], LOCALS_REFERENCE);
+        let variables = messages
+            .iter()
+            .filter(|message| message["command"] == "variables")
+            .collect::<Vec<_>>();
+        assert_eq!(variables[0]["body"]["variables"], json!([{ "name": "$0", "value": "5", "type": "i64", "variablesReference": 0 }]));
+        assert_eq!(variables[1]["success"], false);
+        assert_eq!(variables[1]["message"], "Unknown variables reference 999");
+
+        assert_eq!(response(&messages, "continue")["success"], true);
+        let events = events(&messages);
+        assert_eq!(events, ["initialized", "stopped", "output", "exited", "terminated"]);
+        let output = messages.iter().find(|message| message["event"] == "output").unwrap();
+        assert!(output["body"]["output"].as_str().unwrap().starts_with("Returned [11] (gas used: "));
+    }
+
+    #[test]
+    fn test_only_recordings_offer_step_back() {
+        let wasm = wat::parse_str(WAT).unwrap();
+        let mut runtime = StylusRuntime::new(&wasm).unwrap();
+        runtime.enable_recording(true);
+        runtime.execute_function("run", &[5]).unwrap();
+        let path = std::env::temp_dir().join("stylus-dap-test.recording.json");
+        runtime.take_recording().unwrap().save(&path).unwrap();
+
+        let messages = exchange(&[
+            ("initialize", json!({})),
+            ("launch", json!({ "recording": path, "stopOnEntry": true })),
+            ("configurationDone", json!({})),
+            ("next", json!({ "threadId": THREAD_ID })),
+            ("stepBack", json!({ "threadId": THREAD_ID })),
+            ("disconnect", json!({})),
+        ]);
+        std::fs::remove_file(&path).ok();
+
+        assert!(response(&messages, "initialize")["body"].get("supportsStepBack").is_none());
+        let capabilities = messages.iter().find(|message| message["event"] == "capabilities").unwrap();
+        assert_eq!(capabilities["body"]["capabilities"]["supportsStepBack"], true);
+        assert_eq!(response(&messages, "stepBack")["success"], true);
+        assert_eq!(events(&messages), ["capabilities", "initialized", "stopped", "stopped", "stopped"]);
+    }
+}
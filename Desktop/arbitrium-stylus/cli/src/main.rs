@@ -1,10 +1,20 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 use serde_json;
+use std::collections::HashMap;
 use std::path::PathBuf;
+use stylus_core::{
+    AnnotatedLine, ConsoleLine, CoverageReport, FuzzConfig, FuzzTarget, Fuzzer, Invariant, ProfileFormat, Recording,
+    SourceAnnotation, StubPolicy, Trap,
+};
 use stylus_harness::{StylusRunner, TestSuite};
+use trace::TracePrinter;
 use tracing::{info, error};
 
+mod dap;
+mod repl;
+mod trace;
+
 #[derive(Parser)]
 #[command(name = "stylus")]
 #[command(about = "Arbitrum Stylus IDE - Development toolkit for Stylus smart contracts")]
@@ -31,6 +41,21 @@ enum Commands {
         wasm_path: PathBuf,
         #[arg(long)]
         json: bool,
+        /// How to link imports the runtime does not provide: fail, trap or zero
+        #[arg(long, default_value = "fail")]
+        stub_policy: StubPolicy,
+        /// Print each test's call tree; repeat for events and storage writes (-vv) or every host call (-vvv)
+        #[arg(short, long, action = clap::ArgAction::Count)]
+        verbose: u8,
+        /// Collect instruction, branch and line coverage, written as lcov.info and index.html
+        #[arg(long)]
+        coverage: bool,
+        /// Directory for the coverage reports
+        #[arg(long, default_value = "coverage", requires = "coverage")]
+        coverage_dir: PathBuf,
+        /// Fail if line coverage (instruction coverage without debug info) is below this percentage
+        #[arg(long, requires = "coverage")]
+        coverage_threshold: Option<f64>,
     },
     /// Run a specific function
     Run {
@@ -39,8 +64,20 @@ enum Commands {
         wasm_path: PathBuf,
         #[arg(short, long)]
         args: Vec<i64>,
+        /// How to link imports the runtime does not provide: fail, trap or zero
+        #[arg(long, default_value = "fail")]
+        stub_policy: StubPolicy,
+        /// Record the execution to this file for `stylus replay`
+        #[arg(long)]
+        record: Option<PathBuf>,
+        /// Print every host call with its decoded arguments, results and gas
+        #[arg(long)]
+        trace_host: bool,
+        /// Print the call tree; repeat for events and storage writes (-vv) or every host call (-vvv)
+        #[arg(short, long, action = clap::ArgAction::Count)]
+        verbose: u8,
     },
-    /// Debug a function with step-by-step execution
+    /// Debug a function interactively with breakpoints and stepping
     Debug {
         function: String,
         #[arg(short, long, default_value = "target/wasm32-wasi/release")]
@@ -48,6 +85,19 @@ enum Commands {
         #[arg(short, long)]
         args: Vec<i64>,
     },
+    /// Step through a recorded execution, forwards and backwards
+    Replay {
+        recording: PathBuf,
+    },
+    /// Serve the Debug Adapter Protocol for editors, over stdio or TCP
+    Dap {
+        /// Listen for one client on this TCP port instead of using stdio
+        #[arg(long)]
+        port: Option<u16>,
+        /// Contract to debug when the launch request does not name a program
+        #[arg(short, long, default_value = "target/wasm32-wasi/release")]
+        wasm_path: PathBuf,
+    },
     /// Profile gas usage of functions
     Profile {
         function: String,
@@ -55,6 +105,43 @@ enum Commands {
         wasm_path: PathBuf,
         #[arg(short, long)]
         args: Vec<i64>,
+        /// Export the call stacks instead of printing a summary: folded, speedscope, pprof or svg
+        #[arg(long)]
+        format: Option<ProfileFormat>,
+        /// Print each source file with the gas spent on every line
+        #[arg(long, conflicts_with = "format")]
+        annotate: bool,
+        /// Write the export, or the per-line gas as JSON with --annotate, to this file
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Fuzz a function's arguments, or calldata sent to user_entrypoint, guided by coverage
+    Fuzz {
+        /// Export to call with fuzzed i64 arguments; fuzzes calldata to user_entrypoint if omitted
+        function: Option<String>,
+        #[arg(short, long, default_value = "target/wasm32-wasi/release")]
+        wasm_path: PathBuf,
+        /// Inputs to generate
+        #[arg(long, default_value_t = 10_000)]
+        runs: u64,
+        /// Seed for generating inputs; random if omitted
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Longest calldata to generate
+        #[arg(long, default_value_t = 256)]
+        max_len: usize,
+        /// Gas each input may use before it counts as out of gas
+        #[arg(long)]
+        gas_limit: Option<u64>,
+        /// Export checked after every input, which must return non-zero; defaults to all invariant_* exports
+        #[arg(long = "invariant")]
+        invariants: Vec<String>,
+        /// Directory for the corpus and the crashing inputs with their reproducing tests
+        #[arg(long, default_value = "fuzz")]
+        dir: PathBuf,
+        /// How to link imports the runtime does not provide: fail, trap or zero
+        #[arg(long, default_value = "fail")]
+        stub_policy: StubPolicy,
     },
     /// Setup CI configuration
     CiSetup,
@@ -62,9 +149,16 @@ enum Commands {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
-    
     let cli = Cli::parse();
+    if matches!(
+        cli.command,
+        Commands::Dap { port: None, .. } | Commands::Profile { format: Some(_), output: None, .. }
+    ) {
+        // stdout carries the protocol or the export, so logs must go elsewhere.
+        tracing_subscriber::fmt().with_writer(std::io::stderr).init();
+    } else {
+        tracing_subscriber::fmt::init();
+    }
     
     match cli.command {
         Commands::Init { name } => {
@@ -73,17 +167,31 @@ async fn main() -> Result<()> {
         Commands::Build { path } => {
             build_project(&path)?;
         }
-        Commands::Test { wasm_path, json } => {
-            run_tests(&wasm_path, json).await?;
+        Commands::Test { wasm_path, json, stub_policy, verbose, coverage, coverage_dir, coverage_threshold } => {
+            let coverage = coverage.then_some(CoverageOptions { dir: coverage_dir, threshold: coverage_threshold });
+            run_tests(&wasm_path, json, stub_policy, verbose, coverage.as_ref()).await?;
         }
-        Commands::Run { function, wasm_path, args } => {
-            run_function(&function, &wasm_path, &args).await?;
+        Commands::Run { function, wasm_path, args, stub_policy, record, trace_host, verbose } => {
+            run_function(&function, &wasm_path, &args, stub_policy, record.as_ref(), trace_host, verbose).await?;
         }
         Commands::Debug { function, wasm_path, args } => {
             debug_function(&function, &wasm_path, &args).await?;
         }
-        Commands::Profile { function, wasm_path, args } => {
-            profile_function(&function, &wasm_path, &args).await?;
+        Commands::Replay { recording } => {
+            replay_recording(&recording)?;
+        }
+        Commands::Dap { port, wasm_path } => {
+            serve_dap(port, wasm_path)?;
+        }
+        Commands::Profile { function, wasm_path, args, format, annotate, output } => {
+            profile_function(&function, &wasm_path, &args, format, annotate, output.as_ref()).await?;
+        }
+        Commands::Fuzz { function, wasm_path, runs, seed, max_len, gas_limit, invariants, dir, stub_policy } => {
+            let seed = seed.unwrap_or_else(|| {
+                std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |time| time.as_nanos() as u64)
+            });
+            let config = FuzzConfig { runs, seed, max_len, corpus_dir: None };
+            fuzz(function, &wasm_path, config, gas_limit, invariants, &dir, stub_policy)?;
         }
         Commands::CiSetup => {
             setup_ci()?;
@@ -161,7 +269,19 @@ fn build_project(path: &PathBuf) -> Result<()> {
     Ok(())
 }
 
-async fn run_tests(wasm_path: &PathBuf, json_output: bool) -> Result<()> {
+/// Where `stylus test --coverage` writes its reports, and the coverage it must reach.
+struct CoverageOptions {
+    dir: PathBuf,
+    threshold: Option<f64>,
+}
+
+async fn run_tests(
+    wasm_path: &PathBuf,
+    json_output: bool,
+    stub_policy: StubPolicy,
+    verbose: u8,
+    coverage: Option<&CoverageOptions>,
+) -> Result<()> {
     info!("Running tests for WASM at: {:?}", wasm_path);
     
     // Look for .wasm files in the directory
@@ -188,11 +308,17 @@ async fn run_tests(wasm_path: &PathBuf, json_output: bool) -> Result<()> {
         
         match StylusRunner::from_file(&path) {
             Ok(mut runner) => {
+                runner.set_stub_policy(stub_policy);
+                label_contract(&mut runner, &path);
+                if coverage.is_some() {
+                    runner.enable_coverage();
+                }
+                
                 // Run basic tests
                 runner.assert_eq("basic_test", "user_main", &[], 0);
                 
                 let suite = runner.finalize_suite(&path.file_stem().unwrap().to_string_lossy());
-                all_suites.push(suite);
+                all_suites.push((suite, runner));
             }
             Err(e) => {
                 error!("Failed to load WASM file {:?}: {}", path, e);
@@ -201,9 +327,10 @@ async fn run_tests(wasm_path: &PathBuf, json_output: bool) -> Result<()> {
     }
     
     if json_output {
-        println!("{}", serde_json::to_string_pretty(&all_suites)?);
+        let suites: Vec<&TestSuite> = all_suites.iter().map(|(suite, _)| suite).collect();
+        println!("{}", serde_json::to_string_pretty(&suites)?);
     } else {
-        for suite in &all_suites {
+        for (suite, runner) in &mut all_suites {
             println!("\n=== Test Suite: {} ===", suite.name);
             println!("Passed: {}, Failed: {}", suite.passed, suite.failed);
             println!("Total Gas Used: {}", suite.total_gas);
@@ -211,20 +338,71 @@ async fn run_tests(wasm_path: &PathBuf, json_output: bool) -> Result<()> {
             for test in &suite.tests {
                 let status = if test.passed { "✓" } else { "✗" };
                 println!("  {} {}", status, test.name);
+                for line in &test.console {
+                    println!("    {}", line);
+                }
                 if let Some(error) = &test.error {
-                    println!("    Error: {}", error);
+                    println!("    Error: {}", error.replace('\n', "\n    "));
                 }
                 if let Some(result) = &test.execution_result {
-                    println!("    Gas: {}, Return: {}", result.gas_used, result.return_value);
+                    println!("    Gas: {} (L1: {}), Return: {}", result.gas_used, result.l1_cost.l2_gas, result.return_value);
+                    if verbose > 0 {
+                        let runtime = runner.runtime_mut();
+                        let address = runtime.context().contract_address;
+                        TracePrinter::new(&runtime.world(), verbose).print(&mut std::io::stdout(), &address, &test.function, &test.args, result)?;
+                    }
                 }
             }
         }
     }
+
+    if let Some(options) = coverage {
+        let mut report = CoverageReport::default();
+        for coverage in all_suites.iter().filter_map(|(suite, _)| suite.coverage.as_ref()) {
+            report.merge(coverage);
+        }
+        write_coverage(&report, options, !json_output)?;
+    }
     
     Ok(())
 }
 
-async fn run_function(function: &str, wasm_path: &PathBuf, args: &[i64]) -> Result<()> {
+/// Writes the lcov and HTML reports, optionally printing totals, and enforces the threshold.
+fn write_coverage(report: &CoverageReport, options: &CoverageOptions, print: bool) -> Result<()> {
+    std::fs::create_dir_all(&options.dir)
+        .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", options.dir.display(), e))?;
+    for (name, contents) in [("lcov.info", report.to_lcov()), ("index.html", report.to_html())] {
+        let path = options.dir.join(name);
+        std::fs::write(&path, contents).map_err(|e| anyhow::anyhow!("Failed to write {}: {}", path.display(), e))?;
+    }
+
+    if print {
+        println!("\n=== Coverage ===");
+        for (kind, count) in [("Lines", report.lines()), ("Branches", report.branches()), ("Instructions", report.instructions())] {
+            println!("  {:<13} {:>6.1}% ({}/{})", kind, count.percent(), count.covered, count.total);
+        }
+        println!("Wrote coverage reports to {}", options.dir.display());
+    }
+
+    match options.threshold {
+        Some(threshold) if report.percent() < threshold => Err(anyhow::anyhow!(
+            "Coverage {:.1}% is below the {:.1}% threshold",
+            report.percent(),
+            threshold
+        )),
+        _ => Ok(()),
+    }
+}
+
+async fn run_function(
+    function: &str,
+    wasm_path: &PathBuf,
+    args: &[i64],
+    stub_policy: StubPolicy,
+    record: Option<&PathBuf>,
+    trace_host: bool,
+    verbose: u8,
+) -> Result<()> {
     info!("Running function '{}' with args: {:?}", function, args);
     
     let wasm_files: Vec<_> = std::fs::read_dir(wasm_path)?
@@ -240,8 +418,37 @@ async fn run_function(function: &str, wasm_path: &PathBuf, args: &[i64]) -> Resu
     
     if let Some(wasm_file) = wasm_files.first() {
         let mut runner = StylusRunner::from_file(wasm_file.path())?;
-        let result = runner.call(function, args)?;
-        println!("Result: {}", result);
+        runner.set_stub_policy(stub_policy);
+        label_contract(&mut runner, &wasm_file.path());
+        runner.runtime_mut().enable_recording(record.is_some());
+        let outcome = runner.execute(function, args);
+        if let (Some(path), Some(recording)) = (record, runner.runtime_mut().take_recording()) {
+            recording.save(path)?;
+            info!("Recorded {} steps to {:?}", recording.steps(), path);
+        }
+        match outcome {
+            Ok(result) => {
+                print_console(&result.console);
+                if trace_host {
+                    println!("Host calls:");
+                    for call in &result.host_calls {
+                        println!("{}{}", "  ".repeat(call.depth as usize), call);
+                    }
+                }
+                if verbose > 0 {
+                    let runtime = runner.runtime_mut();
+                    let address = runtime.context().contract_address;
+                    TracePrinter::new(&runtime.world(), verbose).print(&mut std::io::stdout(), &address, function, args, &result)?;
+                }
+                println!("Result: {}", result.return_value);
+            }
+            Err(e) => {
+                if let Some(trap) = e.downcast_ref::<Trap>() {
+                    print_console(&trap.console);
+                }
+                return Err(e);
+            }
+        }
     } else {
         error!("No WASM files found in {:?}", wasm_path);
     }
@@ -249,6 +456,21 @@ async fn run_function(function: &str, wasm_path: &PathBuf, args: &[i64]) -> Resu
     Ok(())
 }
 
+/// Names the contract under test after its wasm file in call traces.
+fn label_contract(runner: &mut StylusRunner, path: &std::path::Path) {
+    if let Some(name) = path.file_stem() {
+        let runtime = runner.runtime_mut();
+        let address = runtime.context().contract_address;
+        runtime.world().set_label(&address, &name.to_string_lossy());
+    }
+}
+
+fn print_console(lines: &[ConsoleLine]) {
+    for line in lines {
+        println!("{}", line);
+    }
+}
+
 async fn debug_function(function: &str, wasm_path: &PathBuf, args: &[i64]) -> Result<()> {
     info!("Debugging function '{}' with args: {:?}", function, args);
     
@@ -265,13 +487,8 @@ async fn debug_function(function: &str, wasm_path: &PathBuf, args: &[i64]) -> Re
     
     if let Some(wasm_file) = wasm_files.first() {
         let mut runner = StylusRunner::from_file(wasm_file.path())?;
-        let result = runner.call(function, args)?;
-        
-        println!("=== Debug Session ===");
-        println!("Function: {}", function);
-        println!("Arguments: {:?}", args);
-        println!("Result: {}", result);
-        println!("=== End Debug ===");
+        let mut debugger = runner.runtime_mut().debug(function, args)?;
+        repl::run(&mut debugger)?;
     } else {
         error!("No WASM files found in {:?}", wasm_path);
     }
@@ -279,7 +496,41 @@ async fn debug_function(function: &str, wasm_path: &PathBuf, args: &[i64]) -> Re
     Ok(())
 }
 
-async fn profile_function(function: &str, wasm_path: &PathBuf, args: &[i64]) -> Result<()> {
+fn replay_recording(path: &PathBuf) -> Result<()> {
+    info!("Replaying {:?}", path);
+    let recording = Recording::load(path)?;
+    let mut debugger = recording.replay()?;
+    repl::run(&mut debugger)
+}
+
+fn serve_dap(port: Option<u16>, wasm_path: PathBuf) -> Result<()> {
+    match port {
+        Some(port) => {
+            let listener = std::net::TcpListener::bind(("127.0.0.1", port))?;
+            info!("Debug adapter listening on 127.0.0.1:{}", port);
+            let (stream, peer) = listener.accept()?;
+            info!("Debug client connected from {}", peer);
+            let mut server = dap::Server::new(stream.try_clone()?, wasm_path);
+            server.serve(std::io::BufReader::new(stream))
+        }
+        None => {
+            let mut server = dap::Server::new(std::io::stdout(), wasm_path);
+            server.serve(std::io::stdin().lock())
+        }
+    }
+}
+
+async fn profile_function(
+    function: &str,
+    wasm_path: &PathBuf,
+    args: &[i64],
+    format: Option<ProfileFormat>,
+    annotate: bool,
+    output: Option<&PathBuf>,
+) -> Result<()> {
+    if format.is_none() && !annotate && output.is_some() {
+        return Err(anyhow::anyhow!("--output requires --format or --annotate"));
+    }
     info!("Profiling function '{}' with args: {:?}", function, args);
     
     let wasm_files: Vec<_> = std::fs::read_dir(wasm_path)?
@@ -296,12 +547,117 @@ async fn profile_function(function: &str, wasm_path: &PathBuf, args: &[i64]) ->
     if let Some(wasm_file) = wasm_files.first() {
         let mut runner = StylusRunner::from_file(wasm_file.path())?;
         let profile = runner.profile_function(function, args)?;
-        
+
+        if let Some(format) = format {
+            let exported = stylus_core::profile::export::export(&profile, format);
+            match output {
+                Some(path) => {
+                    std::fs::write(path, exported)
+                        .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", path.display(), e))?;
+                    println!("Wrote profile to {}", path.display());
+                }
+                None => std::io::Write::write_all(&mut std::io::stdout(), &exported)?,
+            }
+            return Ok(());
+        }
+        if annotate {
+            let annotation = SourceAnnotation::new(&profile);
+            print_annotation(&annotation);
+            if let Some(path) = output {
+                std::fs::write(path, annotation.to_json())
+                    .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", path.display(), e))?;
+                println!("Wrote line gas to {}", path.display());
+            }
+            return Ok(());
+        }
+
         println!("=== Gas Profile ===");
         println!("Function: {}", profile.function_name);
         println!("Gas Used: {}", profile.gas_used);
         println!("Instructions: {}", profile.instruction_count);
         println!("Call Depth: {}", profile.call_depth);
+
+        let breakdown = &profile.breakdown;
+        println!("\nGas breakdown:");
+        for (part, gas) in [
+            ("compute", breakdown.compute),
+            ("memory growth", breakdown.memory_growth),
+            ("host I/O", breakdown.host_io),
+        ] {
+            println!("  {:<14} {:>12} gas {:>6.1}%", part, gas, percent(gas, profile.gas_used));
+        }
+        if !breakdown.host_functions.is_empty() {
+            println!("\nHost I/O by function:");
+            for host in &breakdown.host_functions {
+                println!(
+                    "  {:<32} {:>8} calls {:>12} gas {:>6.1}%",
+                    format!("{}::{}", host.module, host.name),
+                    host.calls,
+                    host.gas,
+                    percent(host.gas, profile.gas_used)
+                );
+            }
+        }
+
+        println!("\nBy class:");
+        for class in &profile.classes {
+            println!(
+                "  {:<12} {:>10} instrs {:>12} gas {:>6.1}%",
+                class.class.name(),
+                class.count,
+                class.gas,
+                percent(class.gas, profile.gas_used)
+            );
+        }
+        println!("\nBy function:");
+        println!("  {:<32} {:>8} {:>12} {:>12} {:>8}", "function", "calls", "inclusive", "exclusive", "self %");
+        for function in &profile.functions {
+            println!(
+                "  {:<32} {:>8} {:>12} {:>12} {:>7.1}%",
+                function.name,
+                function.calls,
+                function.inclusive_gas,
+                function.exclusive_gas,
+                percent(function.exclusive_gas, profile.gas_used)
+            );
+        }
+        if !profile.loops.is_empty() {
+            println!("\nHot loops:");
+            for hot in profile.loops.iter().take(HOT_PATHS) {
+                println!(
+                    "  {:<40} {:>6} entries {:>10} iterations {:>12} gas {:>6.1}%  {}",
+                    format!("{} @ {:#x}", hot.function, hot.offset),
+                    hot.entries,
+                    hot.iterations,
+                    hot.gas,
+                    percent(hot.gas, profile.gas_used),
+                    hot.location.as_ref().map(ToString::to_string).unwrap_or_default()
+                );
+            }
+        }
+        println!("\nHot blocks:");
+        for block in profile.blocks.iter().take(HOT_PATHS) {
+            println!(
+                "  {:<40} {:>4} instrs {:>10} runs {:>12} gas {:>6.1}%  {}",
+                format!("{} @ {:#x}", block.function, block.offset),
+                block.length,
+                block.count,
+                block.gas,
+                percent(block.gas, profile.gas_used),
+                block.location.as_ref().map(ToString::to_string).unwrap_or_default()
+            );
+        }
+        println!("\nTop opcodes:");
+        for opcode in profile.opcodes.iter().take(TOP_OPCODES) {
+            println!(
+                "  {:<20} {:>10} instrs {:>12} gas {:>6.1}%  ({})",
+                opcode.opcode,
+                opcode.count,
+                opcode.gas,
+                percent(opcode.gas, profile.gas_used),
+                opcode.class
+            );
+        }
         println!("=== End Profile ===");
     } else {
         error!("No WASM files found in {:?}", wasm_path);
@@ -310,6 +666,130 @@ async fn profile_function(function: &str, wasm_path: &PathBuf, args: &[i64]) ->
     Ok(())
 }
 
+/// Prints each profiled source file with gas and percentage columns beside every line.
+fn print_annotation(annotation: &SourceAnnotation) {
+    if annotation.files.is_empty() {
+        println!("No source lines executed; build the contract with debug info to annotate it.");
+        return;
+    }
+    for file in &annotation.files {
+        println!("=== {} ({} gas, {:.1}%) ===", file.path, file.gas, file.percent);
+        let hits: HashMap<u32, &AnnotatedLine> = file.lines.iter().map(|line| (line.line, line)).collect();
+        match std::fs::read_to_string(&file.path) {
+            Ok(source) => {
+                for (index, text) in source.lines().enumerate() {
+                    match hits.get(&(index as u32 + 1)) {
+                        Some(line) => println!("{:>10} {:>6.1}% {:>5} | {}", line.gas, line.percent, index + 1, text),
+                        None => println!("{:>10} {:>7} {:>5} | {}", "", "", index + 1, text),
+                    }
+                }
+            }
+            Err(_) => {
+                println!("  (source not found; listing executed lines only)");
+                for line in &file.lines {
+                    println!("{:>10} {:>6.1}% {:>5}", line.gas, line.percent, line.line);
+                }
+            }
+        }
+        println!();
+    }
+    if annotation.unattributed_gas > 0 {
+        println!(
+            "{} gas ({:.1}%) ran in code without line info",
+            annotation.unattributed_gas,
+            percent(annotation.unattributed_gas, annotation.gas_used)
+        );
+    }
+}
+
+fn fuzz(
+    function: Option<String>,
+    wasm_path: &PathBuf,
+    mut config: FuzzConfig,
+    gas_limit: Option<u64>,
+    mut invariants: Vec<String>,
+    dir: &std::path::Path,
+    stub_policy: StubPolicy,
+) -> Result<()> {
+    let wasm_file = std::fs::read_dir(wasm_path)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| path.extension().and_then(|ext| ext.to_str()) == Some("wasm"));
+    let Some(wasm_file) = wasm_file else {
+        error!("No WASM files found in {:?}", wasm_path);
+        return Ok(());
+    };
+
+    let mut runner = StylusRunner::from_file(&wasm_file)?;
+    runner.set_stub_policy(stub_policy);
+    let runtime = runner.runtime_mut();
+    if let Some(gas_limit) = gas_limit {
+        runtime.context_mut().gas_limit = gas_limit;
+    }
+    if invariants.is_empty() {
+        invariants = runtime.program()?.exports.keys().filter(|name| name.starts_with("invariant_")).cloned().collect();
+        invariants.sort();
+    }
+
+    let target = function.map_or(FuzzTarget::Entrypoint, FuzzTarget::Function);
+    config.corpus_dir = Some(dir.join("corpus").join(target.name()));
+    println!(
+        "Fuzzing {} in {} (seed {}, {} runs)",
+        target.name(),
+        wasm_file.display(),
+        config.seed,
+        config.runs
+    );
+    if !invariants.is_empty() {
+        println!("Invariants: {}", invariants.join(", "));
+    }
+
+    let mut fuzzer = Fuzzer::new(runtime, target.clone(), config)?;
+    for name in &invariants {
+        fuzzer.add_invariant(Invariant::export(name));
+    }
+    let report = fuzzer.run()?;
+
+    println!("\n=== Fuzz Report ===");
+    println!("Runs: {}, corpus: {} ({} new)", report.runs, report.corpus, report.new_inputs);
+    if let Some(coverage) = &report.coverage {
+        for (kind, count) in [("Branches", coverage.branches()), ("Instructions", coverage.instructions())] {
+            println!("  {:<13} {:>6.1}% ({}/{})", kind, count.percent(), count.covered, count.total);
+        }
+    }
+    if report.failures.is_empty() {
+        println!("No failures found");
+        return Ok(());
+    }
+
+    let crashes = dir.join("crashes");
+    std::fs::create_dir_all(&crashes).map_err(|e| anyhow::anyhow!("Failed to create {}: {}", crashes.display(), e))?;
+    let wasm_file = wasm_file.canonicalize().unwrap_or(wasm_file);
+    println!("Failures:");
+    for failure in &report.failures {
+        let name = format!("{}-{}-{}", target.name(), failure.kind.slug(), failure.input.id());
+        let json = crashes.join(format!("{}.json", name));
+        std::fs::write(&json, serde_json::to_string_pretty(failure)?)
+            .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", json.display(), e))?;
+        let test = crashes.join(format!("{}.rs", name.replace('-', "_")));
+        std::fs::write(&test, failure.to_test(&wasm_file, &invariants))
+            .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", test.display(), e))?;
+        println!("  ✗ {}", failure);
+        println!("    Reproduce with {}", test.display());
+    }
+    Err(anyhow::anyhow!("Fuzzing found {} failure(s)", report.failures.len()))
+}
+
+/// Opcodes listed by `stylus profile`, most gas first.
+const TOP_OPCODES: usize = 20;
+
+/// Loops and blocks listed by `stylus profile`, most gas first.
+const HOT_PATHS: usize = 10;
+
+fn percent(part: u64, total: u64) -> f64 {
+    if total == 0 { 0.0 } else { part as f64 * 100.0 / total as f64 }
+}
+
 fn setup_ci() -> Result<()> {
     info!("Setting up CI configuration");
     
@@ -0,0 +1,324 @@
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use stylus_core::{Breakpoint, Debugger, SourceLocation, StopReason};
+
+const HELP: &str = "\
+Commands:
+  break <fn|0xoffset>   set a breakpoint on a function or code offset (b)
+  delete <id>           remove a breakpoint
+  step                  execute one instruction, entering calls (s)
+  next                  execute one instruction, stepping over calls (n)
+  finish                run until the current function returns
+  continue              run until a breakpoint or the end of the call (c)
+  reverse-step          step back one instruction in a replay (rs)
+  reverse-continue      run backwards to the previous breakpoint in a replay (rc)
+  locals                show the current function's locals
+  stack                 show the current operand stack
+  mem <addr> <len>      dump linear memory
+  bt                    show the call stack
+  storage               show the contract's storage
+  gas                   show gas used so far
+  quit                  end the session (q)
+An empty line repeats the previous command.";
+
+/// Drives `debugger` from commands read on stdin until the user quits or input ends.
+pub fn run(debugger: &mut Debugger) -> Result<()> {
+    let stdin = io::stdin();
+    drive(debugger, stdin.lock(), &mut io::stdout())
+}
+
+/// Drives `debugger` from the commands in `input`, writing everything the session prints to `out`.
+fn drive<R: BufRead>(debugger: &mut Debugger, input: R, out: &mut impl Write) -> Result<()> {
+    let mut sources = SourceCache::default();
+    writeln!(out, "Type 'help' for a list of commands.")?;
+    print_location(debugger, &mut sources, out)?;
+
+    let mut lines = input.lines();
+    let mut previous = String::new();
+    loop {
+        write!(out, "(stylus) ")?;
+        out.flush()?;
+        let Some(line) = lines.next() else {
+            writeln!(out)?;
+            return Ok(());
+        };
+        let line = line?;
+        let line = if line.trim().is_empty() { previous.clone() } else { line.trim().to_string() };
+        previous = line.clone();
+
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else { continue };
+        let args: Vec<&str> = words.collect();
+        match execute(debugger, &mut sources, out, command, &args) {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(e) => writeln!(out, "{}", e)?,
+        }
+    }
+}
+
+/// Runs one command, returning `false` when the session should end.
+fn execute(
+    debugger: &mut Debugger,
+    sources: &mut SourceCache,
+    out: &mut impl Write,
+    command: &str,
+    args: &[&str],
+) -> Result<bool> {
+    match command {
+        "break" | "b" => {
+            let target = args.first().ok_or_else(|| anyhow!("Usage: break <fn|0xoffset>"))?;
+            let breakpoint = parse_breakpoint(debugger, target)?;
+            let id = debugger.add_breakpoint(breakpoint)?;
+            writeln!(out, "Breakpoint {} at {}", id, breakpoint)?;
+        }
+        "delete" | "d" => {
+            let id = args.first().and_then(|id| id.parse().ok()).ok_or_else(|| anyhow!("Usage: delete <id>"))?;
+            if !debugger.remove_breakpoint(id) {
+                return Err(anyhow!("No breakpoint {}", id));
+            }
+        }
+        "step" | "s" => {
+            let reason = debugger.step_in();
+            report(debugger, sources, out, reason)?;
+        }
+        "next" | "n" => {
+            let reason = debugger.step_over();
+            report(debugger, sources, out, reason)?;
+        }
+        "finish" => {
+            let reason = debugger.step_out();
+            report(debugger, sources, out, reason)?;
+        }
+        "continue" | "c" => {
+            let reason = debugger.resume();
+            report(debugger, sources, out, reason)?;
+        }
+        "reverse-step" | "rs" => {
+            let reason = debugger.step_back()?;
+            report(debugger, sources, out, reason)?;
+        }
+        "reverse-continue" | "rc" => {
+            let reason = debugger.reverse_resume()?;
+            report(debugger, sources, out, reason)?;
+        }
+        "locals" => {
+            for (index, value) in debugger.locals().iter().enumerate() {
+                writeln!(out, "  ${} = {} ({})", index, value, value.type_name())?;
+            }
+        }
+        "stack" => {
+            for value in debugger.stack().iter().rev() {
+                writeln!(out, "  {} ({})", value, value.type_name())?;
+            }
+        }
+        "mem" | "x" => {
+            let (address, len) = match args {
+                [address, len] => (parse_number(address)?, parse_number(len)?),
+                _ => return Err(anyhow!("Usage: mem <addr> <len>")),
+            };
+            let address = u32::try_from(address).map_err(|_| anyhow!("Address {} is outside 32-bit memory", args[0]))?;
+            let bytes = debugger.read_memory(address, len as usize)?;
+            print_hexdump(out, address, &bytes)?;
+        }
+        "bt" | "backtrace" => writeln!(out, "{}", debugger.backtrace())?,
+        "storage" => {
+            let slots = debugger.storage();
+            if slots.is_empty() {
+                writeln!(out, "  (empty)")?;
+            }
+            for (key, value) in slots {
+                writeln!(out, "  0x{} = 0x{}", hex(&key), hex(&value))?;
+            }
+        }
+        "gas" => writeln!(out, "Gas used: {}", debugger.gas_used())?,
+        "help" | "h" => writeln!(out, "{}", HELP)?,
+        "quit" | "q" => return Ok(false),
+        other => return Err(anyhow!("Unknown command '{}'; type 'help' for a list", other)),
+    }
+    Ok(true)
+}
+
+fn parse_breakpoint(debugger: &Debugger, target: &str) -> Result<Breakpoint> {
+    if let Some(hex) = target.strip_prefix("0x") {
+        let offset = u64::from_str_radix(hex, 16).map_err(|_| anyhow!("Invalid offset '{}'", target))?;
+        return Ok(Breakpoint::Offset(offset));
+    }
+    debugger
+        .function_index(target)
+        .or_else(|| target.parse().ok())
+        .map(Breakpoint::Function)
+        .ok_or_else(|| anyhow!("Function '{}' not found", target))
+}
+
+fn parse_number(text: &str) -> Result<u64> {
+    let parsed = match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed.map_err(|_| anyhow!("Invalid number '{}'", text))
+}
+
+fn report(debugger: &Debugger, sources: &mut SourceCache, out: &mut impl Write, reason: StopReason) -> Result<()> {
+    match reason {
+        StopReason::Breakpoint(id) => {
+            writeln!(out, "Breakpoint {}", id)?;
+            print_location(debugger, sources, out)?;
+        }
+        StopReason::Step => print_location(debugger, sources, out)?,
+        StopReason::Finished(results) => {
+            for line in debugger.console() {
+                writeln!(out, "{}", line)?;
+            }
+            let results: Vec<String> = results.iter().map(ToString::to_string).collect();
+            writeln!(out, "Finished: [{}] (gas used: {})", results.join(", "), debugger.gas_used())?;
+        }
+        StopReason::Trapped(trap) => writeln!(out, "{}", trap)?,
+    }
+    Ok(())
+}
+
+fn print_location(debugger: &Debugger, sources: &mut SourceCache, out: &mut impl Write) -> Result<()> {
+    let Some(location) = debugger.location() else { return Ok(()) };
+    writeln!(out, "{}", location)?;
+    if let Some(source) = location.source.first().and_then(|frame| frame.location.as_ref()) {
+        if let Some(text) = sources.line(source) {
+            writeln!(out, "{:>6} | {}", source.line, text)?;
+        }
+    }
+    Ok(())
+}
+
+fn print_hexdump(out: &mut impl Write, address: u32, bytes: &[u8]) -> Result<()> {
+    for (row, chunk) in bytes.chunks(16).enumerate() {
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+            .collect();
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        writeln!(out, "  0x{:08x}  {:<47}  {}", address as u64 + row as u64 * 16, hex.join(" "), ascii)?;
+    }
+    Ok(())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Source files read from disk for annotating stops; missing files are remembered as such.
+#[derive(Default)]
+struct SourceCache {
+    files: HashMap<String, Option<Vec<String>>>,
+}
+
+impl SourceCache {
+    fn line(&mut self, location: &SourceLocation) -> Option<&str> {
+        let lines = self.files.entry(location.file.clone()).or_insert_with(|| {
+            std::fs::read_to_string(&location.file)
+                .ok()
+                .map(|text| text.lines().map(str::to_string).collect())
+        });
+        let index = (location.line as usize).checked_sub(1)?;
+        lines.as_ref()?.get(index).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stylus_core::StylusRuntime;
+
+    const WAT: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (data (i32.const 16) "stylus")
+            (func $double (param $x i64) (result i64)
+                local.get $x
+                local.get $x
+                i64.add)
+            (func $run (export "run") (param $n i64) (result i64)
+                local.get $n
+                call $double
+                i64.const 1
+                i64.add)
+        )
+    "#;
+
+    fn debugger(n: i64) -> Debugger {
+        let wasm = wat::parse_str(WAT).unwrap();
+        StylusRuntime::new(&wasm).unwrap().debug("run", &[n]).unwrap()
+    }
+
+    /// Runs one command line and returns what it printed, or its error.
+    fn command(debugger: &mut Debugger, line: &str) -> Result<String> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap();
+        let args: Vec<&str> = words.collect();
+        let mut out = Vec::new();
+        assert!(execute(debugger, &mut SourceCache::default(), &mut out, command, &args)?);
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_breakpoints_and_stepping() {
+        let mut debugger = debugger(5);
+        assert_eq!(command(&mut debugger, "break double").unwrap().lines().next(), Some("Breakpoint 1 at func 0"));
+        let stopped = command(&mut debugger, "continue").unwrap();
+        assert!(stopped.starts_with("Breakpoint 1\n"), "{}", stopped);
+        assert!(stopped.contains("double"), "{}", stopped);
+
+        let backtrace = command(&mut debugger, "bt").unwrap();
+        let double = backtrace.find("double").unwrap();
+        assert!(backtrace[double..].contains("run"), "{}", backtrace);
+
+        assert!(command(&mut debugger, "step").unwrap().contains("local.get"));
+        assert_eq!(command(&mut debugger, "locals").unwrap(), "  $0 = 5 (i64)\n");
+        command(&mut debugger, "finish").unwrap();
+        assert_eq!(command(&mut debugger, "stack").unwrap(), "  10 (i64)\n");
+        assert!(command(&mut debugger, "next").unwrap().contains("i64.add"));
+
+        // The gas command and the finished call report the same count.
+        let finished = command(&mut debugger, "continue").unwrap();
+        let gas = command(&mut debugger, "gas").unwrap();
+        assert_eq!(finished, format!("Finished: [11] (gas used: {})\n", debugger.gas_used()));
+        assert_eq!(gas, format!("Gas used: {}\n", debugger.gas_used()));
+
+        assert_eq!(command(&mut debugger, "delete 1").unwrap(), "");
+        assert_eq!(command(&mut debugger, "delete 1").unwrap_err().to_string(), "No breakpoint 1");
+    }
+
+    #[test]
+    fn test_next_steps_over_calls() {
+        let mut debugger = debugger(1);
+        command(&mut debugger, "next").unwrap();
+        let over = command(&mut debugger, "next").unwrap();
+        assert!(over.contains("i64.const"), "{}", over);
+        assert_eq!(command(&mut debugger, "stack").unwrap(), "  2 (i64)\n");
+    }
+
+    #[test]
+    fn test_mem_dumps_memory_within_32_bit_addresses() {
+        let mut debugger = debugger(1);
+        let dump = command(&mut debugger, "mem 0x10 6").unwrap();
+        assert_eq!(dump, format!("  0x00000010  {:<47}  stylus\n", "73 74 79 6c 75 73"));
+        let err = command(&mut debugger, "mem 0x100000010 6").unwrap_err();
+        assert_eq!(err.to_string(), "Address 0x100000010 is outside 32-bit memory");
+        assert!(command(&mut debugger, "mem 0x10").is_err());
+    }
+
+    #[test]
+    fn test_empty_lines_repeat_the_previous_command() {
+        let mut debugger = debugger(1);
+        let mut out = Vec::new();
+        drive(&mut debugger, "next\n\n\nbogus\nquit\ngas\n".as_bytes(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let mut expected = self::debugger(1);
+        for _ in 0..3 {
+            expected.step_over();
+        }
+        assert_eq!(debugger.location(), expected.location());
+        assert!(out.contains("Unknown command 'bogus'"), "{}", out);
+        assert!(!out.contains("Gas used"), "{}", out);
+    }
+}
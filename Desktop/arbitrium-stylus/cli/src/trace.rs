@@ -0,0 +1,300 @@
+//! Call trees for `stylus run -v` and `stylus test -v`.
+//!
+//! `-v` shows contract calls with their gas, decoded calldata, return values and
+//! revert reasons, `-vv` adds events and storage writes, and `-vvv` every other host call.
+
+use std::io::{self, IsTerminal, Write};
+use stylus_core::abi::{self, AbiValue};
+use stylus_core::{Address, CallNode, CallStep, ExecutionResult, HostCallTrace, World};
+
+const RED: &str = "31";
+const GREEN: &str = "32";
+const YELLOW: &str = "33";
+const BLUE: &str = "34";
+const CYAN: &str = "36";
+const DIM: &str = "2";
+
+pub struct TracePrinter<'a> {
+    world: &'a World,
+    verbosity: u8,
+    color: bool,
+}
+
+impl<'a> TracePrinter<'a> {
+    pub fn new(world: &'a World, verbosity: u8) -> Self {
+        let color = std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal();
+        TracePrinter { world, verbosity, color }
+    }
+
+    /// Writes the tree of a top-level call to `function` on the contract at `address` to `out`.
+    pub fn print(
+        &self,
+        out: &mut impl Write,
+        address: &Address,
+        function: &str,
+        args: &[i64],
+        result: &ExecutionResult,
+    ) -> io::Result<()> {
+        let args: Vec<String> = args.iter().map(ToString::to_string).collect();
+        writeln!(out, "Traces:")?;
+        writeln!(
+            out,
+            "  [{}] {}::{}({})",
+            result.gas_used,
+            self.paint(GREEN, &self.label(address)),
+            function,
+            args.join(", ")
+        )?;
+        let mut returned = format!("← [Return] {}", result.return_value);
+        if !result.output.is_empty() {
+            returned.push_str(&format!(", output: {}", join(&abi::guess_values(&result.output))));
+        }
+        self.print_node(out, &CallNode::build(&result.host_calls), "    ", self.paint(GREEN, &returned))
+    }
+
+    fn print_node(&self, out: &mut impl Write, node: &CallNode, prefix: &str, returned: String) -> io::Result<()> {
+        let mut entries: Vec<(String, Option<&CallNode>)> = Vec::new();
+        for step in &node.steps {
+            match step {
+                CallStep::Call(child) => entries.push((self.call_line(child), Some(child))),
+                CallStep::Host(call) => entries.extend(self.host_line(call).map(|line| (line, None))),
+            }
+        }
+        entries.push((returned, None));
+
+        for (index, (line, child)) in entries.iter().enumerate() {
+            let last = index + 1 == entries.len();
+            writeln!(out, "{}{}{}", prefix, if last { "└─ " } else { "├─ " }, line)?;
+            if let Some(child) = child {
+                let prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+                self.print_node(out, child, &prefix, self.return_line(child))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn call_line(&self, node: &CallNode) -> String {
+        let Some(call) = &node.call else { return String::new() };
+        let to = call.bytes("to").and_then(|to| Address::try_from(to.as_slice()).ok()).unwrap_or_default();
+        let color = if node.reverted() || call.error.is_some() { RED } else { GREEN };
+        let mut line = format!("[{}] {}::{}", call.gas, self.paint(color, &self.label(&to)), self.decode_call(call));
+        let value = call.bytes("value").and_then(|value| abi::decode_u128(&value, 0)).unwrap_or(0);
+        if value > 0 {
+            line.push_str(&format!(" {{value: {}}}", value));
+        }
+        match call.name.as_str() {
+            "delegate_call_contract" => line.push_str(&self.paint(DIM, " [delegatecall]")),
+            "static_call_contract" => line.push_str(&self.paint(DIM, " [staticcall]")),
+            _ => {}
+        }
+        line
+    }
+
+    fn decode_call(&self, call: &HostCallTrace) -> String {
+        let calldata = call.bytes("calldata").unwrap_or_default();
+        if calldata.is_empty() {
+            return "fallback()".to_string();
+        }
+        if calldata.len() < 4 {
+            return format!("fallback({})", abi::hex(&calldata));
+        }
+        let (selector, args) = calldata.split_at(4);
+        let decoded = self.world.signature(selector).and_then(|signature| {
+            let types = abi::signature_types(signature)?;
+            let name = &signature[..signature.find('(')?];
+            Some(format!("{}({})", name, join(&abi::decode_values(&types, args)?)))
+        });
+        decoded.unwrap_or_else(|| format!("{}({})", abi::hex(selector), join(&abi::guess_values(args))))
+    }
+
+    fn return_line(&self, node: &CallNode) -> String {
+        let Some(call) = &node.call else { return String::new() };
+        if let Some(error) = &call.error {
+            return self.paint(RED, &format!("← [Error] {}", error));
+        }
+        let data = call.bytes("return_data").unwrap_or_default();
+        if node.reverted() {
+            let reason = abi::decode_revert(&data).map(|reason| format!("{:?}", reason));
+            let reason = reason.unwrap_or_else(|| if data.is_empty() { "()".to_string() } else { abi::hex(&data) });
+            return self.paint(RED, &format!("← [Revert] {}", reason));
+        }
+        let values = if data.is_empty() { "()".to_string() } else { join(&abi::guess_values(&data)) };
+        self.paint(GREEN, &format!("← [Return] {}", values))
+    }
+
+    /// The line for a host call that is not a contract call, if the verbosity shows it.
+    fn host_line(&self, call: &HostCallTrace) -> Option<String> {
+        match (call.module.as_str(), call.name.as_str()) {
+            ("vm_hooks", "emit_log") if self.verbosity >= 2 => {
+                let topics: Vec<Vec<u8>> =
+                    (0..).map_while(|i| call.bytes(&format!("topic{}", i))).collect();
+                let data = call.bytes("data").unwrap_or_default();
+                let name = topics
+                    .first()
+                    .and_then(|topic| self.world.event(topic))
+                    .and_then(|signature| signature.split_once('('))
+                    .map_or("log", |(name, _)| name);
+                let mut values: Vec<String> = topics.iter().skip(1).map(|topic| word(topic)).collect();
+                values.extend(abi::guess_values(&data).iter().map(ToString::to_string));
+                Some(self.paint(CYAN, &format!("emit {}({})", name, values.join(", "))))
+            }
+            ("vm_hooks", "storage_store_bytes32" | "storage_cache_bytes32") if self.verbosity >= 2 => {
+                let key = call.bytes("key").unwrap_or_default();
+                let value = call.bytes("value").unwrap_or_default();
+                Some(self.paint(YELLOW, &format!("storage[{}] = {}", word(&key), word(&value))))
+            }
+            ("vm_hooks", "emit_log" | "storage_store_bytes32" | "storage_cache_bytes32") => None,
+            _ if self.verbosity >= 3 => Some(self.paint(BLUE, &call.to_string())),
+            _ => None,
+        }
+    }
+
+    fn label(&self, address: &Address) -> String {
+        self.world.label(address).map_or_else(|| abi::hex(address), ToString::to_string)
+    }
+
+    fn paint(&self, color: &str, text: &str) -> String {
+        if self.color {
+            format!("\x1b[{}m{}\x1b[0m", color, text)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+fn join(values: &[AbiValue]) -> String {
+    values.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+}
+
+/// A storage key, value or topic, as a number when it is a small one.
+fn word(bytes: &[u8]) -> String {
+    abi::guess_values(bytes).first().map_or_else(|| abi::hex(bytes), ToString::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use stylus_core::world::address_from_u64;
+
+    const TOKEN: Address = address_from_u64(0x70c3);
+    const VAULT: Address = address_from_u64(0xfa17);
+    const UNKNOWN: Address = address_from_u64(0xdead);
+
+    fn host_call(name: &str, depth: u32, args: &[(&str, String)], results: &[(&str, String)], gas: u64) -> HostCallTrace {
+        let fields = |fields: &[(&str, String)]| fields.iter().map(|(name, value)| (name.to_string(), value.clone())).collect();
+        HostCallTrace {
+            module: "vm_hooks".to_string(),
+            name: name.to_string(),
+            depth,
+            args: fields(args),
+            results: fields(results),
+            gas,
+            error: None,
+        }
+    }
+
+    fn world() -> World {
+        let mut world = World::new(HashMap::new());
+        world.set_label(&TOKEN, "Token");
+        world.set_label(&VAULT, "Vault");
+        world.register_signature("deposit(uint256)");
+        world.register_signature("Deposited(uint256)");
+        world
+    }
+
+    /// `Token::run(5)` deposits into the vault, which logs and stores, then calls an
+    /// unknown contract that reverts.
+    fn result(topic: [u8; 32]) -> ExecutionResult {
+        let mut deposit = abi::selector("deposit(uint256)").to_vec();
+        deposit.extend_from_slice(&abi::encode_u64(5));
+        let host_calls = vec![
+            host_call(
+                "call_contract",
+                1,
+                &[("to", abi::hex(&VAULT)), ("calldata", abi::hex(&deposit)), ("value", abi::hex(&abi::encode_u64(7)))],
+                &[("status", "0".to_string()), ("return_data", abi::hex(&abi::encode_u64(1)))],
+                800,
+            ),
+            host_call("emit_log", 2, &[("topic0", abi::hex(&topic)), ("data", abi::hex(&abi::encode_u64(5)))], &[], 375),
+            host_call("storage_store_bytes32", 2, &[("key", abi::hex(&abi::encode_u64(1))), ("value", abi::hex(&abi::encode_u64(5)))], &[], 5000),
+            host_call("storage_load_bytes32", 2, &[("key", abi::hex(&abi::encode_u64(1)))], &[("value", abi::hex(&abi::encode_u64(5)))], 200),
+            host_call(
+                "call_contract",
+                1,
+                &[("to", abi::hex(&UNKNOWN)), ("calldata", "0x".to_string())],
+                &[("status", "1".to_string()), ("return_data", abi::hex(&abi::encode_revert("nope")))],
+                700,
+            ),
+        ];
+        ExecutionResult {
+            return_value: 1,
+            gas_used: 9000,
+            call_trace: Vec::new(),
+            memory_usage: 0,
+            output: Vec::new(),
+            logs: Vec::new(),
+            l1_cost: Default::default(),
+            console: Vec::new(),
+            host_calls,
+        }
+    }
+
+    fn print(world: &World, verbosity: u8, color: bool, result: &ExecutionResult) -> String {
+        let printer = TracePrinter { world, verbosity, color };
+        let mut out = Vec::new();
+        printer.print(&mut out, &TOKEN, "run", &[5], result).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_nested_calls_are_drawn_with_labels_and_decoded_calldata() {
+        let world = world();
+        let result = result(abi::keccak256(b"Deposited(uint256)"));
+        assert_eq!(
+            print(&world, 2, false, &result),
+            "\
+Traces:
+  [9000] Token::run(5)
+    ├─ [800] Vault::deposit(5) {value: 7}
+    │   ├─ emit Deposited(5)
+    │   ├─ storage[1] = 5
+    │   └─ ← [Return] 1
+    ├─ [700] 0x000000000000000000000000000000000000dead::fallback()
+    │   └─ ← [Revert] \"nope\"
+    └─ ← [Return] 1
+"
+        );
+
+        // Only `-vvv` shows the other host calls, and `-v` hides events and storage.
+        assert!(print(&world, 3, false, &result).contains("vm_hooks::storage_load_bytes32(key="));
+        let quiet = print(&world, 1, false, &result);
+        assert!(!quiet.contains("emit") && !quiet.contains("storage"), "{}", quiet);
+        assert!(quiet.contains("    │   └─ ← [Return] 1\n"), "{}", quiet);
+    }
+
+    #[test]
+    fn test_reverted_calls_are_red() {
+        let printed = print(&world(), 1, true, &result([0u8; 32]));
+        let red = |text: &str| format!("\x1b[31m{}\x1b[0m", text);
+        let green = |text: &str| format!("\x1b[32m{}\x1b[0m", text);
+        assert!(printed.contains(&format!("[800] {}::deposit(5)", green("Vault"))), "{}", printed);
+        assert!(printed.contains(&format!("[700] {}::fallback()", red("0x000000000000000000000000000000000000dead"))));
+        assert!(printed.contains(&red("← [Revert] \"nope\"")), "{}", printed);
+        assert!(printed.ends_with(&format!("└─ {}\n", green("← [Return] 1"))), "{}", printed);
+    }
+
+    #[test]
+    fn test_events_are_named_only_by_their_full_topic_hash() {
+        let world = world();
+        // A topic sharing its first four bytes with a registered function selector is not that function.
+        let mut topic = [0xffu8; 32];
+        topic[..4].copy_from_slice(&abi::selector("deposit(uint256)"));
+        assert!(print(&world, 2, false, &result(topic)).contains("├─ emit log(5)\n"));
+
+        let mut topic = abi::keccak256(b"Deposited(uint256)");
+        topic[31] ^= 1;
+        assert!(print(&world, 2, false, &result(topic)).contains("├─ emit log(5)\n"));
+        assert_eq!(world.event(&abi::keccak256(b"Deposited(uint256)")), Some("Deposited(uint256)"));
+    }
+}
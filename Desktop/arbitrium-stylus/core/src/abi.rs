@@ -0,0 +1,204 @@
+use crate::world::{Address, Bytes32};
+use std::fmt;
+use tiny_keccak::{Hasher, Keccak};
+
+pub fn keccak256(data: &[u8]) -> Bytes32 {
+    let mut hasher = Keccak::v256();
+    hasher.update(data);
+    let mut output = [0u8; 32];
+    hasher.finalize(&mut output);
+    output
+}
+
+pub fn selector(signature: &str) -> [u8; 4] {
+    let hash = keccak256(signature.as_bytes());
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+pub fn encode_u64(value: u64) -> Bytes32 {
+    encode_u128(value as u128)
+}
+
+pub fn encode_u128(value: u128) -> Bytes32 {
+    let mut word = [0u8; 32];
+    word[16..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+pub fn encode_bool(value: bool) -> Bytes32 {
+    encode_u64(value as u64)
+}
+
+pub fn encode_address(address: &Address) -> Bytes32 {
+    let mut word = [0u8; 32];
+    word[12..].copy_from_slice(address);
+    word
+}
+
+/// Encodes a tuple of static words, e.g. the return value of a getter.
+pub fn encode_words(words: &[Bytes32]) -> Vec<u8> {
+    words.iter().flat_map(|w| w.iter().copied()).collect()
+}
+
+/// Encodes a single dynamic `bytes` or `string` value as a complete return payload.
+pub fn encode_bytes(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(64 + data.len().div_ceil(32) * 32);
+    out.extend_from_slice(&encode_u64(32));
+    out.extend_from_slice(&encode_u64(data.len() as u64));
+    out.extend_from_slice(data);
+    out.resize(64 + data.len().div_ceil(32) * 32, 0);
+    out
+}
+
+/// Builds Solidity's standard `Error(string)` revert payload.
+pub fn encode_revert(message: &str) -> Vec<u8> {
+    let mut out = selector("Error(string)").to_vec();
+    out.extend(encode_bytes(message.as_bytes()));
+    out
+}
+
+pub fn decode_revert(data: &[u8]) -> Option<String> {
+    if data.len() < 4 || data[..4] != selector("Error(string)") {
+        return None;
+    }
+    let message = decode_bytes(&data[4..], 0)?;
+    String::from_utf8(message).ok()
+}
+
+pub fn word(args: &[u8], index: usize) -> Option<Bytes32> {
+    let start = index * 32;
+    let slice = args.get(start..start + 32)?;
+    let mut word = [0u8; 32];
+    word.copy_from_slice(slice);
+    Some(word)
+}
+
+pub fn decode_u64(args: &[u8], index: usize) -> Option<u64> {
+    let word = word(args, index)?;
+    if word[..24].iter().any(|&b| b != 0) {
+        return None;
+    }
+    Some(u64::from_be_bytes(word[24..].try_into().unwrap()))
+}
+
+pub fn decode_u128(args: &[u8], index: usize) -> Option<u128> {
+    let word = word(args, index)?;
+    if word[..16].iter().any(|&b| b != 0) {
+        return None;
+    }
+    Some(u128::from_be_bytes(word[16..].try_into().unwrap()))
+}
+
+pub fn decode_address(args: &[u8], index: usize) -> Option<Address> {
+    let word = word(args, index)?;
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&word[12..]);
+    Some(address)
+}
+
+/// Decodes a dynamic `bytes` argument whose head sits at `index`.
+pub fn decode_bytes(args: &[u8], index: usize) -> Option<Vec<u8>> {
+    let offset = decode_u64(args, index)? as usize;
+    let len_word = args.get(offset..offset + 32)?;
+    let len = decode_u64(len_word, 0)? as usize;
+    args.get(offset + 32..offset + 32 + len).map(|d| d.to_vec())
+}
+
+/// A decoded ABI value, formatted the way Solidity tooling prints it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AbiValue {
+    Uint(Bytes32),
+    Int(Bytes32),
+    Address(Address),
+    Bool(bool),
+    Bytes(Vec<u8>),
+    String(String),
+    /// A fixed `bytesN`, or a word of unknown type.
+    Word(Bytes32),
+}
+
+impl fmt::Display for AbiValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AbiValue::Uint(word) => match decode_u128(word, 0) {
+                Some(value) => write!(f, "{}", value),
+                None => write!(f, "{}", hex(word)),
+            },
+            AbiValue::Int(word) => {
+                let negative = word[0] & 0x80 != 0;
+                let fits = word[..16].iter().all(|&b| b == if negative { 0xff } else { 0 });
+                if fits {
+                    write!(f, "{}", i128::from_be_bytes(word[16..].try_into().unwrap()))
+                } else {
+                    write!(f, "{}", hex(word))
+                }
+            }
+            AbiValue::Address(address) => write!(f, "{}", hex(address)),
+            AbiValue::Bool(value) => write!(f, "{}", value),
+            AbiValue::Bytes(data) => write!(f, "{}", hex(data)),
+            AbiValue::String(text) => write!(f, "{:?}", text),
+            AbiValue::Word(word) => write!(f, "{}", hex(word)),
+        }
+    }
+}
+
+/// The parameter types of a signature like `transfer(address,uint256)`. Tuples are not supported.
+pub fn signature_types(signature: &str) -> Option<Vec<&str>> {
+    let params = signature.split_once('(')?.1.strip_suffix(')')?;
+    if params.contains('(') {
+        return None;
+    }
+    Some(params.split(',').map(str::trim).filter(|ty| !ty.is_empty()).collect())
+}
+
+/// Decodes `data` as the ABI encoding of `types`, or `None` if it does not fit them.
+pub fn decode_values(types: &[&str], data: &[u8]) -> Option<Vec<AbiValue>> {
+    types
+        .iter()
+        .enumerate()
+        .map(|(index, &ty)| {
+            let word = word(data, index)?;
+            Some(match ty {
+                "address" => AbiValue::Address(decode_address(data, index)?),
+                "bool" => AbiValue::Bool(word[31] != 0),
+                "bytes" => AbiValue::Bytes(decode_bytes(data, index)?),
+                "string" => AbiValue::String(String::from_utf8_lossy(&decode_bytes(data, index)?).into_owned()),
+                ty if ty.starts_with("uint") => AbiValue::Uint(word),
+                ty if ty.starts_with("int") => AbiValue::Int(word),
+                ty if ty.starts_with("bytes") => AbiValue::Word(word),
+                _ => return None,
+            })
+        })
+        .collect()
+}
+
+/// Decodes data of unknown types word by word: small numbers as integers,
+/// words shaped like addresses as addresses, anything else as raw words.
+pub fn guess_values(data: &[u8]) -> Vec<AbiValue> {
+    data.chunks(32)
+        .enumerate()
+        .map(|(index, chunk)| match word(data, index) {
+            Some(word) if decode_u64(&word, 0).is_some() => AbiValue::Uint(word),
+            Some(word) if word[..12].iter().all(|&b| b == 0) => AbiValue::Address(decode_address(&word, 0).unwrap()),
+            Some(word) => AbiValue::Word(word),
+            None => AbiValue::Bytes(chunk.to_vec()),
+        })
+        .collect()
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("0x{}", hex)
+}
+
+/// Parses `0x`-prefixed hex as written by [`hex`].
+pub fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    let digits = text.strip_prefix("0x")?;
+    if digits.len() % 2 != 0 {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok())
+        .collect()
+}
@@ -0,0 +1,117 @@
+use crate::source_map::SourceFrame;
+use crate::symbols::Symbols;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use wasmer::RuntimeError;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BacktraceFrame {
+    pub func_index: u32,
+    /// Demangled name from the name section.
+    pub function: Option<String>,
+    /// Byte offset of the trapping instruction within the module.
+    pub module_offset: u64,
+    /// The same position relative to the code section, as debug info addresses it.
+    pub code_offset: Option<u64>,
+    /// Source positions from DWARF, innermost inlined function first.
+    pub source: Vec<SourceFrame>,
+}
+
+impl fmt::Display for BacktraceFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.function {
+            Some(name) => write!(f, "{}", name)?,
+            None => write!(f, "<func {}>", self.func_index)?,
+        }
+        write!(f, " (func {} @ 0x{:x})", self.func_index, self.code_offset.unwrap_or(self.module_offset))?;
+        for frame in &self.source {
+            if let Some(location) = &frame.location {
+                write!(f, "\n          at {}", location)?;
+                if let Some(function) = &frame.function {
+                    write!(f, " in {}", function)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl BacktraceFrame {
+    /// Describes the instruction at `module_offset` in `func_index` using the contract's symbols.
+    pub(crate) fn resolve(func_index: u32, module_offset: u64, symbols: &Symbols) -> Self {
+        Self {
+            func_index,
+            function: symbols.function_name(func_index),
+            module_offset,
+            code_offset: symbols.code_offset(module_offset),
+            source: symbols.source_frames(module_offset),
+        }
+    }
+}
+
+/// Wasm frames active when a contract trapped, innermost first.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Backtrace {
+    pub frames: Vec<BacktraceFrame>,
+}
+
+impl Backtrace {
+    pub(crate) fn capture(error: &RuntimeError, symbols: &Symbols) -> Self {
+        let frames = error
+            .trace()
+            .iter()
+            .map(|frame| {
+                let mut resolved = BacktraceFrame::resolve(frame.func_index(), frame.module_offset() as u64, symbols);
+                if resolved.function.is_none() {
+                    resolved.function = frame.function_name().map(crate::symbols::demangle);
+                }
+                resolved
+            })
+            .collect();
+        Self { frames }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+impl fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, frame) in self.frames.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{:>4}: {}", i, frame)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{StylusRuntime, Trap};
+
+    #[test]
+    fn test_wasmer_traps_capture_demangled_frames() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (func $_ZN8contract5check17h0123456789abcdefE (param $amount i64)
+                    (if (i64.eqz (local.get $amount)) (then unreachable)))
+                (func $_ZN8contract8withdraw17hfedcba9876543210E (export "withdraw") (param $amount i64) (result i64)
+                    (call $_ZN8contract5check17h0123456789abcdefE (local.get $amount))
+                    (local.get $amount))
+            )
+        "#).unwrap();
+        let mut runtime = StylusRuntime::new(&wasm).unwrap();
+        let err = runtime.execute_function("withdraw", &[0]).unwrap_err();
+        let trap = err.downcast_ref::<Trap>().unwrap();
+
+        let names: Vec<_> = trap.backtrace.frames.iter().map(|frame| frame.function.as_deref()).collect();
+        assert_eq!(names, [Some("contract::check"), Some("contract::withdraw")]);
+        let check = &trap.backtrace.frames[0];
+        assert_eq!(check.func_index, 0);
+        assert!(check.code_offset.is_some_and(|offset| offset < check.module_offset));
+        assert!(err.to_string().contains(&format!("   0: {}", check)), "{}", err);
+    }
+}
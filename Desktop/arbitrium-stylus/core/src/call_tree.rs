@@ -0,0 +1,84 @@
+//! The nested tree of contract calls behind a flat host call trace.
+
+use crate::host_trace::HostCallTrace;
+use std::iter::Peekable;
+
+/// Host imports that run another contract; the calls it makes follow them one level deeper.
+const CONTRACT_CALLS: &[&str] = &["call_contract", "delegate_call_contract", "static_call_contract"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallStep {
+    Host(HostCallTrace),
+    Call(CallNode),
+}
+
+/// One contract execution and everything it did, in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallNode {
+    /// The host call that entered this contract; `None` for the top-level call.
+    pub call: Option<HostCallTrace>,
+    pub steps: Vec<CallStep>,
+}
+
+impl CallNode {
+    pub fn build(calls: &[HostCallTrace]) -> Self {
+        CallNode { call: None, steps: steps(&mut calls.iter().peekable(), 1) }
+    }
+
+    /// Whether the call returned a failure status; the top-level call never has one.
+    pub fn reverted(&self) -> bool {
+        self.call.as_ref().and_then(|call| call.field("status")).is_some_and(|status| status != "0")
+    }
+}
+
+fn steps<'a>(calls: &mut Peekable<impl Iterator<Item = &'a HostCallTrace>>, depth: u32) -> Vec<CallStep> {
+    let mut nodes = Vec::new();
+    while let Some(call) = calls.next_if(|call| call.depth >= depth) {
+        if call.module == "vm_hooks" && CONTRACT_CALLS.contains(&call.name.as_str()) {
+            let nested = steps(calls, call.depth + 1);
+            nodes.push(CallStep::Call(CallNode { call: Some(call.clone()), steps: nested }));
+        } else {
+            nodes.push(CallStep::Host(call.clone()));
+        }
+    }
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trace(name: &str, depth: u32, status: &str) -> HostCallTrace {
+        HostCallTrace {
+            module: "vm_hooks".to_string(),
+            name: name.to_string(),
+            depth,
+            args: Vec::new(),
+            results: if status.is_empty() { Vec::new() } else { vec![("status".to_string(), status.to_string())] },
+            gas: 0,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_nested_calls_own_the_deeper_host_calls() {
+        let calls = [
+            trace("read_args", 1, ""),
+            trace("call_contract", 1, "0"),
+            trace("storage_load_bytes32", 2, ""),
+            trace("static_call_contract", 2, "1"),
+            trace("write_result", 3, ""),
+            trace("emit_log", 2, ""),
+            trace("write_result", 1, ""),
+        ];
+        let tree = CallNode::build(&calls);
+        assert_eq!(tree.steps.len(), 3);
+        let CallStep::Call(outer) = &tree.steps[1] else { panic!("expected a call") };
+        assert!(!outer.reverted());
+        assert_eq!(outer.steps.len(), 3);
+        let CallStep::Call(inner) = &outer.steps[1] else { panic!("expected a call") };
+        assert!(inner.reverted());
+        assert_eq!(inner.steps, [CallStep::Host(calls[4].clone())]);
+        assert_eq!(tree.steps[2], CallStep::Host(calls[6].clone()));
+    }
+}
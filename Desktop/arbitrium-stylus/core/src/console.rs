@@ -0,0 +1,68 @@
+use crate::world::Address;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::Read;
+use wasmer_wasi::Pipe;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConsoleStream {
+    Stdout,
+    Stderr,
+    /// Written through the `console` host import used by `no_std` contracts.
+    Log,
+}
+
+impl fmt::Display for ConsoleStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ConsoleStream::Stdout => "stdout",
+            ConsoleStream::Stderr => "stderr",
+            ConsoleStream::Log => "console",
+        };
+        f.write_str(name)
+    }
+}
+
+/// One line of output printed by a contract.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsoleLine {
+    pub stream: ConsoleStream,
+    pub address: Address,
+    pub text: String,
+}
+
+impl fmt::Display for ConsoleLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.stream, self.text)
+    }
+}
+
+/// The WASI stdout and stderr of one contract instance.
+#[derive(Default)]
+pub(crate) struct ConsolePipes {
+    pub stdout: Pipe,
+    pub stderr: Pipe,
+}
+
+impl ConsolePipes {
+    /// Moves everything written so far into `lines`.
+    pub fn drain(&mut self, address: Address, lines: &mut Vec<ConsoleLine>) {
+        for (stream, pipe) in [
+            (ConsoleStream::Stdout, &mut self.stdout),
+            (ConsoleStream::Stderr, &mut self.stderr),
+        ] {
+            let mut bytes = Vec::new();
+            if pipe.read_to_end(&mut bytes).is_ok() {
+                push_lines(lines, stream, address, &String::from_utf8_lossy(&bytes));
+            }
+        }
+    }
+}
+
+pub(crate) fn push_lines(lines: &mut Vec<ConsoleLine>, stream: ConsoleStream, address: Address, text: &str) {
+    lines.extend(text.lines().map(|line| ConsoleLine {
+        stream,
+        address,
+        text: line.to_string(),
+    }));
+}
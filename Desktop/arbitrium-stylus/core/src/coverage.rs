@@ -0,0 +1,486 @@
+//! Instruction and branch coverage of a contract, mapped to source lines for lcov and HTML reports.
+//!
+//! Counts come from the interpreter, which also runs nested calls while coverage is enabled.
+//! Each contract's code is counted separately, keyed by its code hash.
+
+use crate::interpreter::{Instr, Machine, Program};
+use crate::profile::export::escape;
+use crate::source_map::SourceLocation;
+use crate::symbols::Symbols;
+use crate::world::Bytes32;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Counts shared by every frame of a session, with a map for each code hash it has run.
+/// A handle counts into the map of the code running in its own frame.
+#[derive(Clone)]
+pub(crate) struct Coverage {
+    maps: Arc<Mutex<HashMap<Bytes32, Arc<Mutex<CoverageMap>>>>>,
+    code_hash: Bytes32,
+    map: Arc<Mutex<CoverageMap>>,
+}
+
+impl Coverage {
+    pub(crate) fn new(code_hash: Bytes32) -> Self {
+        let map = Arc::new(Mutex::new(CoverageMap::default()));
+        let maps = Arc::new(Mutex::new(HashMap::from([(code_hash, map.clone())])));
+        Self { maps, code_hash, map }
+    }
+
+    /// A handle for a nested frame running the code with `code_hash`.
+    pub(crate) fn for_code(&self, code_hash: Bytes32) -> Self {
+        if code_hash == self.code_hash {
+            return self.clone();
+        }
+        let map = self.maps.lock().unwrap_or_else(|e| e.into_inner()).entry(code_hash).or_default().clone();
+        Self { maps: self.maps.clone(), code_hash, map }
+    }
+
+    /// Counts of this handle's code.
+    pub(crate) fn map(&self) -> MutexGuard<'_, CoverageMap> {
+        self.map.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The report of this handle's code.
+    pub(crate) fn report(&self, program: &Program, symbols: &Symbols) -> CoverageReport {
+        self.map().report(program, symbols, self.code_hash)
+    }
+
+    /// Features covered across every contract, so new coverage of a callee also counts.
+    pub(crate) fn features(&self) -> usize {
+        let maps = self.maps.lock().unwrap_or_else(|e| e.into_inner());
+        maps.values().map(|map| map.lock().unwrap_or_else(|e| e.into_inner()).features()).sum()
+    }
+}
+
+/// Raw execution counts, accumulated over every call made while coverage is enabled.
+#[derive(Default)]
+pub(crate) struct CoverageMap {
+    /// Executions of each `(function, body index)`.
+    hits: HashMap<(u32, usize), u64>,
+    /// Times each arm of a branch instruction was taken, keyed like `hits`.
+    arms: HashMap<(u32, usize), Vec<u64>>,
+}
+
+impl CoverageMap {
+    /// Counts the instruction `machine` is about to execute and, for a branch, the arm it will take.
+    pub(crate) fn record(&mut self, machine: &Machine) {
+        let (Some((func, pc)), Some(instr)) = (machine.position(), machine.current_instr()) else { return };
+        *self.hits.entry((func, pc)).or_default() += 1;
+
+        let operand = machine.stack().last().map_or(0, |value| value.as_i64() as u32);
+        let (arms, arm) = match instr {
+            // Arm 0 is the `then` block, arm 1 the `else` block or skipping the `if`.
+            Instr::If { .. } => (2, (operand == 0) as usize),
+            // Arm 0 falls through, arm 1 branches.
+            Instr::BrIf(_) => (2, (operand != 0) as usize),
+            // One arm per table entry, then the default.
+            Instr::BrTable { targets, .. } => (targets.len() + 1, (operand as usize).min(targets.len())),
+            _ => return,
+        };
+        self.arms.entry((func, pc)).or_insert_with(|| vec![0; arms])[arm] += 1;
+    }
+
+    /// Instructions and branch arms executed at least once, which only grows as calls add coverage.
+    pub(crate) fn features(&self) -> usize {
+        self.hits.len() + self.arms.values().flatten().filter(|&&count| count > 0).count()
+    }
+
+    pub(crate) fn report(&self, program: &Program, symbols: &Symbols, code_hash: Bytes32) -> CoverageReport {
+        let location = |offset: u64| symbols.code_offset(offset).and_then(|code| symbols.source_map().location(code));
+        let mut report = CoverageReport::default();
+        let mut files: BTreeMap<String, FileLines> = BTreeMap::new();
+
+        for function in program.functions.iter().filter(|function| function.import.is_none()) {
+            let func = function.index;
+            let hits = |pc: usize| self.hits.get(&(func, pc)).copied().unwrap_or(0);
+            let mut coverage = FunctionCoverage {
+                name: function_name(program, symbols, func),
+                code_hash,
+                calls: hits(0),
+                instructions: function.body.len(),
+                covered_instructions: (0..function.body.len()).filter(|&pc| hits(pc) > 0).count(),
+                branches: 0,
+                covered_branches: 0,
+                location: function.offsets.first().and_then(|&offset| location(offset)),
+            };
+
+            for (pc, instr) in function.body.iter().enumerate() {
+                let arms = match instr {
+                    Instr::If { .. } | Instr::BrIf(_) => 2,
+                    Instr::BrTable { targets, .. } => targets.len() + 1,
+                    _ => 0,
+                };
+                let taken = self.arms.get(&(func, pc));
+                coverage.branches += arms;
+                coverage.covered_branches += taken.map_or(0, |taken| taken.iter().filter(|&&count| count > 0).count());
+
+                let Some(source) = function.offsets.get(pc).and_then(|&offset| location(offset)) else { continue };
+                if is_dependency(&source.file) {
+                    continue;
+                }
+                let file = files.entry(source.file).or_default();
+                let line = file.lines.entry(source.line).or_default();
+                *line = (*line).max(hits(pc));
+                if arms > 0 {
+                    let block = file
+                        .branches
+                        .keys()
+                        .filter(|&&(line, _, _)| line == source.line)
+                        .map(|&(_, block, _)| block + 1)
+                        .max()
+                        .unwrap_or(0);
+                    for arm in 0..arms {
+                        let count = taken.map(|taken| taken[arm]);
+                        file.branches.insert((source.line, block, arm), count);
+                    }
+                }
+            }
+            report.functions.push(coverage);
+        }
+
+        report.files = files.into_iter().map(|(path, lines)| lines.into_file(path)).collect();
+        report
+    }
+}
+
+#[derive(Default)]
+struct FileLines {
+    lines: BTreeMap<u32, u64>,
+    /// `(line, block, arm)` to times taken, `None` if the branch never ran.
+    branches: BTreeMap<(u32, usize, usize), Option<u64>>,
+}
+
+impl FileLines {
+    fn into_file(self, path: String) -> FileCoverage {
+        FileCoverage {
+            path,
+            lines: self.lines.into_iter().map(|(line, hits)| LineCoverage { line, hits }).collect(),
+            branches: self
+                .branches
+                .into_iter()
+                .map(|((line, block, arm), taken)| BranchCoverage { line, block, arm, taken })
+                .collect(),
+        }
+    }
+}
+
+/// Standard library and crates.io sources, which are left out of line coverage.
+fn is_dependency(path: &str) -> bool {
+    path.starts_with("/rustc/") || path.contains("/.cargo/registry/") || path.contains("/.cargo/git/")
+}
+
+fn function_name(program: &Program, symbols: &Symbols, func: u32) -> String {
+    symbols
+        .function_name(func)
+        .or_else(|| program.export_name(func).map(str::to_string))
+        .unwrap_or_else(|| format!("func[{}]", func))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunctionCoverage {
+    pub name: String,
+    /// Hash of the contract code the function is part of.
+    pub code_hash: Bytes32,
+    /// Times the function was entered.
+    pub calls: u64,
+    pub instructions: usize,
+    pub covered_instructions: usize,
+    /// Arms of `if`, `br_if` and `br_table`; each `if` and `br_if` has two.
+    pub branches: usize,
+    pub covered_branches: usize,
+    pub location: Option<SourceLocation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LineCoverage {
+    pub line: u32,
+    /// Executions of the line's most executed instruction.
+    pub hits: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BranchCoverage {
+    pub line: u32,
+    /// Which of the line's branch instructions this arm belongs to.
+    pub block: usize,
+    pub arm: usize,
+    /// `None` if the branch instruction never ran.
+    pub taken: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileCoverage {
+    pub path: String,
+    pub lines: Vec<LineCoverage>,
+    pub branches: Vec<BranchCoverage>,
+}
+
+/// Covered out of total, for lines, branches or instructions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CoverageCount {
+    pub covered: usize,
+    pub total: usize,
+}
+
+impl CoverageCount {
+    /// Nothing to cover counts as fully covered.
+    pub fn percent(self) -> f64 {
+        if self.total == 0 { 100.0 } else { self.covered as f64 * 100.0 / self.total as f64 }
+    }
+
+    fn add(self, covered: usize, total: usize) -> Self {
+        CoverageCount { covered: self.covered + covered, total: self.total + total }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CoverageReport {
+    pub functions: Vec<FunctionCoverage>,
+    /// The contract's own source files; empty when it was built without debug info.
+    pub files: Vec<FileCoverage>,
+}
+
+impl CoverageReport {
+    pub fn lines(&self) -> CoverageCount {
+        self.files.iter().fold(CoverageCount::default(), |count, file| {
+            count.add(file.lines.iter().filter(|line| line.hits > 0).count(), file.lines.len())
+        })
+    }
+
+    pub fn branches(&self) -> CoverageCount {
+        self.functions
+            .iter()
+            .fold(CoverageCount::default(), |count, function| count.add(function.covered_branches, function.branches))
+    }
+
+    pub fn instructions(&self) -> CoverageCount {
+        self.functions.iter().fold(CoverageCount::default(), |count, function| {
+            count.add(function.covered_instructions, function.instructions)
+        })
+    }
+
+    /// Line coverage, or instruction coverage for contracts without line info.
+    pub fn percent(&self) -> f64 {
+        if self.files.is_empty() { self.instructions().percent() } else { self.lines().percent() }
+    }
+
+    /// Adds another report's counts, such as another suite's; files with the same path are combined,
+    /// and so are functions with the same name and code. Which instructions and arms a function
+    /// covered is not kept, so the combined function keeps the larger of the covered counts.
+    pub fn merge(&mut self, other: &CoverageReport) {
+        for function in &other.functions {
+            let same = |existing: &&mut FunctionCoverage| {
+                existing.name == function.name && existing.code_hash == function.code_hash
+            };
+            match self.functions.iter_mut().find(same) {
+                Some(existing) => {
+                    existing.calls += function.calls;
+                    existing.covered_instructions = existing.covered_instructions.max(function.covered_instructions);
+                    existing.covered_branches = existing.covered_branches.max(function.covered_branches);
+                }
+                None => self.functions.push(function.clone()),
+            }
+        }
+        let mut files: BTreeMap<String, FileLines> = BTreeMap::new();
+        for file in self.files.iter().chain(&other.files) {
+            let lines = files.entry(file.path.clone()).or_default();
+            for line in &file.lines {
+                *lines.lines.entry(line.line).or_default() += line.hits;
+            }
+            for branch in &file.branches {
+                let taken = lines.branches.entry((branch.line, branch.block, branch.arm)).or_default();
+                *taken = match (*taken, branch.taken) {
+                    (Some(a), Some(b)) => Some(a + b),
+                    (a, b) => a.or(b),
+                };
+            }
+        }
+        self.files = files.into_iter().map(|(path, lines)| lines.into_file(path)).collect();
+    }
+
+    /// The report in lcov's tracefile format, as read by genhtml, Codecov and editor plugins.
+    pub fn to_lcov(&self) -> String {
+        let mut out = String::new();
+        for file in &self.files {
+            writeln!(out, "TN:").unwrap();
+            writeln!(out, "SF:{}", file.path).unwrap();
+            let functions: Vec<_> = self
+                .functions
+                .iter()
+                .filter_map(|function| function.location.as_ref().map(|location| (function, location)))
+                .filter(|(_, location)| location.file == file.path)
+                .collect();
+            for (function, location) in &functions {
+                writeln!(out, "FN:{},{}", location.line, function.name).unwrap();
+            }
+            for (function, _) in &functions {
+                writeln!(out, "FNDA:{},{}", function.calls, function.name).unwrap();
+            }
+            writeln!(out, "FNF:{}", functions.len()).unwrap();
+            writeln!(out, "FNH:{}", functions.iter().filter(|(function, _)| function.calls > 0).count()).unwrap();
+            for branch in &file.branches {
+                let taken = branch.taken.map_or("-".to_string(), |taken| taken.to_string());
+                writeln!(out, "BRDA:{},{},{},{}", branch.line, branch.block, branch.arm, taken).unwrap();
+            }
+            writeln!(out, "BRF:{}", file.branches.len()).unwrap();
+            writeln!(out, "BRH:{}", file.branches.iter().filter(|branch| branch.taken.unwrap_or(0) > 0).count()).unwrap();
+            for line in &file.lines {
+                writeln!(out, "DA:{},{}", line.line, line.hits).unwrap();
+            }
+            writeln!(out, "LF:{}", file.lines.len()).unwrap();
+            writeln!(out, "LH:{}", file.lines.iter().filter(|line| line.hits > 0).count()).unwrap();
+            writeln!(out, "end_of_record").unwrap();
+        }
+        out
+    }
+
+    /// A standalone HTML page with totals, per-file and per-function tables and uncovered lines.
+    pub fn to_html(&self) -> String {
+        let cell = |count: CoverageCount| {
+            let class = match count.percent() {
+                p if p >= 90.0 => "high",
+                p if p >= 60.0 => "medium",
+                _ => "low",
+            };
+            format!("<td class=\"{}\">{:.1}% ({}/{})</td>", class, count.percent(), count.covered, count.total)
+        };
+
+        let mut html = String::from(concat!(
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Coverage</title><style>\n",
+            "body{font-family:sans-serif;margin:2em}table{border-collapse:collapse;margin-bottom:2em}\n",
+            "td,th{border:1px solid #ccc;padding:4px 8px;text-align:left}code{font-size:90%}\n",
+            ".high{background:#c8f0c8}.medium{background:#f8f0b0}.low{background:#f4c0c0}\n",
+            "</style></head><body>\n<h1>Coverage</h1>\n",
+        ));
+        html.push_str("<table><tr><th>Lines</th><th>Branches</th><th>Instructions</th></tr>\n<tr>");
+        html.push_str(&(cell(self.lines()) + &cell(self.branches()) + &cell(self.instructions())));
+        html.push_str("</tr></table>\n");
+
+        if !self.files.is_empty() {
+            html.push_str("<h2>Files</h2>\n<table><tr><th>File</th><th>Lines</th><th>Branches</th><th>Uncovered lines</th></tr>\n");
+            for file in &self.files {
+                let lines = CoverageCount::default()
+                    .add(file.lines.iter().filter(|line| line.hits > 0).count(), file.lines.len());
+                let branches = CoverageCount::default()
+                    .add(file.branches.iter().filter(|branch| branch.taken.unwrap_or(0) > 0).count(), file.branches.len());
+                let uncovered: Vec<String> =
+                    file.lines.iter().filter(|line| line.hits == 0).map(|line| line.line.to_string()).collect();
+                writeln!(
+                    html,
+                    "<tr><td><code>{}</code></td>{}{}<td>{}</td></tr>",
+                    escape(&file.path),
+                    cell(lines),
+                    cell(branches),
+                    uncovered.join(", ")
+                )
+                .unwrap();
+            }
+            html.push_str("</table>\n");
+        }
+
+        html.push_str("<h2>Functions</h2>\n<table><tr><th>Function</th><th>Calls</th><th>Instructions</th><th>Branches</th><th>Source</th></tr>\n");
+        for function in &self.functions {
+            writeln!(
+                html,
+                "<tr><td><code>{}</code></td><td>{}</td>{}{}<td>{}</td></tr>",
+                escape(&function.name),
+                function.calls,
+                cell(CoverageCount { covered: function.covered_instructions, total: function.instructions }),
+                cell(CoverageCount { covered: function.covered_branches, total: function.branches }),
+                function.location.as_ref().map(|location| escape(&location.to_string())).unwrap_or_default()
+            )
+            .unwrap();
+        }
+        html.push_str("</table>\n</body></html>\n");
+        html
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ExecutionContext, StylusRuntime};
+
+    #[test]
+    fn test_coverage_counts_instructions_and_branch_arms() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (func (export "pick") (param $n i64) (result i64)
+                    (if (result i64) (i64.gt_s (local.get $n) (i64.const 0))
+                        (then (i64.const 1))
+                        (else (i64.const -1))))
+                (func (export "unused") (result i64)
+                    (i64.const 7))
+            )
+        "#).unwrap();
+        let mut runtime = StylusRuntime::new(&wasm).unwrap();
+        runtime.enable_coverage(true);
+        assert_eq!(runtime.execute_function("pick", &[5]).unwrap().return_value, 1);
+
+        let report = runtime.coverage().unwrap();
+        let pick = report.functions.iter().find(|function| function.name == "pick").unwrap();
+        assert_eq!((pick.calls, pick.branches, pick.covered_branches), (1, 2, 1));
+        assert!(pick.covered_instructions < pick.instructions);
+        let unused = report.functions.iter().find(|function| function.name == "unused").unwrap();
+        assert_eq!((unused.calls, unused.covered_instructions), (0, 0));
+        assert!(report.percent() < 100.0);
+
+        runtime.execute_function("pick", &[-5]).unwrap();
+        let report = runtime.coverage().unwrap();
+        assert_eq!(report.branches(), CoverageCount { covered: 2, total: 2 });
+
+        let mut merged = CoverageReport::default();
+        merged.files.push(FileCoverage {
+            path: "/work/src/lib.rs".to_string(),
+            lines: vec![LineCoverage { line: 3, hits: 0 }, LineCoverage { line: 4, hits: 2 }],
+            branches: vec![BranchCoverage { line: 3, block: 0, arm: 0, taken: None }],
+        });
+        merged.merge(&report);
+        assert_eq!(merged.lines(), CoverageCount { covered: 1, total: 2 });
+        let lcov = merged.to_lcov();
+        assert!(lcov.contains("SF:/work/src/lib.rs\n"));
+        assert!(lcov.contains("BRDA:3,0,0,-\n"));
+        assert!(lcov.contains("DA:4,2\nLF:2\nLH:1\nend_of_record\n"));
+        assert!(merged.to_html().contains("<code>/work/src/lib.rs</code>"));
+
+        // Merging the same code again adds to its functions instead of listing them twice.
+        merged.merge(&report);
+        assert_eq!(merged.functions.len(), report.functions.len());
+        let pick = merged.functions.iter().find(|function| function.name == "pick").unwrap();
+        assert_eq!((pick.calls, pick.covered_branches), (4, 2));
+    }
+
+    #[test]
+    fn test_nested_calls_are_covered_without_changing_gas() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (import "vm_hooks" "contract_address" (func $address (param i32)))
+                (import "vm_hooks" "call_contract" (func $call (param i32 i32 i32 i32 i64 i32) (result i32)))
+                (memory (export "memory") 1)
+                (func (export "run") (result i32)
+                    (call $address (i32.const 0))
+                    (call $call (i32.const 0) (i32.const 64) (i32.const 1) (i32.const 96) (i64.const 100000) (i32.const 128)))
+                (func (export "user_entrypoint") (param $len i32) (result i32)
+                    (if (result i32) (local.get $len)
+                        (then (i32.const 0))
+                        (else (i32.const 1))))
+            )
+        "#).unwrap();
+        let mut runtime = StylusRuntime::new(&wasm).unwrap();
+        let plain = runtime.execute_function("run", &[]).unwrap();
+        runtime.enable_coverage(true);
+        let covered = runtime.execute_function("run", &[]).unwrap();
+        assert_eq!((covered.return_value, covered.gas_used), (0, plain.gas_used));
+        // The limit is enforced at the same points, so a limit the plain call fits in still fits.
+        runtime.context_mut().gas_limit = plain.gas_used;
+        assert!(runtime.execute_function("run", &[]).is_ok());
+        runtime.context_mut().gas_limit = ExecutionContext::default().gas_limit;
+
+        let report = runtime.coverage().unwrap();
+        let entrypoint = report.functions.iter().find(|function| function.name == "user_entrypoint").unwrap();
+        assert_eq!((entrypoint.calls, entrypoint.covered_branches), (1, 1));
+    }
+}
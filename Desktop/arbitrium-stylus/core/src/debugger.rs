@@ -0,0 +1,519 @@
+use crate::backtrace::Backtrace;
+use crate::console::ConsoleLine;
+use crate::error::Trap;
+use crate::interpreter::{Checkpoint, Machine, Step, Val};
+use crate::source_map::SourceFrame;
+use crate::world::{Address, Bytes32, Log};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Where execution should pause.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Breakpoint {
+    /// The first instruction of a function.
+    Function(u32),
+    /// The instruction at a byte offset into the code section, as printed in backtraces.
+    Offset(u64),
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Function(index) => write!(f, "func {}", index),
+            Breakpoint::Offset(offset) => write!(f, "0x{:x}", offset),
+        }
+    }
+}
+
+/// Why `step_*` or `resume` returned control.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StopReason {
+    Breakpoint(usize),
+    Step,
+    Finished(Vec<Val>),
+    Trapped(Trap),
+}
+
+/// The instruction execution is paused before.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub func_index: u32,
+    pub function: Option<String>,
+    /// Index of the instruction within the function body.
+    pub pc: usize,
+    pub module_offset: u64,
+    /// Offset into the code section, which breakpoints and backtraces use.
+    pub code_offset: Option<u64>,
+    pub instruction: String,
+    /// Source positions from DWARF, innermost inlined function first.
+    pub source: Vec<SourceFrame>,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.function {
+            Some(name) => write!(f, "{}", name)?,
+            None => write!(f, "<func {}>", self.func_index)?,
+        }
+        write!(f, " @ 0x{:x}: {}", self.code_offset.unwrap_or(self.module_offset), self.instruction)?;
+        if let Some(location) = self.source.first().and_then(|frame| frame.location.as_ref()) {
+            write!(f, "\n    at {}", location)?;
+        }
+        Ok(())
+    }
+}
+
+enum Status {
+    Paused,
+    Finished(Vec<Val>),
+    Trapped(Trap),
+}
+
+/// Steps between the snapshots a replay keeps for stepping backwards.
+const CHECKPOINT_INTERVAL: u64 = 1024;
+
+/// Runs one contract call under control of breakpoints and single-stepping.
+///
+/// Created paused before the first instruction by `StylusRuntime::debug`, or by
+/// `Recording::replay`, which can also step backwards.
+pub struct Debugger {
+    machine: Machine,
+    breakpoints: BTreeMap<usize, (Breakpoint, (u32, usize))>,
+    next_breakpoint: usize,
+    status: Status,
+    /// Snapshots of a replay in step order, the first taken before the call starts.
+    checkpoints: Option<Vec<Checkpoint>>,
+}
+
+impl Debugger {
+    pub(crate) fn new(machine: Machine) -> Self {
+        Self {
+            machine,
+            breakpoints: BTreeMap::new(),
+            next_breakpoint: 1,
+            status: Status::Paused,
+            checkpoints: None,
+        }
+    }
+
+    pub(crate) fn replaying(machine: Machine) -> Self {
+        let checkpoints = Some(vec![machine.checkpoint()]);
+        Self { checkpoints, ..Self::new(machine) }
+    }
+
+    /// Adds a breakpoint and returns its id.
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> Result<usize> {
+        let program = self.machine.program();
+        let position = match breakpoint {
+            Breakpoint::Function(index) => match program.function(index) {
+                Some(function) if function.import.is_none() => (index, 0),
+                Some(_) => return Err(anyhow!("Function {} is an import", index)),
+                None => return Err(anyhow!("Function {} not found", index)),
+            },
+            Breakpoint::Offset(offset) => self
+                .machine
+                .symbols()
+                .module_offset(offset)
+                .and_then(|module_offset| program.locate(module_offset))
+                .ok_or_else(|| anyhow!("No instruction at offset 0x{:x}", offset))?,
+        };
+        let id = self.next_breakpoint;
+        self.next_breakpoint += 1;
+        self.breakpoints.insert(id, (breakpoint, position));
+        Ok(id)
+    }
+
+    /// Breaks where `line` of `file` begins, using DWARF line tables. Returns the line the
+    /// breakpoint actually landed on and one breakpoint id per place the line starts.
+    pub fn add_line_breakpoint(&mut self, file: &str, line: u32) -> Result<(u32, Vec<usize>)> {
+        let symbols = self.machine.symbols().clone();
+        let (resolved, addresses) = symbols
+            .source_map()
+            .line_addresses(file, line)
+            .ok_or_else(|| anyhow!("No code at {}:{}", file, line))?;
+        let mut ids = Vec::new();
+        for address in addresses {
+            if let Ok(id) = self.add_breakpoint(Breakpoint::Offset(address)) {
+                ids.push(id);
+            }
+        }
+        if ids.is_empty() {
+            return Err(anyhow!("No instruction starts {}:{}", file, line));
+        }
+        Ok((resolved, ids))
+    }
+
+    pub fn remove_breakpoint(&mut self, id: usize) -> bool {
+        self.breakpoints.remove(&id).is_some()
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = (usize, Breakpoint)> + '_ {
+        self.breakpoints.iter().map(|(&id, &(breakpoint, _))| (id, breakpoint))
+    }
+
+    /// Finds a function by export name, or by its demangled or raw symbol name.
+    pub fn function_index(&self, name: &str) -> Option<u32> {
+        let program = self.machine.program();
+        let symbols = self.machine.symbols();
+        program.export(name).or_else(|| {
+            program.functions.iter().map(|function| function.index).find(|&index| {
+                symbols.raw_name(index) == Some(name) || symbols.function_name(index).as_deref() == Some(name)
+            })
+        })
+    }
+
+    /// Executes one instruction, entering calls.
+    pub fn step_in(&mut self) -> StopReason {
+        self.run_until(|_, _| true)
+    }
+
+    /// Executes one instruction, running any call it makes to completion.
+    pub fn step_over(&mut self) -> StopReason {
+        self.run_until(|machine, depth| machine.frames().len() <= depth)
+    }
+
+    /// Runs until the current function returns to its caller.
+    pub fn step_out(&mut self) -> StopReason {
+        self.run_until(|machine, depth| machine.frames().len() < depth)
+    }
+
+    /// Runs until execution reaches a different source line. With `over`, lines of
+    /// called functions are skipped; without debug info this steps one instruction.
+    pub fn step_line(&mut self, over: bool) -> StopReason {
+        let Some(start) = source_line(&self.machine) else {
+            return if over { self.step_over() } else { self.step_in() };
+        };
+        self.run_until(|machine, depth| {
+            if over && machine.frames().len() > depth {
+                return false;
+            }
+            source_line(machine).is_some_and(|line| line != start)
+        })
+    }
+
+    /// Runs until a breakpoint, the end of the call, or a trap.
+    pub fn resume(&mut self) -> StopReason {
+        self.run_until(|_, _| false)
+    }
+
+    fn run_until(&mut self, done: impl Fn(&Machine, usize) -> bool) -> StopReason {
+        if let Some(reason) = self.stopped() {
+            return reason;
+        }
+
+        let depth = self.machine.frames().len();
+        let mut first = true;
+        loop {
+            // The instruction we are paused on has already reported its breakpoint.
+            if !first {
+                if let Some(id) = self.breakpoint_at_position() {
+                    return StopReason::Breakpoint(id);
+                }
+            }
+            first = false;
+
+            let step = self.machine.step();
+            self.checkpoint_if_due();
+            match step {
+                Ok(Step::Running) if done(&self.machine, depth) => return StopReason::Step,
+                Ok(Step::Running) => {}
+                Ok(Step::Finished(results)) => {
+                    self.status = Status::Finished(results.clone());
+                    return StopReason::Finished(results);
+                }
+                Err(e) => {
+                    let trap = Trap::new(e.message(), self.machine.console().to_vec(), self.machine.backtrace());
+                    self.status = Status::Trapped(trap.clone());
+                    return StopReason::Trapped(trap);
+                }
+            }
+        }
+    }
+
+    fn checkpoint_if_due(&mut self) {
+        let steps = self.machine.steps();
+        let Some(checkpoints) = &mut self.checkpoints else { return };
+        let last = checkpoints.last().map(Checkpoint::steps).unwrap_or(0);
+        if steps.is_multiple_of(CHECKPOINT_INTERVAL) && steps > last {
+            checkpoints.push(self.machine.checkpoint());
+        }
+    }
+
+    /// Steps backwards one instruction through a replayed recording.
+    pub fn step_back(&mut self) -> Result<StopReason> {
+        let start = self.first_step()?;
+        let steps = self.machine.steps();
+        if steps <= start {
+            return Err(anyhow!("Already at the start of the recording"));
+        }
+        self.rewind_to(steps - 1)?;
+        Ok(StopReason::Step)
+    }
+
+    /// Runs backwards to the most recent breakpoint, or to the start of the recording.
+    pub fn reverse_resume(&mut self) -> Result<StopReason> {
+        let start = self.first_step()?;
+        let current = self.machine.steps();
+        self.rewind_to(start)?;
+
+        let mut hit = None;
+        while self.machine.steps() < current {
+            if let Some(id) = self.breakpoint_at_position() {
+                hit = Some((self.machine.steps(), id));
+            }
+            self.machine.step().map_err(|e| anyhow!("Replay failed: {}", e.message()))?;
+            self.checkpoint_if_due();
+        }
+        match hit {
+            Some((step, id)) => {
+                self.rewind_to(step)?;
+                Ok(StopReason::Breakpoint(id))
+            }
+            None => {
+                self.rewind_to(start)?;
+                Ok(StopReason::Step)
+            }
+        }
+    }
+
+    /// Moves a replay to just before instruction `step`, replaying from the nearest snapshot.
+    pub fn rewind_to(&mut self, step: u64) -> Result<()> {
+        let checkpoints = self
+            .checkpoints
+            .as_ref()
+            .ok_or_else(|| anyhow!("Only recorded runs can step backwards"))?;
+        let checkpoint = checkpoints
+            .iter()
+            .rev()
+            .find(|checkpoint| checkpoint.steps() <= step)
+            .ok_or_else(|| anyhow!("Step {} is before the start of the recording", step))?;
+        self.machine.restore(checkpoint);
+        self.status = Status::Paused;
+        while self.machine.steps() < step {
+            match self.machine.step() {
+                Ok(Step::Running) => self.checkpoint_if_due(),
+                Ok(Step::Finished(_)) => return Err(anyhow!("The recording ends before step {}", step)),
+                Err(e) => return Err(anyhow!("Replay failed: {}", e.message())),
+            }
+        }
+        Ok(())
+    }
+
+    fn first_step(&self) -> Result<u64> {
+        self.checkpoints
+            .as_ref()
+            .and_then(|checkpoints| checkpoints.first())
+            .map(Checkpoint::steps)
+            .ok_or_else(|| anyhow!("Only recorded runs can step backwards"))
+    }
+
+    /// Instructions executed so far, including any start function.
+    pub fn steps(&self) -> u64 {
+        self.machine.steps()
+    }
+
+    fn stopped(&self) -> Option<StopReason> {
+        match &self.status {
+            Status::Paused => None,
+            Status::Finished(results) => Some(StopReason::Finished(results.clone())),
+            Status::Trapped(trap) => Some(StopReason::Trapped(trap.clone())),
+        }
+    }
+
+    fn breakpoint_at_position(&self) -> Option<usize> {
+        let position = self.machine.position()?;
+        self.breakpoints
+            .iter()
+            .find(|(_, (_, at))| *at == position)
+            .map(|(&id, _)| id)
+    }
+
+    /// The breakpoint on the instruction we are paused before, if any.
+    pub fn current_breakpoint(&self) -> Option<usize> {
+        match self.status {
+            Status::Paused => self.breakpoint_at_position(),
+            _ => None,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        matches!(self.status, Status::Paused)
+    }
+
+    pub fn location(&self) -> Option<Location> {
+        let (func_index, pc) = self.machine.position()?;
+        let module_offset = self.machine.module_offset()?;
+        let symbols = self.machine.symbols();
+        Some(Location {
+            func_index,
+            function: symbols
+                .function_name(func_index)
+                .or_else(|| self.machine.program().export_name(func_index).map(str::to_string)),
+            pc,
+            module_offset,
+            code_offset: symbols.code_offset(module_offset),
+            instruction: self.machine.current_instr()?.to_string(),
+            source: symbols.source_frames(module_offset),
+        })
+    }
+
+    /// Parameters and locals of the innermost frame.
+    pub fn locals(&self) -> &[Val] {
+        self.machine.frames().last().map(|frame| frame.locals.as_slice()).unwrap_or(&[])
+    }
+
+    /// The innermost frame's operand stack, bottom first.
+    pub fn stack(&self) -> &[Val] {
+        self.machine.stack()
+    }
+
+    pub fn globals(&self) -> &[Val] {
+        self.machine.globals()
+    }
+
+    pub fn read_memory(&self, address: u32, len: usize) -> Result<Vec<u8>> {
+        let start = address as usize;
+        self.machine
+            .memory()
+            .get(start..start.saturating_add(len))
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| anyhow!("Memory range 0x{:x}+{} is out of bounds", address, len))
+    }
+
+    pub fn memory_size(&self) -> usize {
+        self.machine.memory().len()
+    }
+
+    pub fn backtrace(&self) -> Backtrace {
+        match &self.status {
+            Status::Trapped(trap) => trap.backtrace.clone(),
+            _ => self.machine.backtrace(),
+        }
+    }
+
+    /// Gas used so far, instructions and host calls included. Once the call finishes this is
+    /// what `ExecutionResult::gas_used` reports, as `stylus run` and `stylus test` show it.
+    pub fn gas_used(&self) -> u64 {
+        self.machine.gas_used()
+    }
+
+    pub fn console(&self) -> &[ConsoleLine] {
+        self.machine.console()
+    }
+
+    /// The address whose storage the call reads and writes.
+    pub fn address(&self) -> Address {
+        self.machine.env().frame.address
+    }
+
+    /// The contract's current storage slots, sorted by key. A replay only knows
+    /// the slots the recorded call touched.
+    pub fn storage(&self) -> Vec<(Bytes32, Bytes32)> {
+        if let Some(trace) = self.machine.replay_trace() {
+            return trace.storage_at(self.machine.steps());
+        }
+        let world = self.machine.env().world();
+        let mut slots: Vec<_> = world
+            .account(&self.address())
+            .map(|account| account.storage.iter().map(|(k, v)| (*k, *v)).collect())
+            .unwrap_or_default();
+        slots.sort();
+        slots
+    }
+
+    pub fn output(&self) -> &[u8] {
+        &self.machine.env().output
+    }
+
+    pub fn logs(&self) -> &[Log] {
+        &self.machine.env().logs
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+}
+
+/// The innermost source file and line of the next instruction.
+fn source_line(machine: &Machine) -> Option<(String, u32)> {
+    let symbols = machine.symbols();
+    let code_offset = symbols.code_offset(machine.module_offset()?)?;
+    let location = symbols.source_map().location(code_offset)?;
+    Some((location.file, location.line)).filter(|(_, line)| *line > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StylusRuntime;
+
+    #[test]
+    fn test_breakpoints_and_stepping() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (memory (export "memory") 1)
+                (global $counter (mut i32) (i32.const 7))
+                (func $double (param $x i64) (result i64)
+                    local.get $x
+                    local.get $x
+                    i64.add)
+                (func (export "run") (param $n i64) (result i64)
+                    (local $acc i64)
+                    (i32.store16 (i32.const 16) (i32.const 0xabcd))
+                    local.get $n
+                    call $double
+                    local.set $acc
+                    local.get $acc
+                    i64.const 1
+                    i64.add)
+            )
+        "#).unwrap();
+        let mut runtime = StylusRuntime::new(&wasm).unwrap();
+
+        let mut debugger = runtime.debug("run", &[5]).unwrap();
+        let double = debugger.function_index("double").unwrap();
+        let id = debugger.add_breakpoint(Breakpoint::Function(double)).unwrap();
+        assert_eq!(debugger.resume(), StopReason::Breakpoint(id));
+        assert_eq!(debugger.location().unwrap().func_index, double);
+        assert_eq!(debugger.locals(), &[Val::I64(5)]);
+
+        assert_eq!(debugger.step_out(), StopReason::Step);
+        assert_eq!(debugger.stack(), &[Val::I64(10)]);
+        assert_eq!(debugger.read_memory(16, 2).unwrap(), vec![0xcd, 0xab]);
+        assert_eq!(debugger.globals(), &[Val::I32(7)]);
+        assert_eq!(debugger.resume(), StopReason::Finished(vec![Val::I64(11)]));
+        assert!(debugger.gas_used() > 0);
+
+        // Stepping over the call never stops inside `double`.
+        let mut debugger = runtime.debug("run", &[1]).unwrap();
+        while debugger.step_over() == StopReason::Step {
+            assert_ne!(debugger.location().unwrap().func_index, double);
+        }
+        assert_eq!(debugger.step_in(), StopReason::Finished(vec![Val::I64(3)]));
+    }
+
+    #[test]
+    fn test_offset_breakpoints_use_backtrace_offsets() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (func (export "run") (param $n i64) (result i64)
+                    (if (i64.eqz (local.get $n)) (then unreachable))
+                    local.get $n)
+            )
+        "#).unwrap();
+        let mut runtime = StylusRuntime::new(&wasm).unwrap();
+        let StopReason::Trapped(trap) = runtime.debug("run", &[0]).unwrap().resume() else {
+            panic!("expected a trap");
+        };
+        let offset = trap.backtrace.frames[0].code_offset.unwrap();
+
+        let mut debugger = runtime.debug("run", &[0]).unwrap();
+        let id = debugger.add_breakpoint(Breakpoint::Offset(offset)).unwrap();
+        assert_eq!(debugger.resume(), StopReason::Breakpoint(id));
+        let location = debugger.location().unwrap();
+        assert_eq!((location.code_offset, location.instruction.as_str()), (Some(offset), "unreachable"));
+        assert!(location.to_string().contains(&format!("@ 0x{:x}", offset)));
+    }
+}
@@ -0,0 +1,84 @@
+use crate::backtrace::Backtrace;
+use crate::console::ConsoleLine;
+use crate::host::hex_address;
+use crate::panic::{self, ContractPanic};
+use crate::world::Address;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StateChange {
+    StorageWrite,
+    LogEmission,
+    ValueTransfer,
+    ContractCreation,
+}
+
+impl fmt::Display for StateChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            StateChange::StorageWrite => "storage write",
+            StateChange::LogEmission => "log emission",
+            StateChange::ValueTransfer => "value transfer",
+            StateChange::ContractCreation => "contract creation",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A state-changing operation attempted while executing in read-only mode.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StaticCallViolation {
+    pub operation: StateChange,
+    pub address: Address,
+}
+
+impl fmt::Display for StaticCallViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Static call violation: {} attempted by {} in read-only mode",
+            self.operation,
+            hex_address(&self.address)
+        )
+    }
+}
+
+impl std::error::Error for StaticCallViolation {}
+
+/// A contract call that trapped, with whatever the contract printed before it did.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trap {
+    pub message: String,
+    pub console: Vec<ConsoleLine>,
+    /// The Rust panic behind the trap, when the contract printed one.
+    pub panic: Option<ContractPanic>,
+    pub backtrace: Backtrace,
+}
+
+impl Trap {
+    pub fn new(message: String, console: Vec<ConsoleLine>, backtrace: Backtrace) -> Self {
+        let panic = panic::find_panic(&console);
+        Self {
+            message,
+            console,
+            panic,
+            backtrace,
+        }
+    }
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Function execution failed: {}", self.message)?;
+        if let Some(panic) = &self.panic {
+            write!(f, "\n  {}", panic)?;
+        }
+        if !self.backtrace.is_empty() {
+            write!(f, "\nBacktrace:\n{}", self.backtrace)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Trap {}
@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+/// L1 gas charged per byte of compressed transaction data (EIP-2028 non-zero byte cost).
+pub(crate) const L1_GAS_PER_BYTE: u64 = 16;
+
+/// Approximate size of the signed transaction fields that surround the calldata
+/// (nonce, gas fields, destination, value and signature).
+const TX_ENVELOPE_BYTES: u64 = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct L1Pricing {
+    /// Brotli quality ArbOS uses when measuring how much a transaction compresses.
+    pub compression_level: u32,
+    /// Multiplier applied to the estimate, in basis points (10_000 = no padding).
+    pub padding_bips: u64,
+}
+
+impl Default for L1Pricing {
+    fn default() -> Self {
+        Self {
+            compression_level: 1,
+            padding_bips: 10_000,
+        }
+    }
+}
+
+/// The L1 data-posting component of a transaction's cost.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct L1Cost {
+    pub calldata_bytes: u64,
+    pub compressed_bytes: u64,
+    pub l1_gas: u64,
+    pub fee_wei: u128,
+    /// The fee expressed in L2 gas at the current L2 base fee, as Arbitrum reports `gasUsedForL1`.
+    pub l2_gas: u64,
+}
+
+impl L1Pricing {
+    pub fn estimate(&self, calldata: &[u8], l1_base_fee: u128, l2_base_fee: u128) -> L1Cost {
+        let compressed_bytes = self.compressed_size(calldata) + TX_ENVELOPE_BYTES;
+        let l1_gas = compressed_bytes * L1_GAS_PER_BYTE;
+        let fee_wei = l1_gas as u128 * l1_base_fee * self.padding_bips as u128 / 10_000;
+        let l2_gas = if l2_base_fee == 0 {
+            0
+        } else {
+            u64::try_from(fee_wei.div_ceil(l2_base_fee)).unwrap_or(u64::MAX)
+        };
+
+        L1Cost {
+            calldata_bytes: calldata.len() as u64,
+            compressed_bytes,
+            l1_gas,
+            fee_wei,
+            l2_gas,
+        }
+    }
+
+    fn compressed_size(&self, data: &[u8]) -> u64 {
+        if data.is_empty() {
+            return 0;
+        }
+        let mut compressed = Vec::new();
+        {
+            let mut writer = brotli::CompressorWriter::new(&mut compressed, 4096, self.compression_level, 22);
+            if writer.write_all(data).is_err() {
+                return data.len() as u64;
+            }
+        }
+        compressed.len().min(data.len()) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compressible_calldata_costs_less() {
+        let pricing = L1Pricing::default();
+        let zeros = vec![0u8; 1024];
+        let noisy: Vec<u8> = (0..1024u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8).collect();
+
+        let cheap = pricing.estimate(&zeros, 1_000_000_000, 100_000_000);
+        let expensive = pricing.estimate(&noisy, 1_000_000_000, 100_000_000);
+
+        assert_eq!(cheap.calldata_bytes, 1024);
+        assert!(cheap.compressed_bytes < expensive.compressed_bytes);
+        assert!(cheap.fee_wei < expensive.fee_wei);
+        assert_eq!(expensive.fee_wei, expensive.l1_gas as u128 * 1_000_000_000);
+        assert_eq!(expensive.l2_gas as u128, expensive.fee_wei / 100_000_000);
+    }
+}
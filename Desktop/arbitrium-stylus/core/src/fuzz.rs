@@ -0,0 +1,776 @@
+//! Coverage-guided fuzzing of a contract's exports or its `user_entrypoint`.
+//!
+//! Inputs that reach new instructions or branch arms join the corpus and are mutated further.
+//! Every input runs in the interpreter against the world as it was before the input, so
+//! out-of-gas is found against `ExecutionContext::gas_limit` and inputs do not affect each other.
+
+use crate::abi::{self, keccak256};
+use crate::error::{StaticCallViolation, Trap};
+use crate::imports::StubPolicy;
+use crate::interpreter::{Instr, Val};
+use crate::{CoverageReport, ExecutionResult, StylusRuntime};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// What each input is sent to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FuzzTarget {
+    /// An export, called with one i64 per parameter.
+    Function(String),
+    /// `user_entrypoint`, called with calldata.
+    Entrypoint,
+}
+
+impl FuzzTarget {
+    pub fn name(&self) -> &str {
+        match self {
+            FuzzTarget::Function(name) => name,
+            FuzzTarget::Entrypoint => "user_entrypoint",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FuzzInput {
+    Args(Vec<i64>),
+    Calldata(Vec<u8>),
+}
+
+impl FuzzInput {
+    /// A short stable name for the input, used for corpus and crash files.
+    pub fn id(&self) -> String {
+        let hash = match self {
+            FuzzInput::Args(args) => keccak256(&args.iter().flat_map(|arg| arg.to_be_bytes()).collect::<Vec<_>>()),
+            FuzzInput::Calldata(calldata) => keccak256(calldata),
+        };
+        abi::hex(&hash[..8])[2..].to_string()
+    }
+}
+
+impl fmt::Display for FuzzInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FuzzInput::Args(args) => {
+                let args: Vec<String> = args.iter().map(ToString::to_string).collect();
+                write!(f, "({})", args.join(", "))
+            }
+            FuzzInput::Calldata(calldata) => write!(f, "({})", abi::hex(calldata)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FailureKind {
+    Trap,
+    /// A trap the contract explained with a Rust panic message.
+    Panic,
+    OutOfGas,
+    /// The named invariant did not hold after a successful call.
+    Invariant(String),
+}
+
+impl FailureKind {
+    /// The kind as it appears in crash file names, with the invariant's name for violations.
+    pub fn slug(&self) -> String {
+        match self {
+            FailureKind::Trap => "trap".to_string(),
+            FailureKind::Panic => "panic".to_string(),
+            FailureKind::OutOfGas => "out-of-gas".to_string(),
+            FailureKind::Invariant(name) => format!("invariant-{}", name),
+        }
+    }
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailureKind::Trap => f.write_str("trap"),
+            FailureKind::Panic => f.write_str("panic"),
+            FailureKind::OutOfGas => f.write_str("out of gas"),
+            FailureKind::Invariant(name) => write!(f, "invariant {} violated", name),
+        }
+    }
+}
+
+/// An input that made the contract fail, and how.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Failure {
+    pub kind: FailureKind,
+    pub message: String,
+    /// `file:line:column` of the panic, for panics that print one.
+    pub location: Option<String>,
+    pub target: FuzzTarget,
+    pub input: FuzzInput,
+    pub gas_limit: u64,
+    /// How the runtime linked missing imports, which the reproducing test has to match.
+    pub stub_policy: StubPolicy,
+}
+
+impl Failure {
+    /// Whether both failures are the same bug: a panic at the same place, or otherwise the same message.
+    pub fn same_bug(&self, other: &Failure) -> bool {
+        self.kind == other.kind
+            && match (&self.location, &other.location) {
+                (Some(location), Some(other)) => location == other,
+                _ => self.message == other.message,
+            }
+    }
+
+    /// A Rust test that sends the input to the contract at `wasm` again, checking the given
+    /// invariant exports afterwards. It fails for as long as the bug does.
+    pub fn to_test(&self, wasm: &Path, invariants: &[String]) -> String {
+        let target = match &self.target {
+            FuzzTarget::Function(name) => format!("FuzzTarget::Function({:?}.to_string())", name),
+            FuzzTarget::Entrypoint => "FuzzTarget::Entrypoint".to_string(),
+        };
+        let input = match &self.input {
+            FuzzInput::Args(args) => {
+                let args: Vec<String> = args.iter().map(ToString::to_string).collect();
+                format!("FuzzInput::Args(vec![{}])", args.join(", "))
+            }
+            FuzzInput::Calldata(calldata) => {
+                format!("FuzzInput::Calldata(stylus_core::abi::parse_hex({:?}).unwrap())", abi::hex(calldata))
+            }
+        };
+        let invariants: Vec<String> = invariants.iter().map(|name| format!("Invariant::export({:?})", name)).collect();
+        format!(
+            r#"//! {kind} found by `stylus fuzz` in {function}: {message}
+
+use stylus_core::fuzz::{{self, FuzzInput, FuzzTarget, Invariant}};
+use stylus_core::{{StubPolicy, StylusRuntime}};
+
+#[test]
+fn fuzz_{slug}_{id}() {{
+    let wasm = std::fs::read({wasm:?}).expect("Failed to read contract");
+    let mut runtime = StylusRuntime::new(&wasm).unwrap();
+    runtime.context_mut().gas_limit = {gas_limit};
+    runtime.set_stub_policy(StubPolicy::{stub_policy:?});
+    // Run in the interpreter like the fuzzer did, which enforces the gas limit.
+    runtime.enable_coverage(true);
+
+    let target = {target};
+    let input = {input};
+    let invariants = [{invariants}];
+    let failure = fuzz::run_input(&mut runtime, &target, &input, &invariants).unwrap();
+    assert!(failure.is_none(), "{{}}", failure.unwrap());
+}}
+"#,
+            kind = self.kind,
+            function = self.target.name(),
+            message = self.message.lines().next().unwrap_or_default(),
+            slug = self.kind.slug().replace('-', "_"),
+            id = self.input.id(),
+            wasm = wasm.display().to_string(),
+            gas_limit = self.gas_limit,
+            stub_policy = self.stub_policy,
+            invariants = invariants.join(", "),
+        )
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in {}{}: {}", self.kind, self.target.name(), self.input, self.message)
+    }
+}
+
+type Check = dyn Fn(&mut StylusRuntime, &ExecutionResult) -> std::result::Result<(), String> + Send + Sync;
+
+/// A property checked after every call that did not trap or revert.
+#[derive(Clone)]
+pub struct Invariant {
+    pub name: String,
+    check: Arc<Check>,
+}
+
+impl Invariant {
+    /// `check` returns why the invariant does not hold, if it does not.
+    pub fn new<F>(name: &str, check: F) -> Self
+    where
+        F: Fn(&mut StylusRuntime, &ExecutionResult) -> std::result::Result<(), String> + Send + Sync + 'static,
+    {
+        Invariant { name: name.to_string(), check: Arc::new(check) }
+    }
+
+    /// An export taking no arguments that returns non-zero while the invariant holds.
+    pub fn export(name: &str) -> Self {
+        let export = name.to_string();
+        Self::new(name, move |runtime, _| match runtime.execute_function(&export, &[]) {
+            Ok(result) if result.return_value != 0 => Ok(()),
+            Ok(_) => Err("returned 0".to_string()),
+            Err(e) => Err(e.to_string().lines().next().unwrap_or_default().to_string()),
+        })
+    }
+}
+
+/// Sends `input` to `target` and checks `invariants`, then rolls the world back.
+///
+/// Returns the failure the input causes, if any; errors are left for inputs that cannot run
+/// at all, such as arguments for an export that does not exist.
+pub fn run_input(
+    runtime: &mut StylusRuntime,
+    target: &FuzzTarget,
+    input: &FuzzInput,
+    invariants: &[Invariant],
+) -> Result<Option<Failure>> {
+    let snapshot = runtime.world().snapshot();
+    let outcome = match (target, input) {
+        (FuzzTarget::Function(name), FuzzInput::Args(args)) => runtime.execute_function(name, args),
+        (FuzzTarget::Entrypoint, FuzzInput::Calldata(calldata)) => runtime.execute_calldata(calldata),
+        _ => Err(anyhow!("Input {} does not fit {}", input, target.name())),
+    };
+    let failure = match outcome {
+        Ok(result) if target == &FuzzTarget::Entrypoint && result.return_value != 0 => Ok(None),
+        Ok(result) => Ok(invariants.iter().find_map(|invariant| {
+            let message = (invariant.check)(runtime, &result).err()?;
+            Some((FailureKind::Invariant(invariant.name.clone()), message, None))
+        })),
+        Err(e) => classify(e).map(Some),
+    };
+    runtime.world().revert_to(snapshot);
+
+    Ok(failure?.map(|(kind, message, location)| Failure {
+        kind,
+        message,
+        location,
+        target: target.clone(),
+        input: input.clone(),
+        gas_limit: runtime.context().gas_limit,
+        stub_policy: runtime.stub_policy,
+    }))
+}
+
+/// The kind, message and panic location of a failed call, or the error back if it never ran.
+fn classify(error: anyhow::Error) -> Result<(FailureKind, String, Option<String>)> {
+    if let Some(trap) = error.downcast_ref::<Trap>() {
+        if let Some(panic) = &trap.panic {
+            let location = panic.file.as_ref().map(|file| {
+                format!("{}:{}:{}", file, panic.line.unwrap_or(0), panic.column.unwrap_or(0))
+            });
+            return Ok((FailureKind::Panic, panic.message.clone(), location));
+        }
+        let kind = if trap.message.contains("out of gas") { FailureKind::OutOfGas } else { FailureKind::Trap };
+        return Ok((kind, trap.message.clone(), None));
+    }
+    if let Some(violation) = error.downcast_ref::<StaticCallViolation>() {
+        return Ok((FailureKind::Trap, violation.to_string(), None));
+    }
+    Err(error)
+}
+
+#[derive(Debug, Clone)]
+pub struct FuzzConfig {
+    /// Inputs to generate, not counting the saved corpus replayed first.
+    pub runs: u64,
+    /// Sessions with the same seed, corpus and contract generate the same inputs.
+    pub seed: u64,
+    /// Longest calldata generated.
+    pub max_len: usize,
+    /// Where the corpus is loaded from and inputs that add coverage are saved.
+    pub corpus_dir: Option<PathBuf>,
+}
+
+impl Default for FuzzConfig {
+    fn default() -> Self {
+        Self {
+            runs: 10_000,
+            seed: 0,
+            max_len: 256,
+            corpus_dir: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FuzzReport {
+    /// Inputs run, including the saved corpus but not minimisation attempts.
+    pub runs: u64,
+    /// Inputs in the corpus at the end, saved ones included.
+    pub corpus: usize,
+    /// Inputs that added coverage this session.
+    pub new_inputs: usize,
+    /// One minimised failure per distinct bug.
+    pub failures: Vec<Failure>,
+    pub coverage: Option<CoverageReport>,
+}
+
+/// Attempts to shrink each failing input.
+const MINIMIZE_RUNS: usize = 2_000;
+
+const INTERESTING: &[i64] = &[
+    0,
+    1,
+    -1,
+    2,
+    16,
+    32,
+    255,
+    256,
+    1024,
+    4096,
+    65535,
+    65536,
+    i8::MAX as i64,
+    i8::MIN as i64,
+    i16::MAX as i64,
+    i16::MIN as i64,
+    i32::MAX as i64,
+    i32::MIN as i64,
+    u32::MAX as i64,
+    i64::MAX,
+    i64::MIN,
+];
+
+pub struct Fuzzer<'a> {
+    runtime: &'a mut StylusRuntime,
+    target: FuzzTarget,
+    config: FuzzConfig,
+    invariants: Vec<Invariant>,
+    /// Parameters of a `Function` target.
+    arity: usize,
+    /// Constants from the contract's code, such as selectors and magic numbers it compares against.
+    dictionary: Vec<i64>,
+    corpus: Vec<FuzzInput>,
+    /// Coverage before the input being run.
+    features: usize,
+    rng: Rng,
+}
+
+impl<'a> Fuzzer<'a> {
+    /// Enables coverage on `runtime`, which every input then runs with.
+    pub fn new(runtime: &'a mut StylusRuntime, target: FuzzTarget, config: FuzzConfig) -> Result<Self> {
+        let program = runtime.program()?;
+        let func = program
+            .export(target.name())
+            .ok_or_else(|| anyhow!("Function '{}' not found", target.name()))?;
+        let arity = program.signature(func).params.len();
+
+        let mut dictionary = BTreeSet::new();
+        for function in &program.functions {
+            for instr in &function.body {
+                match instr {
+                    Instr::Const(Val::I32(value)) => dictionary.insert(*value as i64),
+                    Instr::Const(Val::I64(value)) => dictionary.insert(*value),
+                    _ => false,
+                };
+            }
+        }
+
+        runtime.enable_coverage(true);
+        let rng = Rng::new(config.seed);
+        Ok(Fuzzer {
+            runtime,
+            target,
+            config,
+            invariants: Vec::new(),
+            arity,
+            dictionary: dictionary.into_iter().collect(),
+            corpus: Vec::new(),
+            features: 0,
+            rng,
+        })
+    }
+
+    pub fn add_invariant(&mut self, invariant: Invariant) {
+        self.invariants.push(invariant);
+    }
+
+    /// Replays the saved corpus, then runs `config.runs` generated inputs.
+    pub fn run(&mut self) -> Result<FuzzReport> {
+        let mut report = FuzzReport::default();
+        self.features = self.runtime.coverage_features();
+
+        let saved = match &self.config.corpus_dir {
+            Some(dir) => self.load_corpus(dir)?,
+            None => Vec::new(),
+        };
+        if saved.is_empty() {
+            let seed = match self.target {
+                FuzzTarget::Function(_) => FuzzInput::Args(vec![0; self.arity]),
+                FuzzTarget::Entrypoint => FuzzInput::Calldata(Vec::new()),
+            };
+            self.try_input(seed, true, &mut report)?;
+        }
+        for input in saved {
+            self.try_input(input, false, &mut report)?;
+        }
+
+        for _ in 0..self.config.runs {
+            let input = self.next_input();
+            self.try_input(input, true, &mut report)?;
+        }
+
+        report.corpus = self.corpus.len();
+        report.coverage = self.runtime.coverage();
+        Ok(report)
+    }
+
+    fn try_input(&mut self, input: FuzzInput, save: bool, report: &mut FuzzReport) -> Result<()> {
+        report.runs += 1;
+        if let Some(failure) = run_input(self.runtime, &self.target, &input, &self.invariants)? {
+            if !report.failures.iter().any(|known| known.same_bug(&failure)) {
+                let failure = self.minimize(failure)?;
+                report.failures.push(failure);
+            }
+            // Minimising adds coverage of its own, which should not count for the next input.
+            self.features = self.runtime.coverage_features();
+            return Ok(());
+        }
+
+        let features = self.runtime.coverage_features();
+        if features > self.features {
+            self.features = features;
+            if save {
+                self.save(&input)?;
+                report.new_inputs += 1;
+            }
+            self.corpus.push(input);
+        }
+        Ok(())
+    }
+
+    /// Shrinks a failing input for as long as it keeps failing with the same bug.
+    pub fn minimize(&mut self, failure: Failure) -> Result<Failure> {
+        let mut best = failure;
+        let mut budget = MINIMIZE_RUNS;
+        'shrink: loop {
+            for candidate in shrink(&best.input) {
+                if budget == 0 {
+                    break 'shrink;
+                }
+                budget -= 1;
+                if let Some(failure) = run_input(self.runtime, &self.target, &candidate, &self.invariants)? {
+                    if failure.same_bug(&best) {
+                        best = failure;
+                        continue 'shrink;
+                    }
+                }
+            }
+            break;
+        }
+        Ok(best)
+    }
+
+    fn load_corpus(&self, dir: &Path) -> Result<Vec<FuzzInput>> {
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let entries = std::fs::read_dir(dir).map_err(|e| anyhow!("Failed to read corpus {}: {}", dir.display(), e))?;
+        let mut paths: Vec<PathBuf> = entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect();
+        paths.sort();
+
+        let mut inputs = Vec::new();
+        for path in paths.iter().filter(|path| path.is_file()) {
+            let bytes = std::fs::read(path).map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+            let input = match self.target {
+                FuzzTarget::Entrypoint => FuzzInput::Calldata(bytes),
+                FuzzTarget::Function(_) => {
+                    let args: Vec<i64> = serde_json::from_slice(&bytes)
+                        .map_err(|e| anyhow!("Failed to parse corpus entry {}: {}", path.display(), e))?;
+                    if args.len() != self.arity {
+                        return Err(anyhow!(
+                            "Corpus entry {} has {} argument(s), {} takes {}",
+                            path.display(),
+                            args.len(),
+                            self.target.name(),
+                            self.arity
+                        ));
+                    }
+                    FuzzInput::Args(args)
+                }
+            };
+            inputs.push(input);
+        }
+        Ok(inputs)
+    }
+
+    /// Saves calldata as raw bytes and arguments as a JSON array, named after the input.
+    fn save(&self, input: &FuzzInput) -> Result<()> {
+        let Some(dir) = &self.config.corpus_dir else { return Ok(()) };
+        std::fs::create_dir_all(dir).map_err(|e| anyhow!("Failed to create {}: {}", dir.display(), e))?;
+        let (path, contents) = match input {
+            FuzzInput::Args(args) => (dir.join(format!("{}.json", input.id())), serde_json::to_vec(args)?),
+            FuzzInput::Calldata(calldata) => (dir.join(input.id()), calldata.clone()),
+        };
+        std::fs::write(&path, contents).map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// A mutated corpus entry, or now and then a fresh random input.
+    fn next_input(&mut self) -> FuzzInput {
+        let mut input = if self.corpus.is_empty() || self.rng.below(16) == 0 {
+            self.random_input()
+        } else {
+            self.corpus[self.rng.below(self.corpus.len())].clone()
+        };
+        for _ in 0..=self.rng.below(4) {
+            match &mut input {
+                FuzzInput::Args(args) => self.mutate_args(args),
+                FuzzInput::Calldata(calldata) => self.mutate_calldata(calldata),
+            }
+        }
+        input
+    }
+
+    fn random_input(&mut self) -> FuzzInput {
+        match self.target {
+            FuzzTarget::Function(_) => FuzzInput::Args((0..self.arity).map(|_| self.value()).collect()),
+            FuzzTarget::Entrypoint => {
+                let len = self.rng.below(self.config.max_len.min(68) + 1);
+                FuzzInput::Calldata((0..len).map(|_| self.rng.next() as u8).collect())
+            }
+        }
+    }
+
+    /// An interesting value, a constant from the contract or a random one.
+    fn value(&mut self) -> i64 {
+        match self.rng.below(3) {
+            0 => INTERESTING[self.rng.below(INTERESTING.len())],
+            1 if !self.dictionary.is_empty() => self.dictionary[self.rng.below(self.dictionary.len())],
+            _ => self.rng.next() as i64,
+        }
+    }
+
+    fn mutate_args(&mut self, args: &mut [i64]) {
+        if args.is_empty() {
+            return;
+        }
+        let i = self.rng.below(args.len());
+        args[i] = match self.rng.below(4) {
+            0 => self.value(),
+            1 => args[i].wrapping_add(self.rng.below(33) as i64 - 16),
+            2 => args[i] ^ (1 << self.rng.below(64)),
+            _ => match &self.corpus[..] {
+                [] => self.value(),
+                corpus => match &corpus[self.rng.below(corpus.len())] {
+                    FuzzInput::Args(other) => other.get(i).copied().unwrap_or(0),
+                    FuzzInput::Calldata(_) => 0,
+                },
+            },
+        };
+    }
+
+    fn mutate_calldata(&mut self, calldata: &mut Vec<u8>) {
+        let len = calldata.len();
+        match self.rng.below(8) {
+            0 if len > 0 => calldata[self.rng.below(len)] ^= 1 << self.rng.below(8),
+            1 if len > 0 => calldata[self.rng.below(len)] = self.rng.next() as u8,
+            // A constant from the contract in either byte order, e.g. a selector or magic number.
+            2 => {
+                let value = self.value();
+                let bytes = match self.rng.below(4) {
+                    0 => (value as u32).to_be_bytes().to_vec(),
+                    1 => (value as u32).to_le_bytes().to_vec(),
+                    2 => value.to_be_bytes().to_vec(),
+                    _ => value.to_le_bytes().to_vec(),
+                };
+                let at = if self.rng.below(2) == 0 { 0 } else { self.rng.below(len + 1) };
+                overwrite(calldata, at, &bytes);
+            }
+            // An ABI word after the selector.
+            3 => {
+                let value = self.value();
+                let mut word = if value < 0 { [0xffu8; 32] } else { [0u8; 32] };
+                word[24..].copy_from_slice(&value.to_be_bytes());
+                let at = 4 + 32 * self.rng.below(len.saturating_sub(4) / 32 + 1);
+                overwrite(calldata, at, &word);
+            }
+            4 => {
+                let at = self.rng.below(len + 1);
+                let bytes: Vec<u8> = (0..=self.rng.below(32)).map(|_| self.rng.next() as u8).collect();
+                calldata.splice(at..at, bytes);
+            }
+            5 if len > 0 => {
+                let start = self.rng.below(len);
+                let end = start + 1 + self.rng.below(len - start);
+                calldata.drain(start..end);
+            }
+            6 if !self.corpus.is_empty() => {
+                if let FuzzInput::Calldata(other) = &self.corpus[self.rng.below(self.corpus.len())] {
+                    let at = self.rng.below(len + 1);
+                    let from = self.rng.below(other.len() + 1);
+                    calldata.truncate(at);
+                    calldata.extend_from_slice(&other[from..]);
+                }
+            }
+            _ => calldata.push(self.value() as u8),
+        }
+        calldata.truncate(self.config.max_len);
+    }
+}
+
+/// Writes `bytes` at `at`, growing `calldata` if they run past its end.
+fn overwrite(calldata: &mut Vec<u8>, at: usize, bytes: &[u8]) {
+    if calldata.len() < at + bytes.len() {
+        calldata.resize(at + bytes.len(), 0);
+    }
+    calldata[at..at + bytes.len()].copy_from_slice(bytes);
+}
+
+/// Smaller variants of `input`, the most aggressive first.
+fn shrink(input: &FuzzInput) -> Vec<FuzzInput> {
+    let mut candidates = Vec::new();
+    match input {
+        FuzzInput::Args(args) => {
+            for (i, &arg) in args.iter().enumerate() {
+                for smaller in [0, arg / 2, arg - arg.signum()] {
+                    if smaller != arg {
+                        let mut args = args.clone();
+                        args[i] = smaller;
+                        candidates.push(FuzzInput::Args(args));
+                    }
+                }
+            }
+        }
+        FuzzInput::Calldata(calldata) => {
+            let mut chunk = calldata.len();
+            while chunk > 0 {
+                for start in (0..calldata.len()).step_by(chunk) {
+                    let mut calldata = calldata.clone();
+                    calldata.drain(start..(start + chunk).min(calldata.len()));
+                    candidates.push(FuzzInput::Calldata(calldata));
+                }
+                chunk /= 2;
+            }
+            for (i, &byte) in calldata.iter().enumerate() {
+                if byte != 0 {
+                    let mut calldata = calldata.clone();
+                    calldata[i] = 0;
+                    candidates.push(FuzzInput::Calldata(calldata));
+                }
+            }
+        }
+    }
+    candidates
+}
+
+/// splitmix64, so a seed reproduces a whole session without an RNG dependency.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform enough below `n`, which must not be 0.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const CONTRACT: &str = r#"
+        (module
+            (import "vm_hooks" "read_args" (func $read_args (param i32)))
+            (memory (export "memory") 1)
+            (func (export "user_entrypoint") (param $len i32) (result i32)
+                (call $read_args (i32.const 0))
+                (if (i32.lt_u (local.get $len) (i32.const 4)) (then (return (i32.const 1))))
+                (if (i32.eq (i32.load (i32.const 0)) (i32.const 0xdeadbeef)) (then unreachable))
+                (i32.const 0))
+            (func (export "check") (param $x i64) (result i64)
+                (if (i64.eq (local.get $x) (i64.const 0x12345678)) (then unreachable))
+                (if (i64.lt_s (local.get $x) (i64.const -1000)) (then (loop $spin (br $spin))))
+                (local.get $x))
+            (func (export "invariant_broken") (result i64)
+                (i64.const 0))
+        )
+    "#;
+
+    /// A corpus directory no other test or test process uses.
+    fn corpus_dir() -> PathBuf {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let name = format!("stylus-fuzz-corpus-{}-{}", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed));
+        let dir = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn runtime() -> StylusRuntime {
+        let mut runtime = StylusRuntime::new(&wat::parse_str(CONTRACT).unwrap()).unwrap();
+        runtime.context_mut().gas_limit = 20_000;
+        runtime.enable_coverage(true);
+        runtime
+    }
+
+    #[test]
+    fn test_fuzzer_finds_and_minimizes_traps_and_out_of_gas() {
+        let mut runtime = runtime();
+        let config = FuzzConfig { runs: 1_000, seed: 7, ..FuzzConfig::default() };
+        let mut fuzzer = Fuzzer::new(&mut runtime, FuzzTarget::Function("check".to_string()), config).unwrap();
+        let report = fuzzer.run().unwrap();
+
+        let trap = report.failures.iter().find(|failure| failure.kind == FailureKind::Trap).unwrap();
+        assert_eq!(trap.input, FuzzInput::Args(vec![0x12345678]));
+        let out_of_gas = report.failures.iter().find(|failure| failure.kind == FailureKind::OutOfGas).unwrap();
+        assert!(matches!(out_of_gas.input, FuzzInput::Args(ref args) if args[0] < -1000));
+        assert_eq!(report.failures.len(), 2);
+        assert!(report.coverage.unwrap().branches().covered >= 4);
+
+        let test = trap.to_test(Path::new("contract.wasm"), &[]);
+        assert!(test.contains("FuzzInput::Args(vec![305419896])"));
+        assert!(test.contains("gas_limit = 20000;"));
+    }
+
+    #[test]
+    fn test_fuzzer_builds_calldata_from_contract_constants() {
+        let dir = corpus_dir();
+        let mut runtime = runtime();
+        let config = FuzzConfig { runs: 3_000, seed: 1, corpus_dir: Some(dir.clone()), ..FuzzConfig::default() };
+        let mut fuzzer = Fuzzer::new(&mut runtime, FuzzTarget::Entrypoint, config).unwrap();
+        let report = fuzzer.run().unwrap();
+
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].input, FuzzInput::Calldata(0xdeadbeef_u32.to_le_bytes().to_vec()));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), report.new_inputs);
+        assert!(report.new_inputs > 0);
+
+        // The saved corpus is replayed at the start of the next session.
+        let config = FuzzConfig { runs: 0, corpus_dir: Some(dir.clone()), ..FuzzConfig::default() };
+        let report = Fuzzer::new(&mut runtime, FuzzTarget::Entrypoint, config).unwrap().run().unwrap();
+        assert_eq!(report.runs as usize, std::fs::read_dir(&dir).unwrap().count());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_invariants_are_checked_after_successful_calls() {
+        let mut runtime = runtime();
+        let target = FuzzTarget::Function("check".to_string());
+        let not_42 = [Invariant::new("not_42", |_, result| match result.return_value {
+            42 => Err("returned 42".to_string()),
+            _ => Ok(()),
+        })];
+
+        let failure = run_input(&mut runtime, &target, &FuzzInput::Args(vec![42]), &not_42).unwrap().unwrap();
+        assert_eq!(failure.kind, FailureKind::Invariant("not_42".to_string()));
+        assert_eq!(failure.to_string(), "invariant not_42 violated in check(42): returned 42");
+        assert_eq!(failure.kind.slug(), "invariant-not_42");
+        assert!(run_input(&mut runtime, &target, &FuzzInput::Args(vec![1]), &not_42).unwrap().is_none());
+
+        let broken = [Invariant::export("invariant_broken")];
+        runtime.set_stub_policy(StubPolicy::Zero);
+        let failure = run_input(&mut runtime, &target, &FuzzInput::Args(vec![1]), &broken).unwrap().unwrap();
+        assert_eq!(failure.message, "returned 0");
+        let test = failure.to_test(Path::new("contract.wasm"), &["invariant_broken".to_string()]);
+        assert!(test.contains("fn fuzz_invariant_invariant_broken_"));
+        assert!(test.contains("runtime.set_stub_policy(StubPolicy::Zero);"));
+
+        let missing = FuzzTarget::Function("missing".to_string());
+        assert!(run_input(&mut runtime, &missing, &FuzzInput::Args(vec![]), &[]).is_err());
+    }
+}
@@ -0,0 +1,1070 @@
+use crate::abi::{encode_u128, keccak256};
+use crate::backtrace::Backtrace;
+use crate::console::{self, ConsoleLine, ConsolePipes, ConsoleStream};
+use crate::coverage::Coverage;
+use crate::error::{StateChange, StaticCallViolation, Trap};
+use crate::host_trace::{self, HostCallTrace};
+use crate::imports::{self, StubPolicy};
+use crate::inspector::InspectorSlot;
+use crate::interpreter::{Machine, Program, Val};
+use crate::metering;
+use crate::precompiles::{PrecompileCall, Precompiles};
+use crate::symbols::Symbols;
+use crate::world::{address_from_u64, Address, Bytes32, Log, World};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use wasmer::{
+    Function, FunctionEnv, FunctionEnvMut, FunctionType, Imports, Instance, Memory, MemoryView, Module,
+    RuntimeError, Store, Type, Value,
+};
+use wasmer_wasi::WasiState;
+
+pub const DEFAULT_SENDER: Address = address_from_u64(0xa11ce);
+pub const DEFAULT_CONTRACT: Address = address_from_u64(0xc0de);
+
+const MAX_CALL_DEPTH: u32 = 1024;
+
+/// The trap message for a call that ran out of gas, in either engine.
+pub(crate) const OUT_OF_GAS: &str = "out of gas";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionContext {
+    pub sender: Address,
+    pub origin: Address,
+    pub contract_address: Address,
+    pub value: u128,
+    pub block_number: u64,
+    pub block_timestamp: u64,
+    pub block_basefee: u128,
+    pub block_gas_limit: u64,
+    pub coinbase: Address,
+    pub chain_id: u64,
+    pub gas_price: u128,
+    pub gas_limit: u64,
+}
+
+impl Default for ExecutionContext {
+    fn default() -> Self {
+        Self {
+            sender: DEFAULT_SENDER,
+            origin: DEFAULT_SENDER,
+            contract_address: DEFAULT_CONTRACT,
+            value: 0,
+            block_number: 1,
+            block_timestamp: 1_700_000_000,
+            block_basefee: 100_000_000,
+            block_gas_limit: 32_000_000,
+            coinbase: [0u8; 20],
+            chain_id: 421614,
+            gas_price: 100_000_000,
+            gas_limit: 30_000_000,
+        }
+    }
+}
+
+/// The contract-level view of one call: whose storage is used, who called, and with what.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CallFrame {
+    pub address: Address,
+    pub code_address: Address,
+    pub caller: Address,
+    pub value: u128,
+    pub calldata: Vec<u8>,
+    pub depth: u32,
+    pub is_static: bool,
+}
+
+impl CallFrame {
+    pub fn top_level(context: &ExecutionContext, calldata: Vec<u8>) -> Self {
+        Self {
+            address: context.contract_address,
+            code_address: context.contract_address,
+            caller: context.sender,
+            value: context.value,
+            calldata,
+            depth: 1,
+            is_static: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallKind {
+    Call,
+    DelegateCall,
+    StaticCall,
+}
+
+/// Access to a contract's linear memory from inside a host function.
+pub trait GuestMemory {
+    fn read(&self, ptr: u32, len: usize) -> Result<Vec<u8>, RuntimeError>;
+    fn write(&mut self, ptr: u32, data: &[u8]) -> Result<(), RuntimeError>;
+
+    fn read_address(&self, ptr: u32) -> Result<Address, RuntimeError> {
+        Ok(self.read(ptr, 20)?.try_into().unwrap())
+    }
+
+    fn read_word(&self, ptr: u32) -> Result<Bytes32, RuntimeError> {
+        Ok(self.read(ptr, 32)?.try_into().unwrap())
+    }
+
+    fn write_u32(&mut self, ptr: u32, value: u32) -> Result<(), RuntimeError> {
+        self.write(ptr, &value.to_le_bytes())
+    }
+}
+
+struct WasmerMemory<'a>(Option<MemoryView<'a>>);
+
+impl WasmerMemory<'_> {
+    fn view(&self) -> Result<&MemoryView<'_>, RuntimeError> {
+        self.0.as_ref().ok_or_else(|| RuntimeError::new("Contract does not export memory"))
+    }
+}
+
+impl GuestMemory for WasmerMemory<'_> {
+    fn read(&self, ptr: u32, len: usize) -> Result<Vec<u8>, RuntimeError> {
+        let mut buf = vec![0u8; len];
+        self.view()?
+            .read(ptr as u64, &mut buf)
+            .map_err(|e| RuntimeError::new(format!("Memory read out of bounds: {}", e)))?;
+        Ok(buf)
+    }
+
+    fn write(&mut self, ptr: u32, data: &[u8]) -> Result<(), RuntimeError> {
+        self.view()?
+            .write(ptr as u64, data)
+            .map_err(|e| RuntimeError::new(format!("Memory write out of bounds: {}", e)))
+    }
+}
+
+pub type HostFunction = dyn Fn(&mut dyn GuestMemory, &[Value]) -> Result<Vec<Value>, RuntimeError> + Send + Sync;
+
+/// A host import registered by the user on top of WASI and `vm_hooks`.
+#[derive(Clone)]
+pub struct CustomImport {
+    pub module: String,
+    pub name: String,
+    pub params: Vec<Type>,
+    pub results: Vec<Type>,
+    pub gas_cost: u64,
+    pub(crate) handler: Arc<HostFunction>,
+}
+
+/// State shared by every `vm_hooks` import of one contract instance.
+pub struct HostEnv {
+    pub(crate) world: Arc<Mutex<World>>,
+    pub(crate) context: ExecutionContext,
+    pub(crate) frame: CallFrame,
+    pub(crate) gas_table: HashMap<String, u64>,
+    pub(crate) memory: Option<Memory>,
+    pub(crate) output: Vec<u8>,
+    pub(crate) return_data: Vec<u8>,
+    pub(crate) logs: Vec<Log>,
+    /// Gas of the instructions executed before the current host call.
+    pub(crate) instruction_gas: u64,
+    pub(crate) host_gas: u64,
+    /// The wasmer instance whose metering holds the instruction gas; `None` in the interpreter.
+    pub(crate) instance: Option<Instance>,
+    pub(crate) stub_policy: StubPolicy,
+    pub(crate) custom_imports: Arc<Vec<CustomImport>>,
+    pub(crate) console: Vec<ConsoleLine>,
+    pub(crate) pipes: Option<ConsolePipes>,
+    pub(crate) host_calls: Vec<HostCallTrace>,
+    pub(crate) inspector: InspectorSlot,
+    /// Where the interpreter counts executed instructions, when coverage is enabled.
+    pub(crate) coverage: Option<Coverage>,
+}
+
+impl HostEnv {
+    pub(crate) fn new(
+        world: Arc<Mutex<World>>,
+        context: ExecutionContext,
+        gas_table: HashMap<String, u64>,
+        frame: CallFrame,
+    ) -> Self {
+        Self {
+            world,
+            context,
+            frame,
+            gas_table,
+            memory: None,
+            output: Vec::new(),
+            return_data: Vec::new(),
+            logs: Vec::new(),
+            instruction_gas: 0,
+            host_gas: 0,
+            instance: None,
+            stub_policy: StubPolicy::default(),
+            custom_imports: Arc::new(Vec::new()),
+            console: Vec::new(),
+            pipes: None,
+            host_calls: Vec::new(),
+            inspector: InspectorSlot::default(),
+            coverage: None,
+        }
+    }
+
+    /// A fresh environment for a nested call that shares this session's world and settings,
+    /// limited to the gas this frame has left.
+    pub(crate) fn child(&self, frame: CallFrame) -> Self {
+        let mut child = Self::new(self.world.clone(), self.context.clone(), self.gas_table.clone(), frame);
+        child.context.gas_limit = self.gas_left();
+        child.stub_policy = self.stub_policy;
+        child.custom_imports = self.custom_imports.clone();
+        child.inspector = self.inspector.clone();
+        child.coverage = self.coverage.clone();
+        child
+    }
+
+    pub(crate) fn world(&self) -> MutexGuard<'_, World> {
+        lock_world(&self.world)
+    }
+
+    /// Fails with a `StaticCallViolation` if this frame may not change state.
+    fn ensure_mutable(&self, operation: StateChange) -> Result<(), RuntimeError> {
+        if self.frame.is_static {
+            return Err(RuntimeError::user(Box::new(StaticCallViolation {
+                operation,
+                address: self.frame.address,
+            })));
+        }
+        Ok(())
+    }
+
+    /// Collects what the contract has written to stdout and stderr since the last flush.
+    pub(crate) fn flush_console(&mut self) {
+        if let Some(pipes) = self.pipes.as_mut() {
+            pipes.drain(self.frame.address, &mut self.console);
+        }
+    }
+
+    fn print(&mut self, text: &str) {
+        console::push_lines(&mut self.console, ConsoleStream::Log, self.frame.address, text);
+    }
+
+    fn charge(&mut self, op: &str) {
+        self.host_gas += self.gas_table.get(op).copied().unwrap_or(0);
+    }
+
+    /// Instruction and host gas charged so far.
+    pub(crate) fn gas_used(&self) -> u64 {
+        self.instruction_gas + self.host_gas
+    }
+
+    pub(crate) fn gas_left(&self) -> u64 {
+        self.context.gas_limit.saturating_sub(self.gas_used())
+    }
+}
+
+pub(crate) fn lock_world(world: &Arc<Mutex<World>>) -> MutexGuard<'_, World> {
+    world.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Result of running a contract's `user_entrypoint` in its own frame.
+pub(crate) struct FrameOutcome {
+    pub success: bool,
+    pub output: Vec<u8>,
+    pub logs: Vec<Log>,
+    /// Everything the frame charged, its instructions included.
+    pub gas_used: u64,
+    pub console: Vec<ConsoleLine>,
+    pub host_calls: Vec<HostCallTrace>,
+}
+
+/// Builds the WASI and `vm_hooks` imports every contract instance is linked against.
+pub(crate) fn link(store: &mut Store, module: &Module, env: &FunctionEnv<HostEnv>) -> Result<Imports> {
+    let pipes = ConsolePipes::default();
+    let wasi_env = WasiState::new("stylus-runtime")
+        .stdout(Box::new(pipes.stdout.clone()))
+        .stderr(Box::new(pipes.stderr.clone()))
+        .finalize(store)?;
+    let mut import_object = wasi_env.import_object(store, module)?;
+    env.as_mut(store).pipes = Some(pipes);
+    define_imports(store, env, &mut import_object);
+    Ok(import_object)
+}
+
+pub(crate) fn instantiate(store: &mut Store, module: &Module, env: HostEnv) -> Result<(Instance, FunctionEnv<HostEnv>)> {
+    let (stub_policy, custom_imports) = (env.stub_policy, env.custom_imports.clone());
+    let env = FunctionEnv::new(store, env);
+    let mut import_object = link(store, module, &env)?;
+    imports::resolve(store, module, &mut import_object, &custom_imports, stub_policy)?;
+
+    let instance = Instance::new(store, module, &import_object)?;
+    let host = env.as_mut(store);
+    if let Ok(memory) = instance.exports.get_memory("memory") {
+        host.memory = Some(memory.clone());
+    }
+    // Metering starts with the call; the start function is free, as in the interpreter.
+    host.host_gas = 0;
+    host.instance = Some(instance.clone());
+    let gas_limit = host.context.gas_limit;
+    metering::reset(store, &instance, gas_limit);
+    Ok((instance, env))
+}
+
+/// Runs a nested call in wasmer, or in the interpreter while coverage is enabled so its
+/// instructions are counted too. Either way the frame charges the same gas.
+pub(crate) fn execute_frame(parent: &HostEnv, frame: CallFrame) -> Result<FrameOutcome> {
+    let (engine, module, wasm, code_hash, symbols) = {
+        let world = parent.world();
+        let contract = world
+            .contract(&frame.code_address)
+            .ok_or_else(|| anyhow!("No contract deployed at {}", hex_address(&frame.code_address)))?;
+        let (module, wasm, symbols) = (contract.module.clone(), contract.wasm.clone(), contract.symbols.clone());
+        (contract.engine.clone(), module, wasm, contract.code_hash, symbols)
+    };
+    if let Some(coverage) = &parent.coverage {
+        let mut env = parent.child(frame);
+        env.coverage = Some(coverage.for_code(code_hash));
+        return interpret_frame(&wasm, symbols, env);
+    }
+
+    let mut store = Store::new(engine);
+    let calldata_len = frame.calldata.len() as i32;
+    let env = parent.child(frame);
+    let gas_limit = env.context.gas_limit;
+    let (instance, env) = instantiate(&mut store, &module, env)?;
+
+    let entrypoint = instance
+        .exports
+        .get_typed_function::<i32, i32>(&store, "user_entrypoint")
+        .map_err(|_| anyhow!("Function 'user_entrypoint' not found"))?;
+    let status = entrypoint.call(&mut store, calldata_len);
+    let gas_used = metering::gas_used(&mut store, &instance, gas_limit);
+
+    let host = env.as_mut(&mut store);
+    host.flush_console();
+    let console = std::mem::take(&mut host.console);
+    let status = status.map_err(|e| {
+        let message = if gas_used.is_none() { OUT_OF_GAS.to_string() } else { e.message() };
+        Trap::new(message, console.clone(), Backtrace::capture(&e, &symbols))
+    })?;
+
+    Ok(FrameOutcome {
+        success: status == 0,
+        output: std::mem::take(&mut host.output),
+        logs: std::mem::take(&mut host.logs),
+        gas_used: gas_used.unwrap_or(gas_limit),
+        console,
+        host_calls: std::mem::take(&mut host.host_calls),
+    })
+}
+
+fn interpret_frame(wasm: &[u8], symbols: Arc<Symbols>, env: HostEnv) -> Result<FrameOutcome> {
+    let program = Arc::new(Program::parse(wasm)?);
+    let entrypoint = program
+        .export("user_entrypoint")
+        .ok_or_else(|| anyhow!("Function 'user_entrypoint' not found"))?;
+    let calldata_len = env.frame.calldata.len() as i32;
+    let mut machine = Machine::new(program, symbols, env)?;
+    machine.call(entrypoint, &[Val::I32(calldata_len)])?;
+    let status = machine
+        .run()
+        .map_err(|e| Trap::new(e.message(), machine.console().to_vec(), machine.backtrace()))?;
+
+    let gas_used = machine.gas_used();
+    let host = machine.env_mut();
+    Ok(FrameOutcome {
+        success: status.first().map_or(0, |value| value.as_i64()) == 0,
+        output: std::mem::take(&mut host.output),
+        logs: std::mem::take(&mut host.logs),
+        gas_used,
+        console: std::mem::take(&mut host.console),
+        host_calls: std::mem::take(&mut host.host_calls),
+    })
+}
+
+pub fn hex_address(address: &Address) -> String {
+    let hex: String = address.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("0x{}", hex)
+}
+
+pub(crate) type HostHandler = fn(&mut HostEnv, &mut dyn GuestMemory, &[Value]) -> Result<Vec<Value>, RuntimeError>;
+
+struct HostImport {
+    name: &'static str,
+    params: &'static [Type],
+    results: &'static [Type],
+    handler: HostHandler,
+}
+
+const fn hook(
+    name: &'static str,
+    params: &'static [Type],
+    results: &'static [Type],
+    handler: HostHandler,
+) -> HostImport {
+    HostImport { name, params, results, handler }
+}
+
+const I32: Type = Type::I32;
+const I64: Type = Type::I64;
+const F32: Type = Type::F32;
+const F64: Type = Type::F64;
+
+const VM_HOOKS: &[HostImport] = &[
+    hook("read_args", &[I32], &[], read_args),
+    hook("write_result", &[I32, I32], &[], write_result),
+    hook("storage_load_bytes32", &[I32, I32], &[], storage_load_bytes32),
+    hook("storage_store_bytes32", &[I32, I32], &[], storage_store_bytes32),
+    hook("storage_cache_bytes32", &[I32, I32], &[], storage_store_bytes32),
+    hook("storage_flush_cache", &[I32], &[], storage_flush_cache),
+    hook("emit_log", &[I32, I32, I32], &[], emit_log),
+    hook("call_contract", &[I32, I32, I32, I32, I64, I32], &[I32], call_contract),
+    hook("delegate_call_contract", &[I32, I32, I32, I64, I32], &[I32], delegate_call_contract),
+    hook("static_call_contract", &[I32, I32, I32, I64, I32], &[I32], static_call_contract),
+    hook("create1", &[I32, I32, I32, I32, I32], &[], create1),
+    hook("create2", &[I32, I32, I32, I32, I32, I32], &[], create2),
+    hook("read_return_data", &[I32, I32, I32], &[I32], read_return_data),
+    hook("return_data_size", &[], &[I32], return_data_size),
+    hook("account_balance", &[I32, I32], &[], account_balance),
+    hook("account_codehash", &[I32, I32], &[], account_codehash),
+    hook("block_basefee", &[I32], &[], block_basefee),
+    hook("block_coinbase", &[I32], &[], block_coinbase),
+    hook("block_gas_limit", &[], &[I64], block_gas_limit),
+    hook("block_number", &[], &[I64], block_number),
+    hook("block_timestamp", &[], &[I64], block_timestamp),
+    hook("chainid", &[], &[I64], chainid),
+    hook("contract_address", &[I32], &[], contract_address),
+    hook("evm_gas_left", &[], &[I64], evm_gas_left),
+    hook("evm_ink_left", &[], &[I64], evm_ink_left),
+    hook("msg_reentrant", &[], &[I32], msg_reentrant),
+    hook("msg_sender", &[I32], &[], msg_sender),
+    hook("msg_value", &[I32], &[], msg_value),
+    hook("native_keccak256", &[I32, I32, I32], &[], native_keccak256),
+    hook("tx_gas_price", &[I32], &[], tx_gas_price),
+    hook("tx_ink_price", &[], &[I32], tx_ink_price),
+    hook("tx_origin", &[I32], &[], tx_origin),
+    hook("memory_grow", &[I32], &[], pay_for_memory_grow),
+    hook("pay_for_memory_grow", &[I32], &[], pay_for_memory_grow),
+];
+
+/// Debug printing imports, matching the `console` module of Stylus debug builds.
+const CONSOLE_HOOKS: &[HostImport] = &[
+    hook("log_txt", &[I32, I32], &[], log_txt),
+    hook("log_i32", &[I32], &[], log_value),
+    hook("log_i64", &[I64], &[], log_value),
+    hook("log_f32", &[F32], &[], log_value),
+    hook("log_f64", &[F64], &[], log_value),
+    hook("tee_i32", &[I32], &[I32], tee_value),
+    hook("tee_i64", &[I64], &[I64], tee_value),
+    hook("tee_f32", &[F32], &[F32], tee_value),
+    hook("tee_f64", &[F64], &[F64], tee_value),
+];
+
+fn builtin(module: &str, name: &str) -> Option<&'static HostImport> {
+    let hooks = match module {
+        "vm_hooks" => VM_HOOKS,
+        "console" => CONSOLE_HOOKS,
+        _ => return None,
+    };
+    hooks.iter().find(|import| import.name == name)
+}
+
+/// The built-in handler for `module::name`, for engines that link imports themselves.
+pub(crate) fn builtin_hook(module: &str, name: &str) -> Option<HostHandler> {
+    builtin(module, name).map(|import| import.handler)
+}
+
+/// The signature the host defines `module::name` with, or `None` if it is not a host import.
+/// Custom imports take precedence, as they do when linking.
+pub(crate) fn host_signature(custom_imports: &[CustomImport], module: &str, name: &str) -> Option<FunctionType> {
+    if let Some(import) = custom_imports.iter().rev().find(|import| import.module == module && import.name == name) {
+        return Some(FunctionType::new(import.params.clone(), import.results.clone()));
+    }
+    builtin(module, name).map(|import| FunctionType::new(import.params.to_vec(), import.results.to_vec()))
+}
+
+pub(crate) fn define_imports(store: &mut Store, env: &FunctionEnv<HostEnv>, imports: &mut Imports) {
+    let hooks = VM_HOOKS
+        .iter()
+        .map(|import| ("vm_hooks", import))
+        .chain(CONSOLE_HOOKS.iter().map(|import| ("console", import)));
+    for (namespace, import) in hooks {
+        let handler = import.handler;
+        let ty = FunctionType::new(import.params.to_vec(), import.results.to_vec());
+        let function = Function::new_with_env(store, env, ty, move |mut ctx: FunctionEnvMut<HostEnv>, args: &[Value]| {
+            call_import(&mut ctx, (namespace, import.name), args, |env, mem| handler(env, mem, args))
+        });
+        imports.define(namespace, import.name, function);
+    }
+
+    // Registered after the built-ins so user hooks can override them.
+    let custom_imports = env.as_ref(store).custom_imports.clone();
+    for import in custom_imports.iter() {
+        let handler = import.handler.clone();
+        let gas_cost = import.gas_cost;
+        let (module, name) = (import.module.clone(), import.name.clone());
+        let ty = FunctionType::new(import.params.clone(), import.results.clone());
+        let function = Function::new_with_env(store, env, ty, move |mut ctx: FunctionEnvMut<HostEnv>, args: &[Value]| {
+            call_import(&mut ctx, (&module, &name), args, |env, mem| {
+                env.host_gas += gas_cost;
+                handler(mem, args)
+            })
+        });
+        imports.define(&import.module, &import.name, function);
+    }
+}
+
+/// Runs a host import for wasmer: the instruction gas metered so far is brought into the
+/// environment for the call, and the gas left afterwards is handed back to the instance.
+fn call_import(
+    ctx: &mut FunctionEnvMut<HostEnv>,
+    import: (&str, &str),
+    args: &[Value],
+    call: impl FnOnce(&mut HostEnv, &mut dyn GuestMemory) -> Result<Vec<Value>, RuntimeError>,
+) -> Result<Vec<Value>, RuntimeError> {
+    let (env, mut store) = ctx.data_and_store_mut();
+    metering::load(env, &mut store);
+    let memory = env.memory.clone();
+    let mut view = WasmerMemory(memory.as_ref().map(|m| m.view(&store)));
+    let outcome = host_trace::traced(env, &mut view, import, args, call);
+    metering::store(env, &mut store);
+    outcome
+}
+
+fn arg(args: &[Value], index: usize) -> u32 {
+    args[index].unwrap_i32() as u32
+}
+
+fn log_txt(env: &mut HostEnv, mem: &mut dyn GuestMemory, args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    let text = mem.read(arg(args, 0), arg(args, 1) as usize)?;
+    env.print(&String::from_utf8_lossy(&text));
+    Ok(vec![])
+}
+
+fn log_value(env: &mut HostEnv, _mem: &mut dyn GuestMemory, args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    env.print(&format_value(&args[0]));
+    Ok(vec![])
+}
+
+fn tee_value(env: &mut HostEnv, _mem: &mut dyn GuestMemory, args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    env.print(&format_value(&args[0]));
+    Ok(vec![args[0].clone()])
+}
+
+pub(crate) fn format_value(value: &Value) -> String {
+    match value {
+        Value::I32(v) => v.to_string(),
+        Value::I64(v) => v.to_string(),
+        Value::F32(v) => v.to_string(),
+        Value::F64(v) => v.to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+fn read_args(env: &mut HostEnv, mem: &mut dyn GuestMemory, args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    mem.write(arg(args, 0), &env.frame.calldata)?;
+    Ok(vec![])
+}
+
+fn write_result(env: &mut HostEnv, mem: &mut dyn GuestMemory, args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    env.output = mem.read(arg(args, 0), arg(args, 1) as usize)?;
+    Ok(vec![])
+}
+
+fn storage_load_bytes32(env: &mut HostEnv, mem: &mut dyn GuestMemory, args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    let key = mem.read_word(arg(args, 0))?;
+    let value = env.world().storage_load(&env.frame.address, &key);
+    env.inspector.inspect(|inspector| inspector.on_storage_read(&env.frame.address, &key, &value));
+    env.charge("storage_read");
+    mem.write(arg(args, 1), &value)?;
+    Ok(vec![])
+}
+
+fn storage_store_bytes32(env: &mut HostEnv, mem: &mut dyn GuestMemory, args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    env.ensure_mutable(StateChange::StorageWrite)?;
+    let key = mem.read_word(arg(args, 0))?;
+    let value = mem.read_word(arg(args, 1))?;
+    env.world().storage_store(&env.frame.address, key, value);
+    env.inspector.inspect(|inspector| inspector.on_storage_write(&env.frame.address, &key, &value));
+    env.charge("storage_write");
+    Ok(vec![])
+}
+
+fn storage_flush_cache(_env: &mut HostEnv, _mem: &mut dyn GuestMemory, _args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    // Stores are written through to the world immediately, so there is nothing to flush.
+    Ok(vec![])
+}
+
+fn emit_log(env: &mut HostEnv, mem: &mut dyn GuestMemory, args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    env.ensure_mutable(StateChange::LogEmission)?;
+    let data = mem.read(arg(args, 0), arg(args, 1) as usize)?;
+    let topics = arg(args, 2) as usize;
+    if topics > 4 || topics * 32 > data.len() {
+        return Err(RuntimeError::new(format!("Invalid log: {} topics in {} bytes", topics, data.len())));
+    }
+    let (topic_bytes, body) = data.split_at(topics * 32);
+    let log = Log {
+        address: env.frame.address,
+        topics: topic_bytes.chunks(32).map(|t| t.try_into().unwrap()).collect(),
+        data: body.to_vec(),
+    };
+    env.inspector.inspect(|inspector| inspector.on_log(&log));
+    env.logs.push(log);
+    env.charge("log");
+    Ok(vec![])
+}
+
+fn call_contract(env: &mut HostEnv, mem: &mut dyn GuestMemory, args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    let value = u128::from_be_bytes(mem.read_word(arg(args, 3))?[16..].try_into().unwrap());
+    if value > 0 {
+        env.ensure_mutable(StateChange::ValueTransfer)?;
+    }
+    do_call(env, mem, CallKind::Call, args, value, 5)
+}
+
+fn delegate_call_contract(env: &mut HostEnv, mem: &mut dyn GuestMemory, args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    do_call(env, mem, CallKind::DelegateCall, args, 0, 4)
+}
+
+fn static_call_contract(env: &mut HostEnv, mem: &mut dyn GuestMemory, args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    do_call(env, mem, CallKind::StaticCall, args, 0, 4)
+}
+
+fn do_call(
+    env: &mut HostEnv,
+    mem: &mut dyn GuestMemory,
+    kind: CallKind,
+    args: &[Value],
+    value: u128,
+    return_len_arg: usize,
+) -> Result<Vec<Value>, RuntimeError> {
+    let target = mem.read_address(arg(args, 0))?;
+    let calldata = mem.read(arg(args, 1), arg(args, 2) as usize)?;
+    env.charge("call_contract");
+
+    let (success, data) = run_subcall(env, kind, target, calldata, value);
+    mem.write_u32(arg(args, return_len_arg), data.len() as u32)?;
+    env.return_data = data;
+    Ok(vec![Value::I32(if success { 0 } else { 1 })])
+}
+
+fn run_subcall(env: &mut HostEnv, kind: CallKind, target: Address, calldata: Vec<u8>, value: u128) -> (bool, Vec<u8>) {
+    if env.frame.depth >= MAX_CALL_DEPTH {
+        return (false, Vec::new());
+    }
+
+    let frame = match kind {
+        CallKind::DelegateCall => CallFrame {
+            address: env.frame.address,
+            code_address: target,
+            caller: env.frame.caller,
+            value: env.frame.value,
+            calldata,
+            depth: env.frame.depth + 1,
+            is_static: env.frame.is_static,
+        },
+        CallKind::Call | CallKind::StaticCall => CallFrame {
+            address: target,
+            code_address: target,
+            caller: env.frame.address,
+            value,
+            calldata,
+            depth: env.frame.depth + 1,
+            is_static: env.frame.is_static || kind == CallKind::StaticCall,
+        },
+    };
+
+    env.inspector.inspect(|inspector| inspector.on_call_enter(&frame));
+    let (success, output) = if Precompiles::is_precompile(&target) {
+        call_precompile(env, &target, &frame.calldata, value)
+    } else {
+        call_frame(env, frame.clone(), value)
+    };
+    env.inspector.inspect(|inspector| inspector.on_call_exit(&frame, success, &output));
+    (success, output)
+}
+
+fn call_precompile(env: &mut HostEnv, target: &Address, calldata: &[u8], value: u128) -> (bool, Vec<u8>) {
+    let mut world = env.world();
+    let snapshot = world.snapshot();
+    if world.transfer(&env.frame.address, target, value).is_err() {
+        return (false, Vec::new());
+    }
+    let call = PrecompileCall {
+        caller: env.frame.address,
+        calldata,
+        value,
+        depth: env.frame.depth + 1,
+        timestamp: env.context.block_timestamp,
+        is_static: env.frame.is_static,
+    };
+    match world.call_precompile(target, call) {
+        Ok(data) => (true, data),
+        Err(data) => {
+            world.revert_to(snapshot);
+            (false, data)
+        }
+    }
+}
+
+fn call_frame(env: &mut HostEnv, frame: CallFrame, value: u128) -> (bool, Vec<u8>) {
+    let snapshot = {
+        let mut world = env.world();
+        let snapshot = world.snapshot();
+        if world.transfer(&env.frame.address, &frame.address, value).is_err() {
+            return (false, Vec::new());
+        }
+        if world.contract(&frame.code_address).is_none() {
+            return (true, Vec::new());
+        }
+        snapshot
+    };
+
+    // Console output survives reverts so failed calls can still be debugged.
+    env.flush_console();
+    match execute_frame(env, frame.clone()) {
+        Ok(outcome) if outcome.success => {
+            env.logs.extend(outcome.logs);
+            env.host_gas += outcome.gas_used;
+            env.console.extend(outcome.console);
+            env.host_calls.extend(outcome.host_calls);
+            (true, outcome.output)
+        }
+        Ok(outcome) => {
+            env.world().revert_to(snapshot);
+            env.host_gas += outcome.gas_used;
+            env.console.extend(outcome.console);
+            env.host_calls.extend(outcome.host_calls);
+            (false, outcome.output)
+        }
+        Err(e) => {
+            env.world().revert_to(snapshot);
+            let message = e.downcast_ref::<Trap>().map_or_else(|| e.to_string(), |trap| trap.message.clone());
+            env.inspector.inspect(|inspector| inspector.on_trap(&frame, &message));
+            if let Some(trap) = e.downcast_ref::<Trap>() {
+                env.console.extend(trap.console.iter().cloned());
+            }
+            (false, Vec::new())
+        }
+    }
+}
+
+fn create1(env: &mut HostEnv, mem: &mut dyn GuestMemory, args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    do_create(env, mem, args, None, 3)
+}
+
+fn create2(env: &mut HostEnv, mem: &mut dyn GuestMemory, args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    let salt = mem.read_word(arg(args, 3))?;
+    do_create(env, mem, args, Some(salt), 4)
+}
+
+/// CREATE/CREATE2 for local testing: the code bytes are compiled directly as the new contract's wasm.
+/// As in the EVM, a creator that can afford the endowment uses up its nonce even if the code
+/// fails to compile or the address is taken; one that cannot afford it keeps the nonce.
+fn do_create(
+    env: &mut HostEnv,
+    mem: &mut dyn GuestMemory,
+    args: &[Value],
+    salt: Option<Bytes32>,
+    contract_arg: usize,
+) -> Result<Vec<Value>, RuntimeError> {
+    env.ensure_mutable(StateChange::ContractCreation)?;
+    let code = mem.read(arg(args, 0), arg(args, 1) as usize)?;
+    let endowment = u128::from_be_bytes(mem.read_word(arg(args, 2))?[16..].try_into().unwrap());
+    env.charge("create");
+
+    let creator = env.frame.address;
+    let created = {
+        let mut world = env.world();
+        if world.balance(&creator) < endowment {
+            None
+        } else {
+            let nonce = world.account(&creator).map(|a| a.nonce).unwrap_or(0);
+            world.account_mut(&creator).nonce += 1;
+            let address = match &salt {
+                None => World::create_address(&creator, nonce),
+                Some(salt) => World::create2_address(&creator, salt, &keccak256(&code)),
+            };
+            match world.compile(&code) {
+                Ok(contract) if world.contract(&address).is_none() => {
+                    world.transfer(&creator, &address, endowment).ok().map(|()| {
+                        world.install(&address, contract);
+                        address
+                    })
+                }
+                _ => None,
+            }
+        }
+    };
+
+    mem.write(arg(args, contract_arg), &created.unwrap_or([0u8; 20]))?;
+    mem.write_u32(arg(args, contract_arg + 1), 0)?;
+    env.return_data.clear();
+    Ok(vec![])
+}
+
+fn read_return_data(env: &mut HostEnv, mem: &mut dyn GuestMemory, args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    let offset = (arg(args, 1) as usize).min(env.return_data.len());
+    let end = offset.saturating_add(arg(args, 2) as usize).min(env.return_data.len());
+    mem.write(arg(args, 0), &env.return_data[offset..end])?;
+    Ok(vec![Value::I32((end - offset) as i32)])
+}
+
+fn return_data_size(env: &mut HostEnv, _mem: &mut dyn GuestMemory, _args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    Ok(vec![Value::I32(env.return_data.len() as i32)])
+}
+
+fn account_balance(env: &mut HostEnv, mem: &mut dyn GuestMemory, args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    let address = mem.read_address(arg(args, 0))?;
+    let balance = env.world().balance(&address);
+    mem.write(arg(args, 1), &encode_u128(balance))?;
+    Ok(vec![])
+}
+
+fn account_codehash(env: &mut HostEnv, mem: &mut dyn GuestMemory, args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    let address = mem.read_address(arg(args, 0))?;
+    let hash = {
+        let world = env.world();
+        match world.account(&address) {
+            Some(account) => account.contract.as_ref().map(|c| c.code_hash).unwrap_or_else(|| keccak256(&[])),
+            None => [0u8; 32],
+        }
+    };
+    mem.write(arg(args, 1), &hash)?;
+    Ok(vec![])
+}
+
+fn block_basefee(env: &mut HostEnv, mem: &mut dyn GuestMemory, args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    mem.write(arg(args, 0), &encode_u128(env.context.block_basefee))?;
+    Ok(vec![])
+}
+
+fn block_coinbase(env: &mut HostEnv, mem: &mut dyn GuestMemory, args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    mem.write(arg(args, 0), &env.context.coinbase)?;
+    Ok(vec![])
+}
+
+fn block_gas_limit(env: &mut HostEnv, _mem: &mut dyn GuestMemory, _args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    Ok(vec![Value::I64(env.context.block_gas_limit as i64)])
+}
+
+fn block_number(env: &mut HostEnv, _mem: &mut dyn GuestMemory, _args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    Ok(vec![Value::I64(env.context.block_number as i64)])
+}
+
+fn block_timestamp(env: &mut HostEnv, _mem: &mut dyn GuestMemory, _args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    Ok(vec![Value::I64(env.context.block_timestamp as i64)])
+}
+
+fn chainid(env: &mut HostEnv, _mem: &mut dyn GuestMemory, _args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    Ok(vec![Value::I64(env.context.chain_id as i64)])
+}
+
+fn contract_address(env: &mut HostEnv, mem: &mut dyn GuestMemory, args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    mem.write(arg(args, 0), &env.frame.address)?;
+    Ok(vec![])
+}
+
+fn evm_gas_left(env: &mut HostEnv, _mem: &mut dyn GuestMemory, _args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    Ok(vec![Value::I64(env.gas_left() as i64)])
+}
+
+fn evm_ink_left(env: &mut HostEnv, _mem: &mut dyn GuestMemory, _args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    let ink_price = env.world().precompiles.arb_wasm.ink_price as u64;
+    Ok(vec![Value::I64(env.gas_left().saturating_mul(ink_price) as i64)])
+}
+
+fn msg_reentrant(_env: &mut HostEnv, _mem: &mut dyn GuestMemory, _args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    Ok(vec![Value::I32(0)])
+}
+
+fn msg_sender(env: &mut HostEnv, mem: &mut dyn GuestMemory, args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    mem.write(arg(args, 0), &env.frame.caller)?;
+    Ok(vec![])
+}
+
+fn msg_value(env: &mut HostEnv, mem: &mut dyn GuestMemory, args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    mem.write(arg(args, 0), &encode_u128(env.frame.value))?;
+    Ok(vec![])
+}
+
+fn native_keccak256(env: &mut HostEnv, mem: &mut dyn GuestMemory, args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    let data = mem.read(arg(args, 0), arg(args, 1) as usize)?;
+    mem.write(arg(args, 2), &keccak256(&data))?;
+    env.charge("keccak");
+    Ok(vec![])
+}
+
+fn tx_gas_price(env: &mut HostEnv, mem: &mut dyn GuestMemory, args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    mem.write(arg(args, 0), &encode_u128(env.context.gas_price))?;
+    Ok(vec![])
+}
+
+fn tx_ink_price(env: &mut HostEnv, _mem: &mut dyn GuestMemory, _args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    let ink_price = env.world().precompiles.arb_wasm.ink_price;
+    Ok(vec![Value::I32(ink_price as i32)])
+}
+
+fn tx_origin(env: &mut HostEnv, mem: &mut dyn GuestMemory, args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    mem.write(arg(args, 0), &env.context.origin)?;
+    Ok(vec![])
+}
+
+fn pay_for_memory_grow(env: &mut HostEnv, _mem: &mut dyn GuestMemory, args: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+    let pages = arg(args, 0) as u64;
+    env.host_gas += pages * env.gas_table.get("memory_grow").copied().unwrap_or(0);
+    Ok(vec![])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestMemory(Vec<u8>);
+
+    impl GuestMemory for TestMemory {
+        fn read(&self, ptr: u32, len: usize) -> Result<Vec<u8>, RuntimeError> {
+            let start = ptr as usize;
+            self.0
+                .get(start..start + len)
+                .map(<[u8]>::to_vec)
+                .ok_or_else(|| RuntimeError::new("out of bounds memory access"))
+        }
+
+        fn write(&mut self, ptr: u32, data: &[u8]) -> Result<(), RuntimeError> {
+            let start = ptr as usize;
+            self.0
+                .get_mut(start..start + data.len())
+                .ok_or_else(|| RuntimeError::new("out of bounds memory access"))?
+                .copy_from_slice(data);
+            Ok(())
+        }
+    }
+
+    const CREATOR: Address = DEFAULT_CONTRACT;
+
+    fn env() -> HostEnv {
+        let context = ExecutionContext::default();
+        let frame = CallFrame::top_level(&context, Vec::new());
+        let gas_table = [("storage_read", 200), ("storage_write", 5000), ("call_contract", 700), ("create", 32000)]
+            .into_iter()
+            .map(|(op, gas)| (op.to_string(), gas))
+            .collect();
+        let world = World::new(HashMap::new());
+        HostEnv::new(Arc::new(Mutex::new(world)), context, gas_table, frame)
+    }
+
+    fn i32s(args: &[u32]) -> Vec<Value> {
+        args.iter().map(|&arg| Value::I32(arg as i32)).collect()
+    }
+
+    fn word(value: u128) -> Bytes32 {
+        let mut word = [0u8; 32];
+        word[16..].copy_from_slice(&value.to_be_bytes());
+        word
+    }
+
+    /// Memory holding `code` at 0, the endowment at 256 and a salt at 288;
+    /// the created address is written to 320.
+    fn create(env: &mut HostEnv, code: &[u8], endowment: u128, salt: Option<Bytes32>) -> Address {
+        let mut mem = TestMemory(vec![0u8; 512]);
+        mem.write(0, code).unwrap();
+        mem.write(256, &word(endowment)).unwrap();
+        match salt {
+            None => create1(env, &mut mem, &i32s(&[0, code.len() as u32, 256, 320, 340])).unwrap(),
+            Some(salt) => {
+                mem.write(288, &salt).unwrap();
+                create2(env, &mut mem, &i32s(&[0, code.len() as u32, 256, 288, 320, 340])).unwrap()
+            }
+        };
+        mem.read_address(320).unwrap()
+    }
+
+    fn nonce(env: &HostEnv, address: &Address) -> u64 {
+        env.world().account(address).map_or(0, |account| account.nonce)
+    }
+
+    #[test]
+    fn test_storage_hooks_round_trip_and_charge() {
+        let mut env = env();
+        let mut mem = TestMemory(vec![0u8; 128]);
+        mem.write(0, &[7u8; 32]).unwrap();
+        mem.write(32, &[9u8; 32]).unwrap();
+
+        storage_store_bytes32(&mut env, &mut mem, &i32s(&[0, 32])).unwrap();
+        storage_load_bytes32(&mut env, &mut mem, &i32s(&[0, 64])).unwrap();
+        assert_eq!(mem.read_word(64).unwrap(), [9u8; 32]);
+        assert_eq!(env.world().storage_load(&DEFAULT_CONTRACT, &[7u8; 32]), [9u8; 32]);
+        assert_eq!(env.host_gas, 5200);
+
+        env.frame.is_static = true;
+        let err = storage_store_bytes32(&mut env, &mut mem, &i32s(&[0, 0])).unwrap_err();
+        assert!(err.downcast::<StaticCallViolation>().is_ok());
+        assert_eq!(env.world().storage_load(&DEFAULT_CONTRACT, &[7u8; 32]), [9u8; 32]);
+        assert!(storage_load_bytes32(&mut env, &mut mem, &i32s(&[0, 64])).is_ok());
+    }
+
+    #[test]
+    fn test_call_contract_transfers_value_or_fails_without_it() {
+        let mut env = env();
+        let target = address_from_u64(0xbeef);
+        env.world().set_balance(&CREATOR, 100);
+        let mut mem = TestMemory(vec![0u8; 128]);
+        mem.write(0, &target).unwrap();
+        mem.write(32, &word(60)).unwrap();
+        let args = vec![Value::I32(0), Value::I32(0), Value::I32(0), Value::I32(32), Value::I64(0), Value::I32(64)];
+
+        assert_eq!(call_contract(&mut env, &mut mem, &args).unwrap(), [Value::I32(0)]);
+        assert_eq!(call_contract(&mut env, &mut mem, &args).unwrap(), [Value::I32(1)]);
+        let world = env.world();
+        assert_eq!((world.balance(&CREATOR), world.balance(&target)), (40, 60));
+        drop(world);
+        assert_eq!(env.host_gas, 1400);
+
+        env.frame.is_static = true;
+        assert!(call_contract(&mut env, &mut mem, &args).is_err());
+        mem.write(32, &word(0)).unwrap();
+        assert_eq!(call_contract(&mut env, &mut mem, &args).unwrap(), [Value::I32(0)]);
+    }
+
+    #[test]
+    fn test_create_installs_the_contract_at_the_derived_address() {
+        let code = wat::parse_str("(module)").unwrap();
+        let mut env = env();
+        env.world().set_balance(&CREATOR, 100);
+
+        let created = create(&mut env, &code, 30, None);
+        assert_eq!(created, World::create_address(&CREATOR, 0));
+        assert!(env.world().contract(&created).is_some());
+        assert_eq!(env.world().balance(&created), 30);
+        assert_eq!(nonce(&env, &CREATOR), 1);
+
+        let salt = [5u8; 32];
+        let created = create(&mut env, &code, 0, Some(salt));
+        assert_eq!(created, World::create2_address(&CREATOR, &salt, &keccak256(&code)));
+        assert_eq!(env.host_gas, 64000);
+    }
+
+    #[test]
+    fn test_failed_creates_use_the_nonce_like_the_evm() {
+        let code = wat::parse_str("(module)").unwrap();
+        let mut env = env();
+        env.world().set_balance(&CREATOR, 10);
+
+        // Code that does not compile fails after the nonce is used.
+        assert_eq!(create(&mut env, b"not wasm", 0, None), [0u8; 20]);
+        assert_eq!(nonce(&env, &CREATOR), 1);
+
+        // So does a CREATE2 onto an address that already holds a contract.
+        let salt = [1u8; 32];
+        assert_ne!(create(&mut env, &code, 0, Some(salt)), [0u8; 20]);
+        assert_eq!(create(&mut env, &code, 0, Some(salt)), [0u8; 20]);
+        assert_eq!(nonce(&env, &CREATOR), 3);
+
+        // An endowment the creator cannot pay fails before the nonce is touched.
+        assert_eq!(create(&mut env, &code, 11, None), [0u8; 20]);
+        assert_eq!(nonce(&env, &CREATOR), 3);
+        assert_eq!(env.world().balance(&CREATOR), 10);
+        assert_eq!(create(&mut env, &code, 10, None), World::create_address(&CREATOR, 3));
+
+        env.frame.is_static = true;
+        let mut mem = TestMemory(vec![0u8; 512]);
+        assert!(create1(&mut env, &mut mem, &i32s(&[0, 0, 256, 320, 340])).is_err());
+        assert_eq!(nonce(&env, &CREATOR), 4);
+    }
+}
@@ -0,0 +1,230 @@
+use crate::abi::{hex, parse_hex};
+use crate::host::{format_value, GuestMemory, HostEnv, OUT_OF_GAS};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use wasmer::{RuntimeError, Value};
+
+/// One host import call, with its arguments and results decoded from the contract's memory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostCallTrace {
+    pub module: String,
+    pub name: String,
+    /// Call depth of the contract that made the call; 1 for the top-level contract.
+    pub depth: u32,
+    /// Decoded arguments as `(name, value)`, e.g. `("key", "0x…")`.
+    pub args: Vec<(String, String)>,
+    /// Returned values and what the host wrote back to memory.
+    pub results: Vec<(String, String)>,
+    /// Host gas charged, including that of any nested contract call.
+    pub gas: u64,
+    /// Set when the call trapped.
+    pub error: Option<String>,
+}
+
+impl HostCallTrace {
+    /// A decoded argument or result by name.
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.args.iter().chain(&self.results).find(|(field, _)| field == name).map(|(_, value)| value.as_str())
+    }
+
+    /// A decoded byte field, such as `calldata` or `key`, parsed back from hex.
+    pub fn bytes(&self, name: &str) -> Option<Vec<u8>> {
+        parse_hex(self.field(name)?)
+    }
+}
+
+impl fmt::Display for HostCallTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |fields: &[(String, String)]| {
+            fields.iter().map(|(name, value)| format!("{}={}", name, value)).collect::<Vec<_>>().join(", ")
+        };
+        write!(f, "{}::{}({})", self.module, self.name, join(&self.args))?;
+        match &self.error {
+            Some(error) => write!(f, " !! {}", error)?,
+            None if !self.results.is_empty() => write!(f, " -> {}", join(&self.results))?,
+            None => {}
+        }
+        write!(f, " [{} gas]", self.gas)
+    }
+}
+
+/// Where a decoded value comes from; indices are into the call's arguments.
+#[derive(Clone, Copy)]
+enum Field {
+    Int(usize),
+    Word(usize),
+    Address(usize),
+    /// `(pointer, length)` arguments.
+    Bytes(usize, usize),
+    Text(usize, usize),
+    /// The length of bytes at a pointer is the number the call returned.
+    ReturnedBytes(usize),
+    Result(usize),
+    Calldata(usize),
+    ReturnData,
+    /// `emit_log`'s data, after its topics which are decoded as their own fields.
+    Log,
+}
+
+type Fields = &'static [(&'static str, Field)];
+
+/// How the arguments and results of a built-in hook are laid out.
+fn layout(module: &str, name: &str) -> Option<(Fields, Fields)> {
+    use Field::*;
+    let layout: (Fields, Fields) = match (module, name) {
+        ("vm_hooks", "read_args") => (&[], &[("calldata", Calldata(0))]),
+        ("vm_hooks", "write_result") => (&[("data", Bytes(0, 1))], &[]),
+        ("vm_hooks", "storage_load_bytes32") => (&[("key", Word(0))], &[("value", Word(1))]),
+        ("vm_hooks", "storage_store_bytes32" | "storage_cache_bytes32") => {
+            (&[("key", Word(0)), ("value", Word(1))], &[])
+        }
+        ("vm_hooks", "emit_log") => (&[("data", Log)], &[]),
+        ("vm_hooks", "call_contract") => (
+            &[("to", Address(0)), ("calldata", Bytes(1, 2)), ("value", Word(3)), ("gas", Int(4))],
+            &[("status", Result(0)), ("return_data", ReturnData)],
+        ),
+        ("vm_hooks", "delegate_call_contract" | "static_call_contract") => (
+            &[("to", Address(0)), ("calldata", Bytes(1, 2)), ("gas", Int(3))],
+            &[("status", Result(0)), ("return_data", ReturnData)],
+        ),
+        ("vm_hooks", "create1") => (&[("code_len", Int(1)), ("endowment", Word(2))], &[("address", Address(3))]),
+        ("vm_hooks", "create2") => (
+            &[("code_len", Int(1)), ("endowment", Word(2)), ("salt", Word(3))],
+            &[("address", Address(4))],
+        ),
+        ("vm_hooks", "read_return_data") => (&[("offset", Int(1)), ("size", Int(2))], &[("data", ReturnedBytes(0))]),
+        ("vm_hooks", "account_balance") => (&[("address", Address(0))], &[("balance", Word(1))]),
+        ("vm_hooks", "account_codehash") => (&[("address", Address(0))], &[("codehash", Word(1))]),
+        ("vm_hooks", "block_basefee" | "msg_value" | "tx_gas_price") => (&[], &[("value", Word(0))]),
+        ("vm_hooks", "block_coinbase" | "contract_address" | "msg_sender" | "tx_origin") => {
+            (&[], &[("address", Address(0))])
+        }
+        ("vm_hooks", "native_keccak256") => (&[("data", Bytes(0, 1))], &[("hash", Word(2))]),
+        ("vm_hooks", "memory_grow" | "pay_for_memory_grow") => (&[("pages", Int(0))], &[]),
+        ("console", "log_txt") => (&[("text", Text(0, 1))], &[]),
+        _ => return None,
+    };
+    Some(layout)
+}
+
+/// Runs a host import, appending the call to the environment's host call trace.
+pub(crate) fn traced(
+    env: &mut HostEnv,
+    mem: &mut dyn GuestMemory,
+    (module, name): (&str, &str),
+    args: &[Value],
+    call: impl FnOnce(&mut HostEnv, &mut dyn GuestMemory) -> Result<Vec<Value>, RuntimeError>,
+) -> Result<Vec<Value>, RuntimeError> {
+    let layout = layout(module, name);
+    let decoded = match layout {
+        Some((inputs, _)) => decode(inputs, env, &*mem, args, &[]),
+        None => args.iter().enumerate().map(|(i, value)| (format!("arg{}", i), format_value(value))).collect(),
+    };
+    // Pushed before running so nested contract calls are listed after the call that made them.
+    let index = env.host_calls.len();
+    env.host_calls.push(HostCallTrace {
+        module: module.to_string(),
+        name: name.to_string(),
+        depth: env.frame.depth,
+        args: decoded,
+        results: Vec::new(),
+        gas: 0,
+        error: None,
+    });
+    let host_gas = env.host_gas;
+
+    // Host charges count against the same limit as instructions and fail the call that exceeds it.
+    let outcome = call(env, mem).and_then(|results| {
+        if env.gas_used() > env.context.gas_limit {
+            return Err(RuntimeError::new(OUT_OF_GAS));
+        }
+        Ok(results)
+    });
+    let results = match (&outcome, layout) {
+        (Ok(results), Some((_, outputs))) => decode(outputs, env, &*mem, args, results),
+        (Ok(results), None) => {
+            results.iter().enumerate().map(|(i, value)| (format!("result{}", i), format_value(value))).collect()
+        }
+        (Err(_), _) => Vec::new(),
+    };
+    let trace = &mut env.host_calls[index];
+    trace.results = results;
+    trace.gas = env.host_gas.saturating_sub(host_gas);
+    trace.error = outcome.as_ref().err().map(RuntimeError::message);
+    env.inspector.inspect(|inspector| inspector.on_host_call(&env.host_calls[index]));
+    outcome
+}
+
+fn decode(fields: Fields, env: &HostEnv, mem: &dyn GuestMemory, args: &[Value], results: &[Value]) -> Vec<(String, String)> {
+    let int = |index: usize| args.get(index).and_then(Value::i32).map_or(0, |v| v as u32);
+    let bytes = |ptr: usize, len: usize| mem.read(int(ptr), len).map(|data| hex(&data));
+    let mut decoded = Vec::new();
+    for &(name, field) in fields {
+        let value = match field {
+            Field::Int(index) => Ok(args.get(index).map(format_value).unwrap_or_default()),
+            Field::Word(index) => bytes(index, 32),
+            Field::Address(index) => bytes(index, 20),
+            Field::Bytes(ptr, len) => bytes(ptr, int(len) as usize),
+            Field::Text(ptr, len) => mem
+                .read(int(ptr), int(len) as usize)
+                .map(|text| format!("{:?}", String::from_utf8_lossy(&text))),
+            Field::ReturnedBytes(ptr) => {
+                let len = results.first().and_then(Value::i32).unwrap_or(0) as usize;
+                bytes(ptr, len)
+            }
+            Field::Result(index) => Ok(results.get(index).map(format_value).unwrap_or_default()),
+            Field::Calldata(ptr) => bytes(ptr, env.frame.calldata.len()),
+            Field::ReturnData => Ok(hex(&env.return_data)),
+            Field::Log => {
+                let Ok(data) = mem.read(int(0), int(1) as usize) else { continue };
+                let topics = (int(2) as usize).min(data.len() / 32);
+                for (i, topic) in data[..topics * 32].chunks(32).enumerate() {
+                    decoded.push((format!("topic{}", i), hex(topic)));
+                }
+                Ok(hex(&data[topics * 32..]))
+            }
+        };
+        let value = value.unwrap_or_else(|e| format!("<{}>", e.message()));
+        decoded.push((name.to_string(), value));
+    }
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::StylusRuntime;
+
+    #[test]
+    fn test_host_calls_are_traced_with_decoded_arguments() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (import "vm_hooks" "storage_store_bytes32" (func $store (param i32 i32)))
+                (import "vm_hooks" "storage_load_bytes32" (func $load (param i32 i32)))
+                (import "vm_hooks" "emit_log" (func $log (param i32 i32 i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 31) "\01")
+                (data (i32.const 63) "\2a")
+                (func (export "run") (param $n i64) (result i64)
+                    (call $store (i32.const 0) (i32.const 32))
+                    (call $load (i32.const 0) (i32.const 64))
+                    (call $log (i32.const 0) (i32.const 64) (i32.const 1))
+                    (i64.load8_u (i32.const 95)))
+            )
+        "#).unwrap();
+        let mut runtime = StylusRuntime::new(&wasm).unwrap();
+        let result = runtime.execute_function("run", &[0]).unwrap();
+        assert_eq!(result.return_value, 42);
+
+        let names: Vec<&str> = result.host_calls.iter().map(|call| call.name.as_str()).collect();
+        assert_eq!(names, ["storage_store_bytes32", "storage_load_bytes32", "emit_log"]);
+        let word = |last: &str| format!("0x{}{}", "0".repeat(62), last);
+        let store = &result.host_calls[0];
+        assert_eq!(store.args, [("key".to_string(), word("01")), ("value".to_string(), word("2a"))]);
+        assert!(store.gas > 0);
+        assert_eq!(result.host_calls[1].results, [("value".to_string(), word("2a"))]);
+        let log = &result.host_calls[2];
+        assert_eq!(log.args[0], ("topic0".to_string(), word("01")));
+        assert_eq!(log.args[1], ("data".to_string(), word("2a")));
+        assert!(log.to_string().starts_with("vm_hooks::emit_log(topic0=0x"));
+    }
+}
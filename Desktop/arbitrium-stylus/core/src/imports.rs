@@ -0,0 +1,223 @@
+use crate::host::{self, CustomImport};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use wasmer::{ExternType, Function, FunctionType, Imports, Module, RuntimeError, Store, Type, Value};
+
+/// What to do with imports that neither WASI nor the Stylus host provides.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StubPolicy {
+    /// Refuse to instantiate and report every missing import.
+    #[default]
+    Fail,
+    /// Link stubs that trap with the import's name when called.
+    Trap,
+    /// Link stubs that return zero for every result.
+    Zero,
+}
+
+impl FromStr for StubPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "fail" => Ok(StubPolicy::Fail),
+            "trap" => Ok(StubPolicy::Trap),
+            "zero" => Ok(StubPolicy::Zero),
+            _ => Err(anyhow!("Unknown stub policy '{}' (expected fail, trap or zero)", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MissingImport {
+    pub module: String,
+    pub name: String,
+    pub kind: String,
+    pub signature: Option<String>,
+    /// The signature the host provides, when the contract imports a host function with another one.
+    #[serde(default)]
+    pub expected: Option<String>,
+}
+
+impl fmt::Display for MissingImport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.signature, &self.expected) {
+            (Some(signature), Some(expected)) => {
+                write!(f, "{}::{} {} (host provides {})", self.module, self.name, signature, expected)
+            }
+            (Some(signature), None) => write!(f, "{}::{} {}", self.module, self.name, signature),
+            (None, _) => write!(f, "{}::{} ({})", self.module, self.name, self.kind),
+        }
+    }
+}
+
+/// Returned when a module cannot be linked under `StubPolicy::Fail`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnresolvedImports(pub Vec<MissingImport>);
+
+impl fmt::Display for UnresolvedImports {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Contract has {} unresolved import(s):", self.0.len())?;
+        for import in &self.0 {
+            write!(f, "\n  {}", import)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for UnresolvedImports {}
+
+pub fn format_signature(ty: &FunctionType) -> String {
+    let list = |types: &[Type]| {
+        types
+            .iter()
+            .map(|t| format!("{:?}", t).to_lowercase())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    format!("({}) -> ({})", list(ty.params()), list(ty.results()))
+}
+
+/// Lists every import of `module` that `imports` does not satisfy, including host functions
+/// imported with a different signature than the host, or `custom_imports`, define them with.
+pub fn find_missing(module: &Module, imports: &Imports, custom_imports: &[CustomImport]) -> Vec<MissingImport> {
+    module
+        .imports()
+        .filter_map(|import| {
+            let (kind, signature, expected) = match import.ty() {
+                ExternType::Function(ty) => {
+                    let expected = host::host_signature(custom_imports, import.module(), import.name())
+                        .filter(|expected| expected != ty);
+                    ("function", Some(format_signature(ty)), expected.map(|expected| format_signature(&expected)))
+                }
+                ExternType::Global(_) => ("global", None, None),
+                ExternType::Table(_) => ("table", None, None),
+                ExternType::Memory(_) => ("memory", None, None),
+            };
+            if expected.is_none() && imports.exists(import.module(), import.name()) {
+                return None;
+            }
+            Some(MissingImport {
+                module: import.module().to_string(),
+                name: import.name().to_string(),
+                kind: kind.to_string(),
+                signature,
+                expected,
+            })
+        })
+        .collect()
+}
+
+/// Applies `policy` to the imports `module` is missing, linking stubs where allowed.
+/// Mismatched signatures always fail, since the host function cannot be replaced by a stub.
+pub(crate) fn resolve(
+    store: &mut Store,
+    module: &Module,
+    imports: &mut Imports,
+    custom_imports: &[CustomImport],
+    policy: StubPolicy,
+) -> Result<()> {
+    let missing = find_missing(module, imports, custom_imports);
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let unstubbable = missing.iter().any(|m| m.signature.is_none() || m.expected.is_some());
+    if policy == StubPolicy::Fail || unstubbable {
+        return Err(UnresolvedImports(missing).into());
+    }
+
+    for import in module.imports() {
+        let ExternType::Function(ty) = import.ty() else {
+            continue;
+        };
+        if imports.exists(import.module(), import.name()) {
+            continue;
+        }
+
+        tracing::warn!("Linking {:?} stub for unresolved import {}::{}", policy, import.module(), import.name());
+        let label = format!("{}::{}", import.module(), import.name());
+        let results = ty.results().to_vec();
+        let stub = Function::new(store, ty.clone(), move |_args: &[Value]| match policy {
+            StubPolicy::Zero => Ok(results.iter().map(zero_value).collect()),
+            _ => Err(RuntimeError::new(format!("Called unresolved import {}", label))),
+        });
+        imports.define(import.module(), import.name(), stub);
+    }
+    Ok(())
+}
+
+fn zero_value(ty: &Type) -> Value {
+    match ty {
+        Type::I32 => Value::I32(0),
+        Type::I64 => Value::I64(0),
+        Type::F32 => Value::F32(0.0),
+        Type::F64 => Value::F64(0.0),
+        Type::V128 => Value::V128(0),
+        Type::ExternRef => Value::ExternRef(None),
+        Type::FuncRef => Value::FuncRef(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::StylusRuntime;
+    use super::*;
+
+    #[test]
+    fn test_missing_imports_follow_stub_policy() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (import "env" "probe" (func $probe (param i32) (result i64)))
+                (func (export "safe") (result i64)
+                    i64.const 1)
+                (func (export "probe") (result i64)
+                    (call $probe (i32.const 5)))
+            )
+        "#).unwrap();
+
+        let mut runtime = StylusRuntime::new(&wasm).unwrap();
+        let missing = runtime.missing_imports().unwrap();
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].to_string(), "env::probe (i32) -> (i64)");
+
+        let err = runtime.execute_function("safe", &[]).unwrap_err();
+        assert!(err.downcast_ref::<UnresolvedImports>().is_some());
+
+        runtime.set_stub_policy(StubPolicy::Trap);
+        assert_eq!(runtime.execute_function("safe", &[]).unwrap().return_value, 1);
+        assert!(runtime.execute_function("probe", &[]).is_err());
+
+        runtime.set_stub_policy(StubPolicy::Zero);
+        assert_eq!(runtime.execute_function("probe", &[]).unwrap().return_value, 0);
+    }
+
+    #[test]
+    fn test_host_imports_with_wrong_signatures_are_unresolved() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (import "vm_hooks" "read_args" (func $read_args (param i64)))
+                (memory (export "memory") 1)
+                (func (export "run") (result i64)
+                    (call $read_args (i64.const 0))
+                    (i64.const 1))
+            )
+        "#).unwrap();
+
+        let mut runtime = StylusRuntime::new(&wasm).unwrap();
+        let missing = runtime.missing_imports().unwrap();
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].to_string(), "vm_hooks::read_args (i64) -> () (host provides (i32) -> ())");
+
+        // A stub cannot stand in for a host function, whatever the policy.
+        runtime.set_stub_policy(StubPolicy::Zero);
+        let err = runtime.execute_function("run", &[]).unwrap_err();
+        assert_eq!(err.downcast_ref::<UnresolvedImports>().unwrap().0, missing);
+
+        // The interpreter links imports itself and rejects the same mismatch.
+        let err = runtime.debug("run", &[]).err().unwrap();
+        assert_eq!(err.downcast_ref::<UnresolvedImports>().unwrap().0, missing);
+    }
+}
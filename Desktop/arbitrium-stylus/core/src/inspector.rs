@@ -0,0 +1,191 @@
+//! Hooks for custom analyses of an execution, such as watching storage slots or counting calls.
+
+use crate::host::CallFrame;
+use crate::host_trace::HostCallTrace;
+use crate::interpreter::Machine;
+use crate::world::{Address, Bytes32, Log};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Observes a runtime's calls as they run. Every hook does nothing by default.
+///
+/// Hooks fire for nested contract calls too; `CallFrame::depth` tells them apart.
+pub trait Inspector: Send {
+    /// A contract starts running, at the top level or through `call_contract` and its variants.
+    fn on_call_enter(&mut self, _frame: &CallFrame) {}
+
+    /// A contract finished; `success` is false if it reverted or trapped.
+    fn on_call_exit(&mut self, _frame: &CallFrame, _success: bool, _output: &[u8]) {}
+
+    /// Whether `on_instruction` should be called. Top-level calls then run in the
+    /// interpreter instead of wasmer, which is much slower but meters gas and enforces
+    /// the gas limit at the same instructions, so results and out-of-gas traps match.
+    fn wants_instructions(&self) -> bool {
+        false
+    }
+
+    /// The top-level contract, or a nested call while coverage is enabled, is about to execute
+    /// `machine.current_instr()`.
+    fn on_instruction(&mut self, _machine: &Machine) {}
+
+    /// A host import returned, with its decoded arguments and results.
+    fn on_host_call(&mut self, _call: &HostCallTrace) {}
+
+    fn on_storage_read(&mut self, _address: &Address, _key: &Bytes32, _value: &Bytes32) {}
+
+    fn on_storage_write(&mut self, _address: &Address, _key: &Bytes32, _value: &Bytes32) {}
+
+    fn on_log(&mut self, _log: &Log) {}
+
+    /// A contract trapped; `on_call_exit` follows.
+    fn on_trap(&mut self, _frame: &CallFrame, _message: &str) {}
+}
+
+/// The inspector of a runtime, shared with the host environment of every frame it runs.
+#[derive(Clone, Default)]
+pub(crate) struct InspectorSlot(Arc<Mutex<Option<Box<dyn Inspector>>>>);
+
+impl InspectorSlot {
+    fn lock(&self) -> MutexGuard<'_, Option<Box<dyn Inspector>>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn replace(&self, inspector: Option<Box<dyn Inspector>>) -> Option<Box<dyn Inspector>> {
+        std::mem::replace(&mut *self.lock(), inspector)
+    }
+
+    pub(crate) fn inspect(&self, hook: impl FnOnce(&mut dyn Inspector)) {
+        if let Some(inspector) = self.lock().as_deref_mut() {
+            hook(inspector);
+        }
+    }
+
+    pub(crate) fn wants_instructions(&self) -> bool {
+        self.lock().as_ref().is_some_and(|inspector| inspector.wants_instructions())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{StylusRuntime, Trap};
+
+    #[derive(Default)]
+    struct Seen {
+        calls: Vec<(u32, bool)>,
+        writes: Vec<(Bytes32, Bytes32)>,
+        host_calls: usize,
+        logs: usize,
+        instructions: u64,
+        traps: Vec<String>,
+    }
+
+    /// Reports what it saw through a shared handle.
+    struct Counter(Arc<Mutex<Seen>>);
+
+    impl Inspector for Counter {
+        fn on_call_enter(&mut self, frame: &CallFrame) {
+            self.0.lock().unwrap().calls.push((frame.depth, true));
+        }
+
+        fn on_call_exit(&mut self, frame: &CallFrame, success: bool, _output: &[u8]) {
+            self.0.lock().unwrap().calls.push((frame.depth, success));
+        }
+
+        fn wants_instructions(&self) -> bool {
+            true
+        }
+
+        fn on_instruction(&mut self, _machine: &Machine) {
+            self.0.lock().unwrap().instructions += 1;
+        }
+
+        fn on_host_call(&mut self, _call: &HostCallTrace) {
+            self.0.lock().unwrap().host_calls += 1;
+        }
+
+        fn on_storage_write(&mut self, _address: &Address, key: &Bytes32, value: &Bytes32) {
+            self.0.lock().unwrap().writes.push((*key, *value));
+        }
+
+        fn on_log(&mut self, _log: &Log) {
+            self.0.lock().unwrap().logs += 1;
+        }
+
+        fn on_trap(&mut self, _frame: &CallFrame, message: &str) {
+            self.0.lock().unwrap().traps.push(message.to_string());
+        }
+    }
+
+    #[test]
+    fn test_inspector_sees_storage_logs_instructions_and_traps() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (import "vm_hooks" "storage_store_bytes32" (func $store (param i32 i32)))
+                (import "vm_hooks" "emit_log" (func $log (param i32 i32 i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 31) "\01")
+                (data (i32.const 63) "\2a")
+                (func (export "run") (param $n i64) (result i64)
+                    (call $store (i32.const 0) (i32.const 32))
+                    (call $log (i32.const 0) (i32.const 64) (i32.const 1))
+                    (local.get $n))
+                (func (export "fail") (param $n i64) (result i64)
+                    unreachable)
+            )
+        "#).unwrap();
+        let mut runtime = StylusRuntime::new(&wasm).unwrap();
+        let plain = runtime.execute_function("run", &[7]).unwrap();
+        let seen = Arc::new(Mutex::new(Seen::default()));
+        runtime.set_inspector(Box::new(Counter(seen.clone())));
+
+        // Watching every instruction must not change what the call reports.
+        let inspected = runtime.execute_function("run", &[7]).unwrap();
+        assert_eq!(inspected.return_value, 7);
+        assert_eq!(
+            (inspected.gas_used, inspected.memory_usage, &inspected.logs),
+            (plain.gas_used, plain.memory_usage, &plain.logs)
+        );
+        assert!(runtime.execute_function("fail", &[0]).is_err());
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.calls, [(1, true), (1, true), (1, true), (1, false)]);
+        let mut key = [0u8; 32];
+        key[31] = 1;
+        let mut value = [0u8; 32];
+        value[31] = 0x2a;
+        assert_eq!(seen.writes, [(key, value)]);
+        assert_eq!((seen.host_calls, seen.logs), (2, 1));
+        assert!(seen.instructions >= 8);
+        assert_eq!(seen.traps.len(), 1);
+
+        assert!(runtime.take_inspector().is_some());
+        assert!(runtime.take_inspector().is_none());
+    }
+
+    #[test]
+    fn test_inspected_calls_run_out_of_gas_where_plain_calls_do() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (func (export "spin") (param $n i64) (result i64) (local $i i64)
+                    (loop $next
+                        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+                        (br_if $next (i64.lt_s (local.get $i) (local.get $n))))
+                    (local.get $i))
+            )
+        "#).unwrap();
+        let mut runtime = StylusRuntime::new(&wasm).unwrap();
+        let plain = runtime.execute_function("spin", &[10]).unwrap();
+        runtime.set_inspector(Box::new(Counter(Arc::default())));
+        assert_eq!(runtime.execute_function("spin", &[10]).unwrap().gas_used, plain.gas_used);
+
+        // Exactly enough gas passes in both engines; one less fails in both.
+        runtime.context_mut().gas_limit = plain.gas_used;
+        assert!(runtime.execute_function("spin", &[10]).is_ok());
+        runtime.context_mut().gas_limit = plain.gas_used - 1;
+        let inspected = runtime.execute_function("spin", &[10]).unwrap_err();
+        runtime.take_inspector();
+        let uninspected = runtime.execute_function("spin", &[10]).unwrap_err();
+        assert_eq!(inspected.downcast_ref::<Trap>().unwrap().message, "out of gas");
+        assert_eq!(uninspected.downcast_ref::<Trap>().unwrap().message, "out of gas");
+    }
+}
@@ -0,0 +1,1228 @@
+use super::program::{ConstInit, Instr, Program};
+use super::Val;
+use crate::backtrace::{Backtrace, BacktraceFrame};
+use crate::console::{self, ConsoleLine, ConsoleStream};
+use crate::host::{self, GuestMemory, HostEnv, HostHandler, OUT_OF_GAS};
+use crate::host_trace;
+use crate::imports::{format_signature, MissingImport, StubPolicy, UnresolvedImports};
+use crate::recording::{HostCall, StorageAccess, StorageOp, Trace};
+use crate::symbols::Symbols;
+use crate::world::Log;
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+use wasmer::{FunctionType, RuntimeError, Type, Value};
+use wasmparser::ValType;
+
+const PAGE_SIZE: u64 = 65536;
+const MAX_PAGES: u64 = 65536;
+const MAX_FRAMES: usize = 16 * 1024;
+
+/// WASI errno values returned by the shim.
+const ERRNO_SUCCESS: i32 = 0;
+const ERRNO_BADF: i32 = 8;
+
+/// One active wasm function call.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub func: u32,
+    /// Index into the function body of the next instruction to execute.
+    pub pc: usize,
+    pub locals: Vec<Val>,
+    labels: Vec<Label>,
+    stack_base: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Label {
+    height: usize,
+    arity: usize,
+    target: usize,
+    is_loop: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    Running,
+    Finished(Vec<Val>),
+}
+
+#[derive(Clone, Copy)]
+enum Wasi {
+    FdWrite,
+    ProcExit,
+    SizesGet,
+    RandomGet,
+    ClockTimeGet,
+    Errno(i32),
+}
+
+enum Binding {
+    Hook(HostHandler),
+    Custom(usize),
+    Wasi(Wasi),
+    Stub(String),
+    Zero,
+    Replay,
+}
+
+/// Whether host calls are recorded, or answered from an earlier recording.
+pub(crate) enum Tape {
+    Off,
+    Recording(Trace),
+    Replaying { trace: Arc<Trace>, cursor: usize },
+}
+
+/// Everything `restore` needs to rewind a replaying machine.
+#[derive(Clone)]
+pub(crate) struct Checkpoint {
+    memory: Vec<u8>,
+    globals: Vec<Val>,
+    table: Vec<Option<u32>>,
+    dropped: Vec<bool>,
+    stack: Vec<Val>,
+    frames: Vec<Frame>,
+    results: Option<usize>,
+    gas_used: u64,
+    steps: u64,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    cursor: usize,
+    host_gas: u64,
+    output: Vec<u8>,
+    logs: Vec<Log>,
+    console: Vec<ConsoleLine>,
+}
+
+impl Checkpoint {
+    pub(crate) fn steps(&self) -> u64 {
+        self.steps
+    }
+}
+
+/// A contract instance executed one instruction at a time.
+///
+/// Imports are served by the same handlers as the wasmer engine, so storage, logs,
+/// subcalls and console output behave identically.
+pub struct Machine {
+    program: Arc<Program>,
+    symbols: Arc<Symbols>,
+    env: HostEnv,
+    memory: Vec<u8>,
+    max_pages: u64,
+    globals: Vec<Val>,
+    table: Vec<Option<u32>>,
+    dropped: Vec<bool>,
+    bindings: Vec<Option<Binding>>,
+    costs: Vec<Vec<u64>>,
+    stack: Vec<Val>,
+    frames: Vec<Frame>,
+    results: Option<usize>,
+    gas_used: u64,
+    steps: u64,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    tape: Tape,
+    /// Whether the environment's inspector asked to see every instruction.
+    inspect_instructions: bool,
+}
+
+impl Machine {
+    /// Links and instantiates `program`, running its start function if it has one.
+    pub fn new(program: Arc<Program>, symbols: Arc<Symbols>, env: HostEnv) -> Result<Self> {
+        Self::with_tape(program, symbols, env, Tape::Off)
+    }
+
+    pub(crate) fn with_tape(program: Arc<Program>, symbols: Arc<Symbols>, env: HostEnv, tape: Tape) -> Result<Self> {
+        let replaying = matches!(tape, Tape::Replaying { .. });
+        let bindings = link(&program, &env, replaying)?;
+        let costs = program
+            .functions
+            .iter()
+            .map(|function| function.body.iter().map(|instr| instruction_cost(&env, instr)).collect())
+            .collect();
+
+        let (initial, maximum) = program.memory.unwrap_or((0, Some(0)));
+        let inspect_instructions = env.inspector.wants_instructions();
+        let mut machine = Self {
+            memory: vec![0u8; (initial * PAGE_SIZE) as usize],
+            max_pages: maximum.unwrap_or(MAX_PAGES).min(MAX_PAGES),
+            globals: Vec::new(),
+            table: vec![None; program.table.map(|(initial, _)| initial as usize).unwrap_or(0)],
+            dropped: vec![false; program.data.len()],
+            bindings,
+            costs,
+            stack: Vec::new(),
+            frames: Vec::new(),
+            results: None,
+            gas_used: 0,
+            steps: 0,
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            tape,
+            inspect_instructions,
+            program,
+            symbols,
+            env,
+        };
+        machine.initialize()?;
+
+        if let Some(start) = machine.program.start {
+            machine.call(start, &[])?;
+            machine.run().map_err(|e| anyhow!("Start function failed: {}", e.message()))?;
+            // Gas is metered from the call, as wasmer does after instantiating.
+            machine.gas_used = 0;
+            machine.env.host_gas = 0;
+        }
+        Ok(machine)
+    }
+
+    fn initialize(&mut self) -> Result<()> {
+        let program = self.program.clone();
+        for global in &program.globals {
+            let value = self.const_value(global.init);
+            self.globals.push(value);
+        }
+        for segment in &program.elements {
+            let Some(offset) = segment.offset else { continue };
+            let offset = self.const_value(offset).as_i64() as u32 as usize;
+            let end = offset + segment.functions.len();
+            if end > self.table.len() {
+                return Err(anyhow!("Failed to instantiate: element segment does not fit the table"));
+            }
+            self.table[offset..end].copy_from_slice(&segment.functions);
+        }
+        for (index, segment) in program.data.iter().enumerate() {
+            let Some(offset) = segment.offset else { continue };
+            let offset = self.const_value(offset).as_i64() as u32 as usize;
+            let end = offset + segment.bytes.len();
+            if end > self.memory.len() {
+                return Err(anyhow!("Failed to instantiate: data segment does not fit memory"));
+            }
+            self.memory[offset..end].copy_from_slice(&segment.bytes);
+            self.dropped[index] = true;
+        }
+        Ok(())
+    }
+
+    fn const_value(&self, init: ConstInit) -> Val {
+        match init {
+            ConstInit::Value(value) => value,
+            ConstInit::Global(index) => self.globals[index as usize],
+            ConstInit::FuncRef(_) => Val::I32(0),
+        }
+    }
+
+    /// Prepares a call to `func`; nothing executes until `step` or `run`.
+    /// Any call still in progress is abandoned.
+    pub fn call(&mut self, func: u32, args: &[Val]) -> Result<()> {
+        let function = self
+            .program
+            .function(func)
+            .ok_or_else(|| anyhow!("Function {} not found", func))?;
+        let signature = &self.program.types[function.type_index as usize];
+        if args.len() != signature.params.len() {
+            return Err(anyhow!(
+                "Function {} takes {} argument(s), got {}",
+                func,
+                signature.params.len(),
+                args.len()
+            ));
+        }
+        self.stack.clear();
+        self.frames.clear();
+        self.results = Some(signature.results.len());
+        self.stack.extend_from_slice(args);
+        self.enter(func)?;
+        Ok(())
+    }
+
+    /// Executes one instruction. On a trap the machine stays on the faulting
+    /// instruction so its frames can still be inspected.
+    pub fn step(&mut self) -> Result<Step, RuntimeError> {
+        if self.frames.is_empty() {
+            return self.finish();
+        }
+
+        if self.inspect_instructions {
+            self.env.inspector.inspect(|inspector| inspector.on_instruction(self));
+        }
+        if let Some(coverage) = &self.env.coverage {
+            coverage.map().record(self);
+        }
+        let depth = self.frames.len();
+        let pc = self.frames[depth - 1].pc;
+        if let Err(e) = self.execute() {
+            self.frames.truncate(depth);
+            self.frames[depth - 1].pc = pc;
+            self.results = None;
+            self.flush_console();
+            return Err(e);
+        }
+        self.steps += 1;
+
+        let gas = self.gas_used();
+        match &mut self.tape {
+            Tape::Off => {}
+            Tape::Recording(trace) => {
+                trace.steps = self.steps;
+                trace.gas_used = gas;
+            }
+            Tape::Replaying { trace, .. } => {
+                if self.steps > trace.steps || (self.frames.is_empty() && gas != trace.gas_used) {
+                    return Err(RuntimeError::new(format!(
+                        "Replay diverged from the recording at step {}",
+                        self.steps
+                    )));
+                }
+            }
+        }
+
+        if self.frames.is_empty() {
+            return self.finish();
+        }
+        Ok(Step::Running)
+    }
+
+    /// Steps until the current call returns.
+    pub fn run(&mut self) -> Result<Vec<Val>, RuntimeError> {
+        loop {
+            if let Step::Finished(results) = self.step()? {
+                return Ok(results);
+            }
+        }
+    }
+
+    fn finish(&mut self) -> Result<Step, RuntimeError> {
+        let arity = self
+            .results
+            .take()
+            .ok_or_else(|| RuntimeError::new("No call is in progress"))?;
+        self.flush_console();
+        let results = self.stack.split_off(self.stack.len() - arity);
+        Ok(Step::Finished(results))
+    }
+
+    pub fn program(&self) -> &Arc<Program> {
+        &self.program
+    }
+
+    pub fn symbols(&self) -> &Arc<Symbols> {
+        &self.symbols
+    }
+
+    /// Active frames, outermost first.
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// The operand stack of the innermost frame.
+    pub fn stack(&self) -> &[Val] {
+        let base = self.frames.last().map(|frame| frame.stack_base).unwrap_or(0);
+        &self.stack[base.min(self.stack.len())..]
+    }
+
+    pub fn globals(&self) -> &[Val] {
+        &self.globals
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    /// The next instruction to execute, as `(function, instruction index)`.
+    pub fn position(&self) -> Option<(u32, usize)> {
+        self.frames.last().map(|frame| (frame.func, frame.pc))
+    }
+
+    /// The instruction the next step executes.
+    pub fn current_instr(&self) -> Option<&Instr> {
+        let (func, pc) = self.position()?;
+        self.program.functions[func as usize].body.get(pc)
+    }
+
+    /// Module byte offset of the next instruction.
+    pub fn module_offset(&self) -> Option<u64> {
+        let (func, pc) = self.position()?;
+        self.program.functions[func as usize].offsets.get(pc).copied()
+    }
+
+    /// Instruction gas plus host gas charged so far, as `ExecutionResult::gas_used` reports it.
+    pub fn gas_used(&self) -> u64 {
+        self.gas_used + self.env.host_gas
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// The trace recorded so far, leaving an empty one in its place.
+    pub(crate) fn take_trace(&mut self) -> Option<Trace> {
+        match &mut self.tape {
+            Tape::Recording(trace) => Some(std::mem::take(trace)),
+            _ => None,
+        }
+    }
+
+    /// The recording this machine is replaying.
+    pub(crate) fn replay_trace(&self) -> Option<&Trace> {
+        match &self.tape {
+            Tape::Replaying { trace, .. } => Some(trace),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            memory: self.memory.clone(),
+            globals: self.globals.clone(),
+            table: self.table.clone(),
+            dropped: self.dropped.clone(),
+            stack: self.stack.clone(),
+            frames: self.frames.clone(),
+            results: self.results,
+            gas_used: self.gas_used,
+            steps: self.steps,
+            stdout: self.stdout.clone(),
+            stderr: self.stderr.clone(),
+            cursor: match self.tape {
+                Tape::Replaying { cursor, .. } => cursor,
+                _ => 0,
+            },
+            host_gas: self.env.host_gas,
+            output: self.env.output.clone(),
+            logs: self.env.logs.clone(),
+            console: self.env.console.clone(),
+        }
+    }
+
+    /// Rewinds to `checkpoint`. Only sound while replaying, since live host
+    /// calls may have changed the world since.
+    pub(crate) fn restore(&mut self, checkpoint: &Checkpoint) {
+        let checkpoint = checkpoint.clone();
+        self.memory = checkpoint.memory;
+        self.globals = checkpoint.globals;
+        self.table = checkpoint.table;
+        self.dropped = checkpoint.dropped;
+        self.stack = checkpoint.stack;
+        self.frames = checkpoint.frames;
+        self.results = checkpoint.results;
+        self.gas_used = checkpoint.gas_used;
+        self.steps = checkpoint.steps;
+        self.stdout = checkpoint.stdout;
+        self.stderr = checkpoint.stderr;
+        if let Tape::Replaying { cursor, .. } = &mut self.tape {
+            *cursor = checkpoint.cursor;
+        }
+        self.env.host_gas = checkpoint.host_gas;
+        self.env.output = checkpoint.output;
+        self.env.logs = checkpoint.logs;
+        self.env.console = checkpoint.console;
+    }
+
+    pub fn env(&self) -> &HostEnv {
+        &self.env
+    }
+
+    pub fn env_mut(&mut self) -> &mut HostEnv {
+        &mut self.env
+    }
+
+    /// The current frames resolved against the contract's symbols, innermost first.
+    pub fn backtrace(&self) -> Backtrace {
+        let top = self.frames.len().saturating_sub(1);
+        let frames = self
+            .frames
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(depth, frame)| {
+                // Outer frames have already moved past their call instruction.
+                let pc = if depth == top { frame.pc } else { frame.pc.saturating_sub(1) };
+                let offsets = &self.program.functions[frame.func as usize].offsets;
+                let module_offset = offsets.get(pc).or(offsets.last()).copied().unwrap_or(0);
+                BacktraceFrame::resolve(frame.func, module_offset, &self.symbols)
+            })
+            .collect();
+        Backtrace { frames }
+    }
+
+    /// Moves buffered WASI output into the environment's console.
+    pub(crate) fn flush_console(&mut self) {
+        let address = self.env.frame.address;
+        for (stream, buffer) in [
+            (ConsoleStream::Stdout, &mut self.stdout),
+            (ConsoleStream::Stderr, &mut self.stderr),
+        ] {
+            if !buffer.is_empty() {
+                console::push_lines(&mut self.env.console, stream, address, &String::from_utf8_lossy(buffer));
+                buffer.clear();
+            }
+        }
+    }
+
+    pub fn console(&self) -> &[ConsoleLine] {
+        &self.env.console
+    }
+
+    fn pop(&mut self) -> Val {
+        self.stack.pop().expect("validated module underflowed the operand stack")
+    }
+
+    fn pop_u32(&mut self) -> u32 {
+        self.pop().i32() as u32
+    }
+
+    fn frame(&mut self) -> &mut Frame {
+        self.frames.last_mut().expect("no active frame")
+    }
+
+    fn execute(&mut self) -> Result<(), RuntimeError> {
+        let program = self.program.clone();
+        let frame = self.frames.last_mut().expect("no active frame");
+        let (func, pc) = (frame.func as usize, frame.pc);
+        frame.pc += 1;
+
+        // Like wasmer's metering, the limit is only checked where control can leave straight-line code.
+        let instr = &program.functions[func].body[pc];
+        self.gas_used += self.costs[func][pc];
+        if instr.is_metering_point() && self.gas_used() > self.env.context.gas_limit {
+            return Err(RuntimeError::new(OUT_OF_GAS));
+        }
+
+        match instr {
+            Instr::Unreachable => return Err(RuntimeError::new("unreachable")),
+            Instr::Nop => {}
+            &Instr::Block { end, params, results } => self.push_label(params, results, end + 1, false),
+            &Instr::Loop { params, .. } => self.push_label(params, params, pc + 1, true),
+            &Instr::If { else_pc, end, params, results } => {
+                let condition = self.pop().i32();
+                match else_pc {
+                    // Skipping a missing `else` arm skips its `end` too, as a compiled `if` does.
+                    None if condition == 0 => self.frame().pc = end + 1,
+                    Some(else_pc) if condition == 0 => {
+                        self.push_label(params, results, end + 1, false);
+                        self.frame().pc = else_pc + 1;
+                    }
+                    _ => self.push_label(params, results, end + 1, false),
+                }
+            }
+            &Instr::Else { end } => {
+                self.frame().labels.pop();
+                self.frame().pc = end + 1;
+            }
+            Instr::End => {
+                if self.frame().labels.pop().is_none() {
+                    self.return_from_frame();
+                }
+            }
+            &Instr::Br(depth) => self.branch(depth),
+            &Instr::BrIf(depth) => {
+                if self.pop().i32() != 0 {
+                    self.branch(depth);
+                }
+            }
+            Instr::BrTable { targets, default } => {
+                let index = self.pop_u32() as usize;
+                self.branch(targets.get(index).copied().unwrap_or(*default));
+            }
+            Instr::Return => self.return_from_frame(),
+            &Instr::Call(callee) => self.enter(callee)?,
+            &Instr::CallIndirect { type_index } => {
+                let index = self.pop_u32() as usize;
+                let callee = match self.table.get(index) {
+                    None => return Err(RuntimeError::new("undefined element: out of bounds table access")),
+                    Some(None) => return Err(RuntimeError::new("uninitialized element")),
+                    Some(Some(callee)) => *callee,
+                };
+                if program.types[type_index as usize] != *program.signature(callee) {
+                    return Err(RuntimeError::new("indirect call type mismatch"));
+                }
+                self.enter(callee)?;
+            }
+            Instr::Drop => {
+                self.pop();
+            }
+            Instr::Select => {
+                let condition = self.pop().i32();
+                let b = self.pop();
+                let a = self.pop();
+                self.stack.push(if condition != 0 { a } else { b });
+            }
+            &Instr::LocalGet(index) => {
+                let value = self.frame().locals[index as usize];
+                self.stack.push(value);
+            }
+            &Instr::LocalSet(index) => {
+                let value = self.pop();
+                self.frame().locals[index as usize] = value;
+            }
+            &Instr::LocalTee(index) => {
+                let value = *self.stack.last().expect("validated module underflowed the operand stack");
+                self.frame().locals[index as usize] = value;
+            }
+            &Instr::GlobalGet(index) => self.stack.push(self.globals[index as usize]),
+            &Instr::GlobalSet(index) => self.globals[index as usize] = self.pop(),
+            &Instr::Load(op, offset) => {
+                let address = self.pop_u32() as u64 + offset;
+                let range = self.memory_range(address, op.width() as u64)?;
+                let value = op.decode(&self.memory[range]);
+                self.stack.push(value);
+            }
+            &Instr::Store(op, offset) => {
+                let value = self.pop();
+                let address = self.pop_u32() as u64 + offset;
+                let range = self.memory_range(address, op.width() as u64)?;
+                let width = range.len();
+                self.memory[range].copy_from_slice(&op.encode(value)[..width]);
+            }
+            Instr::MemorySize => self.stack.push(Val::I32((self.memory.len() as u64 / PAGE_SIZE) as i32)),
+            Instr::MemoryGrow => {
+                let delta = self.pop_u32() as u64;
+                let pages = self.memory.len() as u64 / PAGE_SIZE;
+                if pages + delta > self.max_pages {
+                    self.stack.push(Val::I32(-1));
+                } else {
+                    self.memory.resize(((pages + delta) * PAGE_SIZE) as usize, 0);
+                    self.stack.push(Val::I32(pages as i32));
+                }
+            }
+            Instr::MemoryCopy => {
+                let len = self.pop_u32() as u64;
+                let src = self.pop_u32() as u64;
+                let src = self.memory_range(src, len)?;
+                let dst = self.pop_u32() as u64;
+                let dst = self.memory_range(dst, len)?;
+                self.memory.copy_within(src, dst.start);
+            }
+            Instr::MemoryFill => {
+                let len = self.pop_u32() as u64;
+                let value = self.pop().i32() as u8;
+                let dst = self.pop_u32() as u64;
+                let dst = self.memory_range(dst, len)?;
+                self.memory[dst].fill(value);
+            }
+            &Instr::MemoryInit(segment) => {
+                let len = self.pop_u32() as usize;
+                let src = self.pop_u32() as usize;
+                let dst = self.pop_u32() as u64;
+                let dst = self.memory_range(dst, len as u64)?;
+                let bytes: &[u8] = if self.dropped[segment as usize] {
+                    &[]
+                } else {
+                    &program.data[segment as usize].bytes
+                };
+                let src = bytes
+                    .get(src..src.saturating_add(len))
+                    .ok_or_else(|| RuntimeError::new("out of bounds memory access"))?;
+                self.memory[dst].copy_from_slice(src);
+            }
+            &Instr::DataDrop(segment) => self.dropped[segment as usize] = true,
+            &Instr::Const(value) => self.stack.push(value),
+            &Instr::Unary(op) => {
+                let a = self.pop();
+                let value = op.eval(a).map_err(RuntimeError::new)?;
+                self.stack.push(value);
+            }
+            &Instr::Binary(op) => {
+                let b = self.pop();
+                let a = self.pop();
+                let value = op.eval(a, b).map_err(RuntimeError::new)?;
+                self.stack.push(value);
+            }
+        }
+        Ok(())
+    }
+
+    fn memory_range(&self, address: u64, len: u64) -> Result<std::ops::Range<usize>, RuntimeError> {
+        match address.checked_add(len) {
+            Some(end) if end <= self.memory.len() as u64 => Ok(address as usize..end as usize),
+            _ => Err(RuntimeError::new("out of bounds memory access")),
+        }
+    }
+
+    fn push_label(&mut self, params: u32, results: u32, target: usize, is_loop: bool) {
+        let height = self.stack.len() - params as usize;
+        self.frame().labels.push(Label {
+            height,
+            arity: results as usize,
+            target,
+            is_loop,
+        });
+    }
+
+    fn branch(&mut self, depth: u32) {
+        let frame = self.frames.last_mut().expect("no active frame");
+        let Some(index) = frame.labels.len().checked_sub(depth as usize + 1) else {
+            // The function body is the outermost label.
+            self.return_from_frame();
+            return;
+        };
+        let label = frame.labels[index];
+        frame.labels.truncate(if label.is_loop { index + 1 } else { index });
+        frame.pc = label.target;
+
+        let values = self.stack.split_off(self.stack.len() - label.arity);
+        self.stack.truncate(label.height);
+        self.stack.extend(values);
+    }
+
+    fn return_from_frame(&mut self) {
+        let frame = self.frames.pop().expect("no active frame");
+        let arity = self.program.signature(frame.func).results.len();
+        let values = self.stack.split_off(self.stack.len() - arity);
+        self.stack.truncate(frame.stack_base);
+        self.stack.extend(values);
+    }
+
+    fn enter(&mut self, func: u32) -> Result<(), RuntimeError> {
+        let program = self.program.clone();
+        let function = &program.functions[func as usize];
+        let signature = &program.types[function.type_index as usize];
+        let args = self.stack.split_off(self.stack.len() - signature.params.len());
+
+        if function.import.is_some() {
+            let results = self.call_import(func, &args)?;
+            if results.len() != signature.results.len() {
+                return Err(RuntimeError::new(format!(
+                    "Host function returned {} value(s), expected {}",
+                    results.len(),
+                    signature.results.len()
+                )));
+            }
+            for (index, (value, &ty)) in results.iter().zip(&signature.results).enumerate() {
+                if !value.has_type(ty) {
+                    return Err(RuntimeError::new(format!(
+                        "Host function returned {} as result {}, expected {}",
+                        value.type_name(),
+                        index,
+                        ty
+                    )));
+                }
+            }
+            self.stack.extend(results);
+            return Ok(());
+        }
+
+        if self.frames.len() >= MAX_FRAMES {
+            return Err(RuntimeError::new("call stack exhausted"));
+        }
+        let mut locals = args;
+        locals.extend(function.locals.iter().map(|&ty| Val::default_for(ty)));
+        self.frames.push(Frame {
+            func,
+            pc: 0,
+            locals,
+            labels: Vec::new(),
+            stack_base: self.stack.len(),
+        });
+        Ok(())
+    }
+
+    fn call_import(&mut self, func: u32, args: &[Val]) -> Result<Vec<Val>, RuntimeError> {
+        match &self.bindings[func as usize] {
+            Some(Binding::Wasi(call)) => {
+                let call = *call;
+                return self.call_wasi(call, args);
+            }
+            Some(Binding::Replay) => return self.replay_import(func, args),
+            _ => {}
+        }
+        if !matches!(self.tape, Tape::Recording(_)) {
+            return self.call_host(func, args, None);
+        }
+
+        let (logs, console, output) = (self.env.logs.len(), self.env.console.len(), self.env.output.clone());
+        let mut writes = Vec::new();
+        let outcome = self.call_host(func, args, Some(&mut writes));
+        let (module, name) = self.program.functions[func as usize].import.clone().expect("imports have names");
+        let call = HostCall {
+            step: self.steps,
+            instruction_gas: self.gas_used,
+            module,
+            name,
+            args: args.to_vec(),
+            results: outcome.as_ref().cloned().unwrap_or_default(),
+            writes,
+            host_gas: self.env.host_gas,
+            output: (self.env.output != output).then(|| self.env.output.clone()),
+            logs: self.env.logs[logs..].to_vec(),
+            console: self.env.console[console..].to_vec(),
+            error: outcome.as_ref().err().map(RuntimeError::message),
+        };
+        let access = if call.error.is_none() { self.storage_access(&call) } else { None };
+        if let Tape::Recording(trace) = &mut self.tape {
+            trace.storage.extend(access);
+            trace.host_calls.push(call);
+        }
+        outcome
+    }
+
+    fn call_host(
+        &mut self,
+        func: u32,
+        args: &[Val],
+        writes: Option<&mut Vec<(u32, Vec<u8>)>>,
+    ) -> Result<Vec<Val>, RuntimeError> {
+        self.env.instruction_gas = self.gas_used;
+        let values: Vec<Value> = args.iter().map(|arg| arg.to_value()).collect();
+        let mut memory = LinearMemory { bytes: &mut self.memory, writes };
+        let import = self.program.functions[func as usize].import.as_ref().expect("imports have names");
+        let import = (import.0.as_str(), import.1.as_str());
+        let results = match &self.bindings[func as usize] {
+            Some(Binding::Hook(handler)) => {
+                host_trace::traced(&mut self.env, &mut memory, import, &values, |env, mem| handler(env, mem, &values))?
+            }
+            Some(Binding::Custom(index)) => {
+                let custom = self.env.custom_imports[*index].clone();
+                host_trace::traced(&mut self.env, &mut memory, import, &values, |env, mem| {
+                    env.host_gas += custom.gas_cost;
+                    (custom.handler)(mem, &values)
+                })?
+            }
+            Some(Binding::Stub(label)) => return Err(RuntimeError::new(format!("Called unresolved import {}", label))),
+            Some(Binding::Zero) => {
+                let signature = self.program.signature(func);
+                return Ok(signature.results.iter().map(|&ty| Val::default_for(ty)).collect());
+            }
+            Some(Binding::Wasi(_) | Binding::Replay) | None => unreachable!("not a host binding"),
+        };
+        results
+            .iter()
+            .map(|value| Val::from_value(value).ok_or_else(|| RuntimeError::new("Host function returned a reference")))
+            .collect()
+    }
+
+    /// The storage slot a successful `storage_*_bytes32` call touched, read back from memory.
+    fn storage_access(&self, call: &HostCall) -> Option<StorageAccess> {
+        let op = match call.name.as_str() {
+            "storage_load_bytes32" => StorageOp::Load,
+            "storage_store_bytes32" | "storage_cache_bytes32" => StorageOp::Store,
+            _ => return None,
+        };
+        let slot = |index: usize| -> Option<[u8; 32]> {
+            let start = call.args.get(index)?.i32() as u32 as usize;
+            self.memory.get(start..start + 32)?.try_into().ok()
+        };
+        Some(StorageAccess { step: call.step, op, key: slot(0)?, value: slot(1)? })
+    }
+
+    /// Answers a host call from the recording instead of running it.
+    fn replay_import(&mut self, func: u32, args: &[Val]) -> Result<Vec<Val>, RuntimeError> {
+        let Tape::Replaying { trace, cursor } = &mut self.tape else {
+            unreachable!("replay bindings only exist while replaying")
+        };
+        let (module, name) = self.program.functions[func as usize].import.as_ref().expect("imports have names");
+        let call = trace
+            .host_calls
+            .get(*cursor)
+            .filter(|call| {
+                &call.module == module
+                    && &call.name == name
+                    && call.args == args
+                    && call.instruction_gas == self.gas_used
+            })
+            .ok_or_else(|| {
+                RuntimeError::new(format!(
+                    "Replay diverged from the recording at step {}: unexpected call to {}::{}",
+                    self.steps, module, name
+                ))
+            })?;
+        *cursor += 1;
+
+        let mut memory = LinearMemory { bytes: &mut self.memory, writes: None };
+        for (ptr, data) in &call.writes {
+            memory.write(*ptr, data)?;
+        }
+        self.env.host_gas = call.host_gas;
+        if let Some(output) = &call.output {
+            self.env.output = output.clone();
+        }
+        self.env.logs.extend(call.logs.iter().cloned());
+        self.env.console.extend(call.console.iter().cloned());
+        match &call.error {
+            Some(message) => Err(RuntimeError::new(message.clone())),
+            None => Ok(call.results.clone()),
+        }
+    }
+
+    /// The subset of `wasi_snapshot_preview1` a contract's std runtime touches.
+    fn call_wasi(&mut self, call: Wasi, args: &[Val]) -> Result<Vec<Val>, RuntimeError> {
+        let arg = |index: usize| args[index].i32() as u32;
+        let mut memory = LinearMemory { bytes: &mut self.memory, writes: None };
+        let errno = match call {
+            Wasi::FdWrite => {
+                let mut written = 0u32;
+                let mut bytes = Vec::new();
+                for i in 0..arg(2) {
+                    let iovec = memory.read(arg(1) + i * 8, 8)?;
+                    let ptr = u32::from_le_bytes(iovec[..4].try_into().unwrap());
+                    let len = u32::from_le_bytes(iovec[4..].try_into().unwrap());
+                    bytes.extend(memory.read(ptr, len as usize)?);
+                    written += len;
+                }
+                match arg(0) {
+                    1 => self.stdout.extend(bytes),
+                    2 => self.stderr.extend(bytes),
+                    _ => return Ok(vec![Val::I32(ERRNO_BADF)]),
+                }
+                memory.write_u32(arg(3), written)?;
+                ERRNO_SUCCESS
+            }
+            Wasi::ProcExit => return Err(RuntimeError::new(format!("Contract exited with code {}", arg(0)))),
+            Wasi::SizesGet => {
+                memory.write_u32(arg(0), 0)?;
+                memory.write_u32(arg(1), 0)?;
+                ERRNO_SUCCESS
+            }
+            Wasi::RandomGet => {
+                // Deterministic, like everything else a contract can observe.
+                memory.write(arg(0), &vec![0u8; arg(1) as usize])?;
+                ERRNO_SUCCESS
+            }
+            Wasi::ClockTimeGet => {
+                let nanos = self.env.context.block_timestamp.saturating_mul(1_000_000_000);
+                memory.write(arg(2), &nanos.to_le_bytes())?;
+                ERRNO_SUCCESS
+            }
+            Wasi::Errno(errno) => errno,
+        };
+        Ok(vec![Val::I32(errno)])
+    }
+}
+
+/// A contract's linear memory as seen by host functions, optionally logging every write.
+struct LinearMemory<'a> {
+    bytes: &'a mut Vec<u8>,
+    writes: Option<&'a mut Vec<(u32, Vec<u8>)>>,
+}
+
+impl GuestMemory for LinearMemory<'_> {
+    fn read(&self, ptr: u32, len: usize) -> Result<Vec<u8>, RuntimeError> {
+        self.bytes
+            .get(ptr as usize..(ptr as usize).saturating_add(len))
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| RuntimeError::new("Memory read out of bounds"))
+    }
+
+    fn write(&mut self, ptr: u32, data: &[u8]) -> Result<(), RuntimeError> {
+        self.bytes
+            .get_mut(ptr as usize..(ptr as usize).saturating_add(data.len()))
+            .ok_or_else(|| RuntimeError::new("Memory write out of bounds"))?
+            .copy_from_slice(data);
+        if let Some(writes) = &mut self.writes {
+            writes.push((ptr, data.to_vec()));
+        }
+        Ok(())
+    }
+}
+
+/// Resolves every imported function the way `host::instantiate` links them for wasmer.
+/// When replaying, everything but WASI is answered from the recording.
+fn link(program: &Program, env: &HostEnv, replaying: bool) -> Result<Vec<Option<Binding>>> {
+    let mut bindings = Vec::with_capacity(program.functions.len());
+    let mut missing = Vec::new();
+    for function in &program.functions {
+        let Some((module, name)) = &function.import else {
+            bindings.push(None);
+            continue;
+        };
+        if replaying {
+            bindings.push(Some(wasi_call(module, name).map(Binding::Wasi).unwrap_or(Binding::Replay)));
+            continue;
+        }
+        let signature = &program.types[function.type_index as usize];
+        let ty = FunctionType::new(
+            signature.params.iter().map(|&ty| wasmer_type(ty)).collect::<Vec<_>>(),
+            signature.results.iter().map(|&ty| wasmer_type(ty)).collect::<Vec<_>>(),
+        );
+        if let Some(expected) = host::host_signature(&env.custom_imports, module, name).filter(|expected| *expected != ty) {
+            missing.push(MissingImport {
+                module: module.clone(),
+                name: name.clone(),
+                kind: "function".to_string(),
+                signature: Some(format_signature(&ty)),
+                expected: Some(format_signature(&expected)),
+            });
+            bindings.push(None);
+            continue;
+        }
+
+        let custom = env
+            .custom_imports
+            .iter()
+            .rposition(|import| &import.module == module && &import.name == name);
+        let binding = match custom {
+            Some(index) => Some(Binding::Custom(index)),
+            None => host::builtin_hook(module, name)
+                .map(Binding::Hook)
+                .or_else(|| wasi_call(module, name).map(Binding::Wasi)),
+        };
+        let binding = binding.or_else(|| match env.stub_policy {
+            StubPolicy::Fail => {
+                missing.push(MissingImport {
+                    module: module.clone(),
+                    name: name.clone(),
+                    kind: "function".to_string(),
+                    signature: Some(format_signature(&ty)),
+                    expected: None,
+                });
+                None
+            }
+            StubPolicy::Trap => Some(Binding::Stub(format!("{}::{}", module, name))),
+            StubPolicy::Zero => Some(Binding::Zero),
+        });
+        bindings.push(binding);
+    }
+    if !missing.is_empty() {
+        return Err(UnresolvedImports(missing).into());
+    }
+    Ok(bindings)
+}
+
+fn wasi_call(module: &str, name: &str) -> Option<Wasi> {
+    if !module.starts_with("wasi") {
+        return None;
+    }
+    Some(match name {
+        "fd_write" => Wasi::FdWrite,
+        "proc_exit" => Wasi::ProcExit,
+        "environ_sizes_get" | "args_sizes_get" => Wasi::SizesGet,
+        "random_get" => Wasi::RandomGet,
+        "clock_time_get" => Wasi::ClockTimeGet,
+        "environ_get" | "args_get" | "sched_yield" | "fd_close" => Wasi::Errno(ERRNO_SUCCESS),
+        "fd_read" | "fd_seek" | "fd_fdstat_get" | "fd_prestat_get" | "fd_prestat_dir_name" => Wasi::Errno(ERRNO_BADF),
+        _ => return None,
+    })
+}
+
+fn wasmer_type(ty: ValType) -> Type {
+    match ty {
+        ValType::I32 => Type::I32,
+        ValType::I64 => Type::I64,
+        ValType::F32 => Type::F32,
+        ValType::F64 => Type::F64,
+        ValType::V128 => Type::V128,
+        ValType::Ref(_) => Type::FuncRef,
+    }
+}
+
+/// Gas for one instruction, keyed in the gas table by its mnemonic, e.g. `local_get`.
+fn instruction_cost(env: &HostEnv, instr: &Instr) -> u64 {
+    let key = instr.name().replace('.', "_");
+    env.gas_table.get(&key).copied().unwrap_or(1)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host::{CallFrame, CustomImport, ExecutionContext};
+    use crate::world::World;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    fn machine_with(wat: &str, custom_imports: Vec<CustomImport>) -> Machine {
+        let wasm = wat::parse_str(wat).unwrap();
+        let program = Arc::new(Program::parse(&wasm).unwrap());
+        let symbols = Arc::new(Symbols::parse(&wasm));
+        let context = ExecutionContext::default();
+        let frame = CallFrame::top_level(&context, Vec::new());
+        let world = Arc::new(Mutex::new(World::new(HashMap::new())));
+        let mut env = HostEnv::new(world, context, HashMap::new(), frame);
+        env.custom_imports = Arc::new(custom_imports);
+        Machine::new(program, symbols, env).unwrap()
+    }
+
+    fn machine(wat: &str) -> Machine {
+        machine_with(wat, Vec::new())
+    }
+
+    fn run(machine: &mut Machine, name: &str, args: &[Val]) -> Result<Vec<Val>, RuntimeError> {
+        let func = machine.program().export(name).unwrap();
+        machine.call(func, args).unwrap();
+        machine.run()
+    }
+
+    fn trap(machine: &mut Machine, name: &str, args: &[Val]) -> String {
+        run(machine, name, args).unwrap_err().message()
+    }
+
+    #[test]
+    fn test_structured_control_flow() {
+        let mut machine = machine(r#"
+            (module
+                (func (export "switch") (param $i i32) (result i32)
+                    (block $default
+                        (block $two
+                            (block $one
+                                (block $zero
+                                    (br_table $zero $one $two $default (local.get $i)))
+                                (return (i32.const 10)))
+                            (return (i32.const 11)))
+                        (return (i32.const 12)))
+                    (i32.const 99))
+                (func (export "sum") (param $n i32) (result i32) (local $acc i32)
+                    (block $done
+                        (loop $next
+                            (br_if $done (i32.eqz (local.get $n)))
+                            (local.set $acc (i32.add (local.get $acc) (local.get $n)))
+                            (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+                            (br $next)))
+                    (local.get $acc))
+                (func (export "sign") (param $x i64) (result i32)
+                    (if (result i32) (i64.lt_s (local.get $x) (i64.const 0))
+                        (then (i32.const -1))
+                        (else
+                            (if (result i32) (i64.eqz (local.get $x))
+                                (then (i32.const 0))
+                                (else (i32.const 1))))))
+                (func (export "clamp") (param $x i32) (result i32)
+                    (if (i32.gt_s (local.get $x) (i32.const 100))
+                        (then (local.set $x (i32.const 100))))
+                    (local.get $x))
+            )
+        "#);
+        for (i, expected) in [(0, 10), (1, 11), (2, 12), (3, 99), (-1, 99)] {
+            assert_eq!(run(&mut machine, "switch", &[Val::I32(i)]).unwrap(), [Val::I32(expected)]);
+        }
+        assert_eq!(run(&mut machine, "sum", &[Val::I32(10)]).unwrap(), [Val::I32(55)]);
+        for (x, expected) in [(-7, -1), (0, 0), (7, 1)] {
+            assert_eq!(run(&mut machine, "sign", &[Val::I64(x)]).unwrap(), [Val::I32(expected)]);
+        }
+        assert_eq!(run(&mut machine, "clamp", &[Val::I32(500)]).unwrap(), [Val::I32(100)]);
+        assert_eq!(run(&mut machine, "clamp", &[Val::I32(5)]).unwrap(), [Val::I32(5)]);
+        assert!(machine.stack().is_empty());
+    }
+
+    #[test]
+    fn test_integer_traps() {
+        let mut machine = machine(r#"
+            (module
+                (func (export "div") (param i32 i32) (result i32) (i32.div_s (local.get 0) (local.get 1)))
+                (func (export "rem") (param i64 i64) (result i64) (i64.rem_u (local.get 0) (local.get 1)))
+                (func (export "trunc") (param f64) (result i32) (i32.trunc_f64_s (local.get 0)))
+                (func (export "trunc_sat") (param f64) (result i32) (i32.trunc_sat_f64_s (local.get 0)))
+            )
+        "#);
+        assert_eq!(run(&mut machine, "div", &[Val::I32(-7), Val::I32(2)]).unwrap(), [Val::I32(-3)]);
+        assert_eq!(trap(&mut machine, "div", &[Val::I32(1), Val::I32(0)]), "integer divide by zero");
+        assert_eq!(trap(&mut machine, "div", &[Val::I32(i32::MIN), Val::I32(-1)]), "integer overflow");
+        assert_eq!(trap(&mut machine, "rem", &[Val::I64(1), Val::I64(0)]), "integer divide by zero");
+        assert_eq!(run(&mut machine, "trunc", &[Val::F64(-2.9)]).unwrap(), [Val::I32(-2)]);
+        assert_eq!(trap(&mut machine, "trunc", &[Val::F64(f64::NAN)]), "invalid conversion to integer");
+        assert_eq!(trap(&mut machine, "trunc", &[Val::F64(1e10)]), "integer overflow");
+        assert_eq!(run(&mut machine, "trunc_sat", &[Val::F64(1e10)]).unwrap(), [Val::I32(i32::MAX)]);
+    }
+
+    #[test]
+    fn test_memory_grow_fill_and_copy_bounds() {
+        let mut machine = machine(r#"
+            (module
+                (memory 1 2)
+                (func (export "grow") (param i32) (result i32) (memory.grow (local.get 0)))
+                (func (export "fill") (param i32 i32 i32) (memory.fill (local.get 0) (local.get 1) (local.get 2)))
+                (func (export "copy") (param i32 i32 i32) (memory.copy (local.get 0) (local.get 1) (local.get 2)))
+            )
+        "#);
+        run(&mut machine, "fill", &[Val::I32(0), Val::I32(7), Val::I32(4)]).unwrap();
+        run(&mut machine, "copy", &[Val::I32(2), Val::I32(0), Val::I32(4)]).unwrap();
+        assert_eq!(&machine.memory()[..7], &[7, 7, 7, 7, 7, 7, 0]);
+
+        let end = PAGE_SIZE as i32;
+        assert_eq!(trap(&mut machine, "fill", &[Val::I32(end - 2), Val::I32(1), Val::I32(3)]), "out of bounds memory access");
+        assert_eq!(trap(&mut machine, "copy", &[Val::I32(0), Val::I32(end - 2), Val::I32(3)]), "out of bounds memory access");
+        assert_eq!(trap(&mut machine, "copy", &[Val::I32(end), Val::I32(0), Val::I32(1)]), "out of bounds memory access");
+        // Zero-length accesses at the very end are in bounds.
+        run(&mut machine, "fill", &[Val::I32(end), Val::I32(1), Val::I32(0)]).unwrap();
+
+        assert_eq!(run(&mut machine, "grow", &[Val::I32(1)]).unwrap(), [Val::I32(1)]);
+        assert_eq!(run(&mut machine, "grow", &[Val::I32(1)]).unwrap(), [Val::I32(-1)]);
+        assert_eq!(machine.memory().len() as u64, 2 * PAGE_SIZE);
+        run(&mut machine, "fill", &[Val::I32(end), Val::I32(9), Val::I32(3)]).unwrap();
+        assert_eq!(machine.memory()[end as usize + 2], 9);
+    }
+
+    #[test]
+    fn test_call_indirect_checks_table_and_type() {
+        let mut machine = machine(r#"
+            (module
+                (type $unary (func (param i32) (result i32)))
+                (table 3 funcref)
+                (elem (i32.const 0) $double $wide)
+                (func $double (param i32) (result i32) (i32.mul (local.get 0) (i32.const 2)))
+                (func $wide (param i64) (result i64) (local.get 0))
+                (func (export "apply") (param $f i32) (param $x i32) (result i32)
+                    (call_indirect (type $unary) (local.get $x) (local.get $f)))
+            )
+        "#);
+        assert_eq!(run(&mut machine, "apply", &[Val::I32(0), Val::I32(21)]).unwrap(), [Val::I32(42)]);
+        assert_eq!(trap(&mut machine, "apply", &[Val::I32(1), Val::I32(21)]), "indirect call type mismatch");
+        assert_eq!(trap(&mut machine, "apply", &[Val::I32(2), Val::I32(21)]), "uninitialized element");
+        assert!(trap(&mut machine, "apply", &[Val::I32(3), Val::I32(21)]).starts_with("undefined element"));
+    }
+
+    #[test]
+    fn test_wasi_subset() {
+        let mut machine = machine(r#"
+            (module
+                (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "random_get" (func $random_get (param i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "clock_time_get" (func $clock (param i32 i64 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "proc_exit" (func $exit (param i32)))
+                (memory 1)
+                (data (i32.const 0) "\10\00\00\00\06\00\00\00")
+                (data (i32.const 16) "hello\n")
+                (data (i32.const 32) "\ff\ff\ff\ff")
+                (func (export "write") (param $fd i32) (result i32)
+                    (call $fd_write (local.get $fd) (i32.const 0) (i32.const 1) (i32.const 8)))
+                (func (export "written") (result i32) (i32.load (i32.const 8)))
+                (func (export "read") (result i32)
+                    (call $fd_read (i32.const 0) (i32.const 0) (i32.const 1) (i32.const 8)))
+                (func (export "random") (result i32)
+                    (drop (call $random_get (i32.const 32) (i32.const 4)))
+                    (i32.load (i32.const 32)))
+                (func (export "now") (result i64)
+                    (drop (call $clock (i32.const 0) (i64.const 0) (i32.const 40)))
+                    (i64.load (i32.const 40)))
+                (func (export "exit") (call $exit (i32.const 3)))
+            )
+        "#);
+        assert_eq!(run(&mut machine, "write", &[Val::I32(1)]).unwrap(), [Val::I32(ERRNO_SUCCESS)]);
+        assert_eq!(run(&mut machine, "written", &[]).unwrap(), [Val::I32(6)]);
+        assert_eq!(machine.console().len(), 1);
+        assert_eq!((machine.console()[0].stream, machine.console()[0].text.as_str()), (ConsoleStream::Stdout, "hello"));
+        assert_eq!(run(&mut machine, "write", &[Val::I32(9)]).unwrap(), [Val::I32(ERRNO_BADF)]);
+        assert_eq!(run(&mut machine, "read", &[]).unwrap(), [Val::I32(ERRNO_BADF)]);
+
+        assert_eq!(run(&mut machine, "random", &[]).unwrap(), [Val::I32(0)]);
+        let nanos = machine.env().context.block_timestamp as i64 * 1_000_000_000;
+        assert_eq!(run(&mut machine, "now", &[]).unwrap(), [Val::I64(nanos)]);
+        assert_eq!(trap(&mut machine, "exit", &[]), "Contract exited with code 3");
+    }
+
+    #[test]
+    fn test_host_results_must_match_the_import_signature() {
+        let import = |results: Vec<Value>| CustomImport {
+            module: "env".to_string(),
+            name: "answer".to_string(),
+            params: Vec::new(),
+            results: vec![Type::I64],
+            gas_cost: 0,
+            handler: Arc::new(move |_, _| Ok(results.clone())),
+        };
+        let wat = r#"
+            (module
+                (import "env" "answer" (func $answer (result i64)))
+                (func (export "ask") (result i64) (call $answer))
+            )
+        "#;
+        let mut machine = machine_with(wat, vec![import(vec![Value::I64(42)])]);
+        assert_eq!(run(&mut machine, "ask", &[]).unwrap(), [Val::I64(42)]);
+
+        let mut machine = machine_with(wat, vec![import(vec![Value::I32(42)])]);
+        assert_eq!(trap(&mut machine, "ask", &[]), "Host function returned i32 as result 0, expected i64");
+        let mut machine = machine_with(wat, vec![import(Vec::new())]);
+        assert_eq!(trap(&mut machine, "ask", &[]), "Host function returned 0 value(s), expected 1");
+    }
+}
@@ -0,0 +1,158 @@
+//! A small wasm interpreter used where execution has to be paused and inspected,
+//! which the compiled wasmer engine cannot do.
+
+mod machine;
+mod ops;
+mod program;
+
+pub(crate) use machine::{Checkpoint, Tape};
+pub use machine::{Frame, Machine, Step};
+pub use ops::{BinOp, LoadOp, StoreOp, UnOp};
+pub use program::{Function, Instr, Program, Signature};
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use wasmer::Value;
+use wasmparser::ValType;
+
+/// A wasm value on the interpreter's operand stack or in a local.
+///
+/// Floats serialize as their bit patterns so NaN payloads and infinities survive JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Val {
+    I32(i32),
+    I64(i64),
+    F32(#[serde(with = "f32_bits")] f32),
+    F64(#[serde(with = "f64_bits")] f64),
+}
+
+mod f32_bits {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(value.to_bits())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+        u32::deserialize(deserializer).map(f32::from_bits)
+    }
+}
+
+mod f64_bits {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(value.to_bits())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        u64::deserialize(deserializer).map(f64::from_bits)
+    }
+}
+
+impl Val {
+    /// The zero value locals of type `ty` start with.
+    pub fn default_for(ty: ValType) -> Self {
+        match ty {
+            ValType::I64 => Val::I64(0),
+            ValType::F32 => Val::F32(0.0),
+            ValType::F64 => Val::F64(0.0),
+            _ => Val::I32(0),
+        }
+    }
+
+    // Modules are validated before they run, so a type mismatch here is an interpreter bug.
+    pub fn i32(self) -> i32 {
+        match self {
+            Val::I32(v) => v,
+            other => panic!("expected i32, found {:?}", other),
+        }
+    }
+
+    pub fn i64(self) -> i64 {
+        match self {
+            Val::I64(v) => v,
+            other => panic!("expected i64, found {:?}", other),
+        }
+    }
+
+    pub fn f32(self) -> f32 {
+        match self {
+            Val::F32(v) => v,
+            other => panic!("expected f32, found {:?}", other),
+        }
+    }
+
+    pub fn f64(self) -> f64 {
+        match self {
+            Val::F64(v) => v,
+            other => panic!("expected f64, found {:?}", other),
+        }
+    }
+
+    pub fn has_type(self, ty: ValType) -> bool {
+        matches!(
+            (self, ty),
+            (Val::I32(_), ValType::I32) | (Val::I64(_), ValType::I64) | (Val::F32(_), ValType::F32) | (Val::F64(_), ValType::F64)
+        )
+    }
+
+    pub fn type_name(self) -> &'static str {
+        match self {
+            Val::I32(_) => "i32",
+            Val::I64(_) => "i64",
+            Val::F32(_) => "f32",
+            Val::F64(_) => "f64",
+        }
+    }
+
+    /// The value widened to an i64, as `ExecutionResult::return_value` reports it.
+    pub fn as_i64(self) -> i64 {
+        match self {
+            Val::I32(v) => v as i64,
+            Val::I64(v) => v,
+            Val::F32(v) => v as i64,
+            Val::F64(v) => v as i64,
+        }
+    }
+
+    /// Converts a CLI-style integer argument to a parameter of type `ty`.
+    pub fn from_i64(value: i64, ty: ValType) -> Self {
+        match ty {
+            ValType::I64 => Val::I64(value),
+            ValType::F32 => Val::F32(value as f32),
+            ValType::F64 => Val::F64(value as f64),
+            _ => Val::I32(value as i32),
+        }
+    }
+
+    pub(crate) fn to_value(self) -> Value {
+        match self {
+            Val::I32(v) => Value::I32(v),
+            Val::I64(v) => Value::I64(v),
+            Val::F32(v) => Value::F32(v),
+            Val::F64(v) => Value::F64(v),
+        }
+    }
+
+    pub(crate) fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::I32(v) => Some(Val::I32(*v)),
+            Value::I64(v) => Some(Val::I64(*v)),
+            Value::F32(v) => Some(Val::F32(*v)),
+            Value::F64(v) => Some(Val::F64(*v)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Val {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Val::I32(v) => write!(f, "{}", v),
+            Val::I64(v) => write!(f, "{}", v),
+            Val::F32(v) => write!(f, "{}", v),
+            Val::F64(v) => write!(f, "{}", v),
+        }
+    }
+}
@@ -0,0 +1,1202 @@
+use super::Val;
+use crate::profile::OpClass;
+use wasmparser::Operator;
+
+pub(crate) const DIVIDE_BY_ZERO: &str = "integer divide by zero";
+pub(crate) const INTEGER_OVERFLOW: &str = "integer overflow";
+pub(crate) const INVALID_CONVERSION: &str = "invalid conversion to integer";
+
+/// Memory reads, with the width and extension of the loaded value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LoadOp {
+    I32Load,
+    I64Load,
+    F32Load,
+    F64Load,
+    I32Load8S,
+    I32Load8U,
+    I32Load16S,
+    I32Load16U,
+    I64Load8S,
+    I64Load8U,
+    I64Load16S,
+    I64Load16U,
+    I64Load32S,
+    I64Load32U,
+}
+
+impl LoadOp {
+    pub fn name(self) -> &'static str {
+        match self {
+            LoadOp::I32Load => "i32.load",
+            LoadOp::I64Load => "i64.load",
+            LoadOp::F32Load => "f32.load",
+            LoadOp::F64Load => "f64.load",
+            LoadOp::I32Load8S => "i32.load8_s",
+            LoadOp::I32Load8U => "i32.load8_u",
+            LoadOp::I32Load16S => "i32.load16_s",
+            LoadOp::I32Load16U => "i32.load16_u",
+            LoadOp::I64Load8S => "i64.load8_s",
+            LoadOp::I64Load8U => "i64.load8_u",
+            LoadOp::I64Load16S => "i64.load16_s",
+            LoadOp::I64Load16U => "i64.load16_u",
+            LoadOp::I64Load32S => "i64.load32_s",
+            LoadOp::I64Load32U => "i64.load32_u",
+        }
+    }
+
+    pub fn width(self) -> usize {
+        match self {
+            LoadOp::I32Load8S | LoadOp::I32Load8U | LoadOp::I64Load8S | LoadOp::I64Load8U => 1,
+            LoadOp::I32Load16S | LoadOp::I32Load16U | LoadOp::I64Load16S | LoadOp::I64Load16U => 2,
+            LoadOp::I32Load | LoadOp::F32Load | LoadOp::I64Load32S | LoadOp::I64Load32U => 4,
+            LoadOp::I64Load | LoadOp::F64Load => 8,
+        }
+    }
+
+    /// Builds the loaded value from `width()` little-endian bytes.
+    pub(crate) fn decode(self, bytes: &[u8]) -> Val {
+        let mut buf = [0u8; 8];
+        buf[..bytes.len()].copy_from_slice(bytes);
+        let raw = u64::from_le_bytes(buf);
+        match self {
+            LoadOp::I32Load => Val::I32(raw as u32 as i32),
+            LoadOp::I64Load => Val::I64(raw as i64),
+            LoadOp::F32Load => Val::F32(f32::from_bits(raw as u32)),
+            LoadOp::F64Load => Val::F64(f64::from_bits(raw)),
+            LoadOp::I32Load8S => Val::I32(raw as u8 as i8 as i32),
+            LoadOp::I32Load8U => Val::I32(raw as u8 as i32),
+            LoadOp::I32Load16S => Val::I32(raw as u16 as i16 as i32),
+            LoadOp::I32Load16U => Val::I32(raw as u16 as i32),
+            LoadOp::I64Load8S => Val::I64(raw as u8 as i8 as i64),
+            LoadOp::I64Load8U => Val::I64(raw as u8 as i64),
+            LoadOp::I64Load16S => Val::I64(raw as u16 as i16 as i64),
+            LoadOp::I64Load16U => Val::I64(raw as u16 as i64),
+            LoadOp::I64Load32S => Val::I64(raw as u32 as i32 as i64),
+            LoadOp::I64Load32U => Val::I64(raw as u32 as i64),
+        }
+    }
+}
+
+/// Memory writes, with the number of low bytes stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StoreOp {
+    I32Store,
+    I64Store,
+    F32Store,
+    F64Store,
+    I32Store8,
+    I32Store16,
+    I64Store8,
+    I64Store16,
+    I64Store32,
+}
+
+impl StoreOp {
+    pub fn name(self) -> &'static str {
+        match self {
+            StoreOp::I32Store => "i32.store",
+            StoreOp::I64Store => "i64.store",
+            StoreOp::F32Store => "f32.store",
+            StoreOp::F64Store => "f64.store",
+            StoreOp::I32Store8 => "i32.store8",
+            StoreOp::I32Store16 => "i32.store16",
+            StoreOp::I64Store8 => "i64.store8",
+            StoreOp::I64Store16 => "i64.store16",
+            StoreOp::I64Store32 => "i64.store32",
+        }
+    }
+
+    pub fn width(self) -> usize {
+        match self {
+            StoreOp::I32Store8 | StoreOp::I64Store8 => 1,
+            StoreOp::I32Store16 | StoreOp::I64Store16 => 2,
+            StoreOp::I32Store | StoreOp::F32Store | StoreOp::I64Store32 => 4,
+            StoreOp::I64Store | StoreOp::F64Store => 8,
+        }
+    }
+
+    /// The little-endian bytes written to memory; only the first `width()` are used.
+    pub(crate) fn encode(self, value: Val) -> [u8; 8] {
+        let raw = match value {
+            Val::I32(v) => v as u32 as u64,
+            Val::I64(v) => v as u64,
+            Val::F32(v) => v.to_bits() as u64,
+            Val::F64(v) => v.to_bits(),
+        };
+        raw.to_le_bytes()
+    }
+}
+
+/// Numeric instructions that pop one operand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnOp {
+    I32Eqz,
+    I32Clz,
+    I32Ctz,
+    I32Popcnt,
+    I64Eqz,
+    I64Clz,
+    I64Ctz,
+    I64Popcnt,
+    F32Abs,
+    F32Neg,
+    F32Ceil,
+    F32Floor,
+    F32Trunc,
+    F32Nearest,
+    F32Sqrt,
+    F64Abs,
+    F64Neg,
+    F64Ceil,
+    F64Floor,
+    F64Trunc,
+    F64Nearest,
+    F64Sqrt,
+    I32WrapI64,
+    I32TruncF32S,
+    I32TruncF32U,
+    I32TruncF64S,
+    I32TruncF64U,
+    I64ExtendI32S,
+    I64ExtendI32U,
+    I64TruncF32S,
+    I64TruncF32U,
+    I64TruncF64S,
+    I64TruncF64U,
+    F32ConvertI32S,
+    F32ConvertI32U,
+    F32ConvertI64S,
+    F32ConvertI64U,
+    F32DemoteF64,
+    F64ConvertI32S,
+    F64ConvertI32U,
+    F64ConvertI64S,
+    F64ConvertI64U,
+    F64PromoteF32,
+    I32ReinterpretF32,
+    I64ReinterpretF64,
+    F32ReinterpretI32,
+    F64ReinterpretI64,
+    I32Extend8S,
+    I32Extend16S,
+    I64Extend8S,
+    I64Extend16S,
+    I64Extend32S,
+    I32TruncSatF32S,
+    I32TruncSatF32U,
+    I32TruncSatF64S,
+    I32TruncSatF64U,
+    I64TruncSatF32S,
+    I64TruncSatF32U,
+    I64TruncSatF64S,
+    I64TruncSatF64U,
+}
+
+impl UnOp {
+    pub fn class(self) -> OpClass {
+        numeric_class(self.name())
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            UnOp::I32Eqz => "i32.eqz",
+            UnOp::I32Clz => "i32.clz",
+            UnOp::I32Ctz => "i32.ctz",
+            UnOp::I32Popcnt => "i32.popcnt",
+            UnOp::I64Eqz => "i64.eqz",
+            UnOp::I64Clz => "i64.clz",
+            UnOp::I64Ctz => "i64.ctz",
+            UnOp::I64Popcnt => "i64.popcnt",
+            UnOp::F32Abs => "f32.abs",
+            UnOp::F32Neg => "f32.neg",
+            UnOp::F32Ceil => "f32.ceil",
+            UnOp::F32Floor => "f32.floor",
+            UnOp::F32Trunc => "f32.trunc",
+            UnOp::F32Nearest => "f32.nearest",
+            UnOp::F32Sqrt => "f32.sqrt",
+            UnOp::F64Abs => "f64.abs",
+            UnOp::F64Neg => "f64.neg",
+            UnOp::F64Ceil => "f64.ceil",
+            UnOp::F64Floor => "f64.floor",
+            UnOp::F64Trunc => "f64.trunc",
+            UnOp::F64Nearest => "f64.nearest",
+            UnOp::F64Sqrt => "f64.sqrt",
+            UnOp::I32WrapI64 => "i32.wrap_i64",
+            UnOp::I32TruncF32S => "i32.trunc_f32_s",
+            UnOp::I32TruncF32U => "i32.trunc_f32_u",
+            UnOp::I32TruncF64S => "i32.trunc_f64_s",
+            UnOp::I32TruncF64U => "i32.trunc_f64_u",
+            UnOp::I64ExtendI32S => "i64.extend_i32_s",
+            UnOp::I64ExtendI32U => "i64.extend_i32_u",
+            UnOp::I64TruncF32S => "i64.trunc_f32_s",
+            UnOp::I64TruncF32U => "i64.trunc_f32_u",
+            UnOp::I64TruncF64S => "i64.trunc_f64_s",
+            UnOp::I64TruncF64U => "i64.trunc_f64_u",
+            UnOp::F32ConvertI32S => "f32.convert_i32_s",
+            UnOp::F32ConvertI32U => "f32.convert_i32_u",
+            UnOp::F32ConvertI64S => "f32.convert_i64_s",
+            UnOp::F32ConvertI64U => "f32.convert_i64_u",
+            UnOp::F32DemoteF64 => "f32.demote_f64",
+            UnOp::F64ConvertI32S => "f64.convert_i32_s",
+            UnOp::F64ConvertI32U => "f64.convert_i32_u",
+            UnOp::F64ConvertI64S => "f64.convert_i64_s",
+            UnOp::F64ConvertI64U => "f64.convert_i64_u",
+            UnOp::F64PromoteF32 => "f64.promote_f32",
+            UnOp::I32ReinterpretF32 => "i32.reinterpret_f32",
+            UnOp::I64ReinterpretF64 => "i64.reinterpret_f64",
+            UnOp::F32ReinterpretI32 => "f32.reinterpret_i32",
+            UnOp::F64ReinterpretI64 => "f64.reinterpret_i64",
+            UnOp::I32Extend8S => "i32.extend8_s",
+            UnOp::I32Extend16S => "i32.extend16_s",
+            UnOp::I64Extend8S => "i64.extend8_s",
+            UnOp::I64Extend16S => "i64.extend16_s",
+            UnOp::I64Extend32S => "i64.extend32_s",
+            UnOp::I32TruncSatF32S => "i32.trunc_sat_f32_s",
+            UnOp::I32TruncSatF32U => "i32.trunc_sat_f32_u",
+            UnOp::I32TruncSatF64S => "i32.trunc_sat_f64_s",
+            UnOp::I32TruncSatF64U => "i32.trunc_sat_f64_u",
+            UnOp::I64TruncSatF32S => "i64.trunc_sat_f32_s",
+            UnOp::I64TruncSatF32U => "i64.trunc_sat_f32_u",
+            UnOp::I64TruncSatF64S => "i64.trunc_sat_f64_s",
+            UnOp::I64TruncSatF64U => "i64.trunc_sat_f64_u",
+        }
+    }
+
+    pub(crate) fn from_operator(op: &Operator<'_>) -> Option<Self> {
+        Some(match op {
+            Operator::I32Eqz => UnOp::I32Eqz,
+            Operator::I32Clz => UnOp::I32Clz,
+            Operator::I32Ctz => UnOp::I32Ctz,
+            Operator::I32Popcnt => UnOp::I32Popcnt,
+            Operator::I64Eqz => UnOp::I64Eqz,
+            Operator::I64Clz => UnOp::I64Clz,
+            Operator::I64Ctz => UnOp::I64Ctz,
+            Operator::I64Popcnt => UnOp::I64Popcnt,
+            Operator::F32Abs => UnOp::F32Abs,
+            Operator::F32Neg => UnOp::F32Neg,
+            Operator::F32Ceil => UnOp::F32Ceil,
+            Operator::F32Floor => UnOp::F32Floor,
+            Operator::F32Trunc => UnOp::F32Trunc,
+            Operator::F32Nearest => UnOp::F32Nearest,
+            Operator::F32Sqrt => UnOp::F32Sqrt,
+            Operator::F64Abs => UnOp::F64Abs,
+            Operator::F64Neg => UnOp::F64Neg,
+            Operator::F64Ceil => UnOp::F64Ceil,
+            Operator::F64Floor => UnOp::F64Floor,
+            Operator::F64Trunc => UnOp::F64Trunc,
+            Operator::F64Nearest => UnOp::F64Nearest,
+            Operator::F64Sqrt => UnOp::F64Sqrt,
+            Operator::I32WrapI64 => UnOp::I32WrapI64,
+            Operator::I32TruncF32S => UnOp::I32TruncF32S,
+            Operator::I32TruncF32U => UnOp::I32TruncF32U,
+            Operator::I32TruncF64S => UnOp::I32TruncF64S,
+            Operator::I32TruncF64U => UnOp::I32TruncF64U,
+            Operator::I64ExtendI32S => UnOp::I64ExtendI32S,
+            Operator::I64ExtendI32U => UnOp::I64ExtendI32U,
+            Operator::I64TruncF32S => UnOp::I64TruncF32S,
+            Operator::I64TruncF32U => UnOp::I64TruncF32U,
+            Operator::I64TruncF64S => UnOp::I64TruncF64S,
+            Operator::I64TruncF64U => UnOp::I64TruncF64U,
+            Operator::F32ConvertI32S => UnOp::F32ConvertI32S,
+            Operator::F32ConvertI32U => UnOp::F32ConvertI32U,
+            Operator::F32ConvertI64S => UnOp::F32ConvertI64S,
+            Operator::F32ConvertI64U => UnOp::F32ConvertI64U,
+            Operator::F32DemoteF64 => UnOp::F32DemoteF64,
+            Operator::F64ConvertI32S => UnOp::F64ConvertI32S,
+            Operator::F64ConvertI32U => UnOp::F64ConvertI32U,
+            Operator::F64ConvertI64S => UnOp::F64ConvertI64S,
+            Operator::F64ConvertI64U => UnOp::F64ConvertI64U,
+            Operator::F64PromoteF32 => UnOp::F64PromoteF32,
+            Operator::I32ReinterpretF32 => UnOp::I32ReinterpretF32,
+            Operator::I64ReinterpretF64 => UnOp::I64ReinterpretF64,
+            Operator::F32ReinterpretI32 => UnOp::F32ReinterpretI32,
+            Operator::F64ReinterpretI64 => UnOp::F64ReinterpretI64,
+            Operator::I32Extend8S => UnOp::I32Extend8S,
+            Operator::I32Extend16S => UnOp::I32Extend16S,
+            Operator::I64Extend8S => UnOp::I64Extend8S,
+            Operator::I64Extend16S => UnOp::I64Extend16S,
+            Operator::I64Extend32S => UnOp::I64Extend32S,
+            Operator::I32TruncSatF32S => UnOp::I32TruncSatF32S,
+            Operator::I32TruncSatF32U => UnOp::I32TruncSatF32U,
+            Operator::I32TruncSatF64S => UnOp::I32TruncSatF64S,
+            Operator::I32TruncSatF64U => UnOp::I32TruncSatF64U,
+            Operator::I64TruncSatF32S => UnOp::I64TruncSatF32S,
+            Operator::I64TruncSatF32U => UnOp::I64TruncSatF32U,
+            Operator::I64TruncSatF64S => UnOp::I64TruncSatF64S,
+            Operator::I64TruncSatF64U => UnOp::I64TruncSatF64U,
+            _ => return None,
+        })
+    }
+
+    pub(crate) fn eval(self, a: Val) -> Result<Val, &'static str> {
+        Ok(match self {
+            UnOp::I32Eqz => {
+                let a = a.i32();
+                Val::I32((a == 0) as i32)
+            }
+            UnOp::I32Clz => {
+                let a = a.i32();
+                Val::I32(a.leading_zeros() as i32)
+            }
+            UnOp::I32Ctz => {
+                let a = a.i32();
+                Val::I32(a.trailing_zeros() as i32)
+            }
+            UnOp::I32Popcnt => {
+                let a = a.i32();
+                Val::I32(a.count_ones() as i32)
+            }
+            UnOp::I64Eqz => {
+                let a = a.i64();
+                Val::I32((a == 0) as i32)
+            }
+            UnOp::I64Clz => {
+                let a = a.i64();
+                Val::I64(a.leading_zeros() as i64)
+            }
+            UnOp::I64Ctz => {
+                let a = a.i64();
+                Val::I64(a.trailing_zeros() as i64)
+            }
+            UnOp::I64Popcnt => {
+                let a = a.i64();
+                Val::I64(a.count_ones() as i64)
+            }
+            UnOp::F32Abs => {
+                let a = a.f32();
+                Val::F32(a.abs())
+            }
+            UnOp::F32Neg => {
+                let a = a.f32();
+                Val::F32(-a)
+            }
+            UnOp::F32Ceil => {
+                let a = a.f32();
+                Val::F32(a.ceil())
+            }
+            UnOp::F32Floor => {
+                let a = a.f32();
+                Val::F32(a.floor())
+            }
+            UnOp::F32Trunc => {
+                let a = a.f32();
+                Val::F32(a.trunc())
+            }
+            UnOp::F32Nearest => {
+                let a = a.f32();
+                Val::F32(a.round_ties_even())
+            }
+            UnOp::F32Sqrt => {
+                let a = a.f32();
+                Val::F32(a.sqrt())
+            }
+            UnOp::F64Abs => {
+                let a = a.f64();
+                Val::F64(a.abs())
+            }
+            UnOp::F64Neg => {
+                let a = a.f64();
+                Val::F64(-a)
+            }
+            UnOp::F64Ceil => {
+                let a = a.f64();
+                Val::F64(a.ceil())
+            }
+            UnOp::F64Floor => {
+                let a = a.f64();
+                Val::F64(a.floor())
+            }
+            UnOp::F64Trunc => {
+                let a = a.f64();
+                Val::F64(a.trunc())
+            }
+            UnOp::F64Nearest => {
+                let a = a.f64();
+                Val::F64(a.round_ties_even())
+            }
+            UnOp::F64Sqrt => {
+                let a = a.f64();
+                Val::F64(a.sqrt())
+            }
+            UnOp::I32WrapI64 => {
+                let a = a.i64();
+                Val::I32(a as i32)
+            }
+            UnOp::I32TruncF32S => {
+                let a = a.f32();
+                Val::I32(trunc(a as f64, -2147483649.0, 2147483648.0)? as i32)
+            }
+            UnOp::I32TruncF32U => {
+                let a = a.f32();
+                Val::I32(trunc(a as f64, -1.0, 4294967296.0)? as u32 as i32)
+            }
+            UnOp::I32TruncF64S => {
+                let a = a.f64();
+                Val::I32(trunc(a, -2147483649.0, 2147483648.0)? as i32)
+            }
+            UnOp::I32TruncF64U => {
+                let a = a.f64();
+                Val::I32(trunc(a, -1.0, 4294967296.0)? as u32 as i32)
+            }
+            UnOp::I64ExtendI32S => {
+                let a = a.i32();
+                Val::I64(a as i64)
+            }
+            UnOp::I64ExtendI32U => {
+                let a = a.i32();
+                Val::I64(a as u32 as i64)
+            }
+            UnOp::I64TruncF32S => {
+                let a = a.f32();
+                Val::I64(trunc(a as f64, -9223372036854777856.0, 9223372036854775808.0)? as i64)
+            }
+            UnOp::I64TruncF32U => {
+                let a = a.f32();
+                Val::I64(trunc(a as f64, -1.0, 18446744073709551616.0)? as u64 as i64)
+            }
+            UnOp::I64TruncF64S => {
+                let a = a.f64();
+                Val::I64(trunc(a, -9223372036854777856.0, 9223372036854775808.0)? as i64)
+            }
+            UnOp::I64TruncF64U => {
+                let a = a.f64();
+                Val::I64(trunc(a, -1.0, 18446744073709551616.0)? as u64 as i64)
+            }
+            UnOp::F32ConvertI32S => {
+                let a = a.i32();
+                Val::F32(a as f32)
+            }
+            UnOp::F32ConvertI32U => {
+                let a = a.i32();
+                Val::F32(a as u32 as f32)
+            }
+            UnOp::F32ConvertI64S => {
+                let a = a.i64();
+                Val::F32(a as f32)
+            }
+            UnOp::F32ConvertI64U => {
+                let a = a.i64();
+                Val::F32(a as u64 as f32)
+            }
+            UnOp::F32DemoteF64 => {
+                let a = a.f64();
+                Val::F32(a as f32)
+            }
+            UnOp::F64ConvertI32S => {
+                let a = a.i32();
+                Val::F64(a as f64)
+            }
+            UnOp::F64ConvertI32U => {
+                let a = a.i32();
+                Val::F64(a as u32 as f64)
+            }
+            UnOp::F64ConvertI64S => {
+                let a = a.i64();
+                Val::F64(a as f64)
+            }
+            UnOp::F64ConvertI64U => {
+                let a = a.i64();
+                Val::F64(a as u64 as f64)
+            }
+            UnOp::F64PromoteF32 => {
+                let a = a.f32();
+                Val::F64(a as f64)
+            }
+            UnOp::I32ReinterpretF32 => {
+                let a = a.f32();
+                Val::I32(a.to_bits() as i32)
+            }
+            UnOp::I64ReinterpretF64 => {
+                let a = a.f64();
+                Val::I64(a.to_bits() as i64)
+            }
+            UnOp::F32ReinterpretI32 => {
+                let a = a.i32();
+                Val::F32(f32::from_bits(a as u32))
+            }
+            UnOp::F64ReinterpretI64 => {
+                let a = a.i64();
+                Val::F64(f64::from_bits(a as u64))
+            }
+            UnOp::I32Extend8S => {
+                let a = a.i32();
+                Val::I32(a as i8 as i32)
+            }
+            UnOp::I32Extend16S => {
+                let a = a.i32();
+                Val::I32(a as i16 as i32)
+            }
+            UnOp::I64Extend8S => {
+                let a = a.i64();
+                Val::I64(a as i8 as i64)
+            }
+            UnOp::I64Extend16S => {
+                let a = a.i64();
+                Val::I64(a as i16 as i64)
+            }
+            UnOp::I64Extend32S => {
+                let a = a.i64();
+                Val::I64(a as i32 as i64)
+            }
+            UnOp::I32TruncSatF32S => {
+                let a = a.f32();
+                Val::I32(a as i32)
+            }
+            UnOp::I32TruncSatF32U => {
+                let a = a.f32();
+                Val::I32(a as u32 as i32)
+            }
+            UnOp::I32TruncSatF64S => {
+                let a = a.f64();
+                Val::I32(a as i32)
+            }
+            UnOp::I32TruncSatF64U => {
+                let a = a.f64();
+                Val::I32(a as u32 as i32)
+            }
+            UnOp::I64TruncSatF32S => {
+                let a = a.f32();
+                Val::I64(a as i64)
+            }
+            UnOp::I64TruncSatF32U => {
+                let a = a.f32();
+                Val::I64(a as u64 as i64)
+            }
+            UnOp::I64TruncSatF64S => {
+                let a = a.f64();
+                Val::I64(a as i64)
+            }
+            UnOp::I64TruncSatF64U => {
+                let a = a.f64();
+                Val::I64(a as u64 as i64)
+            }
+        })
+    }
+}
+
+/// Numeric instructions that pop two operands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinOp {
+    I32Eq,
+    I32Ne,
+    I32LtS,
+    I32LtU,
+    I32GtS,
+    I32GtU,
+    I32LeS,
+    I32LeU,
+    I32GeS,
+    I32GeU,
+    I32Add,
+    I32Sub,
+    I32Mul,
+    I32DivS,
+    I32DivU,
+    I32RemS,
+    I32RemU,
+    I32And,
+    I32Or,
+    I32Xor,
+    I32Shl,
+    I32ShrS,
+    I32ShrU,
+    I32Rotl,
+    I32Rotr,
+    I64Eq,
+    I64Ne,
+    I64LtS,
+    I64LtU,
+    I64GtS,
+    I64GtU,
+    I64LeS,
+    I64LeU,
+    I64GeS,
+    I64GeU,
+    I64Add,
+    I64Sub,
+    I64Mul,
+    I64DivS,
+    I64DivU,
+    I64RemS,
+    I64RemU,
+    I64And,
+    I64Or,
+    I64Xor,
+    I64Shl,
+    I64ShrS,
+    I64ShrU,
+    I64Rotl,
+    I64Rotr,
+    F32Eq,
+    F32Ne,
+    F32Lt,
+    F32Gt,
+    F32Le,
+    F32Ge,
+    F32Add,
+    F32Sub,
+    F32Mul,
+    F32Div,
+    F32Min,
+    F32Max,
+    F32Copysign,
+    F64Eq,
+    F64Ne,
+    F64Lt,
+    F64Gt,
+    F64Le,
+    F64Ge,
+    F64Add,
+    F64Sub,
+    F64Mul,
+    F64Div,
+    F64Min,
+    F64Max,
+    F64Copysign,
+}
+
+impl BinOp {
+    pub fn class(self) -> OpClass {
+        numeric_class(self.name())
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BinOp::I32Eq => "i32.eq",
+            BinOp::I32Ne => "i32.ne",
+            BinOp::I32LtS => "i32.lt_s",
+            BinOp::I32LtU => "i32.lt_u",
+            BinOp::I32GtS => "i32.gt_s",
+            BinOp::I32GtU => "i32.gt_u",
+            BinOp::I32LeS => "i32.le_s",
+            BinOp::I32LeU => "i32.le_u",
+            BinOp::I32GeS => "i32.ge_s",
+            BinOp::I32GeU => "i32.ge_u",
+            BinOp::I32Add => "i32.add",
+            BinOp::I32Sub => "i32.sub",
+            BinOp::I32Mul => "i32.mul",
+            BinOp::I32DivS => "i32.div_s",
+            BinOp::I32DivU => "i32.div_u",
+            BinOp::I32RemS => "i32.rem_s",
+            BinOp::I32RemU => "i32.rem_u",
+            BinOp::I32And => "i32.and",
+            BinOp::I32Or => "i32.or",
+            BinOp::I32Xor => "i32.xor",
+            BinOp::I32Shl => "i32.shl",
+            BinOp::I32ShrS => "i32.shr_s",
+            BinOp::I32ShrU => "i32.shr_u",
+            BinOp::I32Rotl => "i32.rotl",
+            BinOp::I32Rotr => "i32.rotr",
+            BinOp::I64Eq => "i64.eq",
+            BinOp::I64Ne => "i64.ne",
+            BinOp::I64LtS => "i64.lt_s",
+            BinOp::I64LtU => "i64.lt_u",
+            BinOp::I64GtS => "i64.gt_s",
+            BinOp::I64GtU => "i64.gt_u",
+            BinOp::I64LeS => "i64.le_s",
+            BinOp::I64LeU => "i64.le_u",
+            BinOp::I64GeS => "i64.ge_s",
+            BinOp::I64GeU => "i64.ge_u",
+            BinOp::I64Add => "i64.add",
+            BinOp::I64Sub => "i64.sub",
+            BinOp::I64Mul => "i64.mul",
+            BinOp::I64DivS => "i64.div_s",
+            BinOp::I64DivU => "i64.div_u",
+            BinOp::I64RemS => "i64.rem_s",
+            BinOp::I64RemU => "i64.rem_u",
+            BinOp::I64And => "i64.and",
+            BinOp::I64Or => "i64.or",
+            BinOp::I64Xor => "i64.xor",
+            BinOp::I64Shl => "i64.shl",
+            BinOp::I64ShrS => "i64.shr_s",
+            BinOp::I64ShrU => "i64.shr_u",
+            BinOp::I64Rotl => "i64.rotl",
+            BinOp::I64Rotr => "i64.rotr",
+            BinOp::F32Eq => "f32.eq",
+            BinOp::F32Ne => "f32.ne",
+            BinOp::F32Lt => "f32.lt",
+            BinOp::F32Gt => "f32.gt",
+            BinOp::F32Le => "f32.le",
+            BinOp::F32Ge => "f32.ge",
+            BinOp::F32Add => "f32.add",
+            BinOp::F32Sub => "f32.sub",
+            BinOp::F32Mul => "f32.mul",
+            BinOp::F32Div => "f32.div",
+            BinOp::F32Min => "f32.min",
+            BinOp::F32Max => "f32.max",
+            BinOp::F32Copysign => "f32.copysign",
+            BinOp::F64Eq => "f64.eq",
+            BinOp::F64Ne => "f64.ne",
+            BinOp::F64Lt => "f64.lt",
+            BinOp::F64Gt => "f64.gt",
+            BinOp::F64Le => "f64.le",
+            BinOp::F64Ge => "f64.ge",
+            BinOp::F64Add => "f64.add",
+            BinOp::F64Sub => "f64.sub",
+            BinOp::F64Mul => "f64.mul",
+            BinOp::F64Div => "f64.div",
+            BinOp::F64Min => "f64.min",
+            BinOp::F64Max => "f64.max",
+            BinOp::F64Copysign => "f64.copysign",
+        }
+    }
+
+    pub(crate) fn from_operator(op: &Operator<'_>) -> Option<Self> {
+        Some(match op {
+            Operator::I32Eq => BinOp::I32Eq,
+            Operator::I32Ne => BinOp::I32Ne,
+            Operator::I32LtS => BinOp::I32LtS,
+            Operator::I32LtU => BinOp::I32LtU,
+            Operator::I32GtS => BinOp::I32GtS,
+            Operator::I32GtU => BinOp::I32GtU,
+            Operator::I32LeS => BinOp::I32LeS,
+            Operator::I32LeU => BinOp::I32LeU,
+            Operator::I32GeS => BinOp::I32GeS,
+            Operator::I32GeU => BinOp::I32GeU,
+            Operator::I32Add => BinOp::I32Add,
+            Operator::I32Sub => BinOp::I32Sub,
+            Operator::I32Mul => BinOp::I32Mul,
+            Operator::I32DivS => BinOp::I32DivS,
+            Operator::I32DivU => BinOp::I32DivU,
+            Operator::I32RemS => BinOp::I32RemS,
+            Operator::I32RemU => BinOp::I32RemU,
+            Operator::I32And => BinOp::I32And,
+            Operator::I32Or => BinOp::I32Or,
+            Operator::I32Xor => BinOp::I32Xor,
+            Operator::I32Shl => BinOp::I32Shl,
+            Operator::I32ShrS => BinOp::I32ShrS,
+            Operator::I32ShrU => BinOp::I32ShrU,
+            Operator::I32Rotl => BinOp::I32Rotl,
+            Operator::I32Rotr => BinOp::I32Rotr,
+            Operator::I64Eq => BinOp::I64Eq,
+            Operator::I64Ne => BinOp::I64Ne,
+            Operator::I64LtS => BinOp::I64LtS,
+            Operator::I64LtU => BinOp::I64LtU,
+            Operator::I64GtS => BinOp::I64GtS,
+            Operator::I64GtU => BinOp::I64GtU,
+            Operator::I64LeS => BinOp::I64LeS,
+            Operator::I64LeU => BinOp::I64LeU,
+            Operator::I64GeS => BinOp::I64GeS,
+            Operator::I64GeU => BinOp::I64GeU,
+            Operator::I64Add => BinOp::I64Add,
+            Operator::I64Sub => BinOp::I64Sub,
+            Operator::I64Mul => BinOp::I64Mul,
+            Operator::I64DivS => BinOp::I64DivS,
+            Operator::I64DivU => BinOp::I64DivU,
+            Operator::I64RemS => BinOp::I64RemS,
+            Operator::I64RemU => BinOp::I64RemU,
+            Operator::I64And => BinOp::I64And,
+            Operator::I64Or => BinOp::I64Or,
+            Operator::I64Xor => BinOp::I64Xor,
+            Operator::I64Shl => BinOp::I64Shl,
+            Operator::I64ShrS => BinOp::I64ShrS,
+            Operator::I64ShrU => BinOp::I64ShrU,
+            Operator::I64Rotl => BinOp::I64Rotl,
+            Operator::I64Rotr => BinOp::I64Rotr,
+            Operator::F32Eq => BinOp::F32Eq,
+            Operator::F32Ne => BinOp::F32Ne,
+            Operator::F32Lt => BinOp::F32Lt,
+            Operator::F32Gt => BinOp::F32Gt,
+            Operator::F32Le => BinOp::F32Le,
+            Operator::F32Ge => BinOp::F32Ge,
+            Operator::F32Add => BinOp::F32Add,
+            Operator::F32Sub => BinOp::F32Sub,
+            Operator::F32Mul => BinOp::F32Mul,
+            Operator::F32Div => BinOp::F32Div,
+            Operator::F32Min => BinOp::F32Min,
+            Operator::F32Max => BinOp::F32Max,
+            Operator::F32Copysign => BinOp::F32Copysign,
+            Operator::F64Eq => BinOp::F64Eq,
+            Operator::F64Ne => BinOp::F64Ne,
+            Operator::F64Lt => BinOp::F64Lt,
+            Operator::F64Gt => BinOp::F64Gt,
+            Operator::F64Le => BinOp::F64Le,
+            Operator::F64Ge => BinOp::F64Ge,
+            Operator::F64Add => BinOp::F64Add,
+            Operator::F64Sub => BinOp::F64Sub,
+            Operator::F64Mul => BinOp::F64Mul,
+            Operator::F64Div => BinOp::F64Div,
+            Operator::F64Min => BinOp::F64Min,
+            Operator::F64Max => BinOp::F64Max,
+            Operator::F64Copysign => BinOp::F64Copysign,
+            _ => return None,
+        })
+    }
+
+    pub(crate) fn eval(self, a: Val, b: Val) -> Result<Val, &'static str> {
+        Ok(match self {
+            BinOp::I32Eq => {
+                let (a, b) = (a.i32(), b.i32());
+                Val::I32((a == b) as i32)
+            }
+            BinOp::I32Ne => {
+                let (a, b) = (a.i32(), b.i32());
+                Val::I32((a != b) as i32)
+            }
+            BinOp::I32LtS => {
+                let (a, b) = (a.i32(), b.i32());
+                Val::I32((a < b) as i32)
+            }
+            BinOp::I32LtU => {
+                let (a, b) = (a.i32(), b.i32());
+                Val::I32(((a as u32) < (b as u32)) as i32)
+            }
+            BinOp::I32GtS => {
+                let (a, b) = (a.i32(), b.i32());
+                Val::I32((a > b) as i32)
+            }
+            BinOp::I32GtU => {
+                let (a, b) = (a.i32(), b.i32());
+                Val::I32(((a as u32) > (b as u32)) as i32)
+            }
+            BinOp::I32LeS => {
+                let (a, b) = (a.i32(), b.i32());
+                Val::I32((a <= b) as i32)
+            }
+            BinOp::I32LeU => {
+                let (a, b) = (a.i32(), b.i32());
+                Val::I32(((a as u32) <= (b as u32)) as i32)
+            }
+            BinOp::I32GeS => {
+                let (a, b) = (a.i32(), b.i32());
+                Val::I32((a >= b) as i32)
+            }
+            BinOp::I32GeU => {
+                let (a, b) = (a.i32(), b.i32());
+                Val::I32(((a as u32) >= (b as u32)) as i32)
+            }
+            BinOp::I32Add => {
+                let (a, b) = (a.i32(), b.i32());
+                Val::I32(a.wrapping_add(b))
+            }
+            BinOp::I32Sub => {
+                let (a, b) = (a.i32(), b.i32());
+                Val::I32(a.wrapping_sub(b))
+            }
+            BinOp::I32Mul => {
+                let (a, b) = (a.i32(), b.i32());
+                Val::I32(a.wrapping_mul(b))
+            }
+            BinOp::I32DivS => {
+                let (a, b) = (a.i32(), b.i32());
+                if b == 0 {
+                    return Err(DIVIDE_BY_ZERO);
+                }
+                Val::I32(a.checked_div(b).ok_or(INTEGER_OVERFLOW)?)
+            }
+            BinOp::I32DivU => {
+                let (a, b) = (a.i32(), b.i32());
+                Val::I32((a as u32).checked_div(b as u32).ok_or(DIVIDE_BY_ZERO)? as i32)
+            }
+            BinOp::I32RemS => {
+                let (a, b) = (a.i32(), b.i32());
+                if b == 0 {
+                    return Err(DIVIDE_BY_ZERO);
+                }
+                Val::I32(a.wrapping_rem(b))
+            }
+            BinOp::I32RemU => {
+                let (a, b) = (a.i32(), b.i32());
+                Val::I32((a as u32).checked_rem(b as u32).ok_or(DIVIDE_BY_ZERO)? as i32)
+            }
+            BinOp::I32And => {
+                let (a, b) = (a.i32(), b.i32());
+                Val::I32(a & b)
+            }
+            BinOp::I32Or => {
+                let (a, b) = (a.i32(), b.i32());
+                Val::I32(a | b)
+            }
+            BinOp::I32Xor => {
+                let (a, b) = (a.i32(), b.i32());
+                Val::I32(a ^ b)
+            }
+            BinOp::I32Shl => {
+                let (a, b) = (a.i32(), b.i32());
+                Val::I32(a.wrapping_shl(b as u32))
+            }
+            BinOp::I32ShrS => {
+                let (a, b) = (a.i32(), b.i32());
+                Val::I32(a.wrapping_shr(b as u32))
+            }
+            BinOp::I32ShrU => {
+                let (a, b) = (a.i32(), b.i32());
+                Val::I32((a as u32).wrapping_shr(b as u32) as i32)
+            }
+            BinOp::I32Rotl => {
+                let (a, b) = (a.i32(), b.i32());
+                Val::I32(a.rotate_left(b as u32))
+            }
+            BinOp::I32Rotr => {
+                let (a, b) = (a.i32(), b.i32());
+                Val::I32(a.rotate_right(b as u32))
+            }
+            BinOp::I64Eq => {
+                let (a, b) = (a.i64(), b.i64());
+                Val::I32((a == b) as i32)
+            }
+            BinOp::I64Ne => {
+                let (a, b) = (a.i64(), b.i64());
+                Val::I32((a != b) as i32)
+            }
+            BinOp::I64LtS => {
+                let (a, b) = (a.i64(), b.i64());
+                Val::I32((a < b) as i32)
+            }
+            BinOp::I64LtU => {
+                let (a, b) = (a.i64(), b.i64());
+                Val::I32(((a as u64) < (b as u64)) as i32)
+            }
+            BinOp::I64GtS => {
+                let (a, b) = (a.i64(), b.i64());
+                Val::I32((a > b) as i32)
+            }
+            BinOp::I64GtU => {
+                let (a, b) = (a.i64(), b.i64());
+                Val::I32(((a as u64) > (b as u64)) as i32)
+            }
+            BinOp::I64LeS => {
+                let (a, b) = (a.i64(), b.i64());
+                Val::I32((a <= b) as i32)
+            }
+            BinOp::I64LeU => {
+                let (a, b) = (a.i64(), b.i64());
+                Val::I32(((a as u64) <= (b as u64)) as i32)
+            }
+            BinOp::I64GeS => {
+                let (a, b) = (a.i64(), b.i64());
+                Val::I32((a >= b) as i32)
+            }
+            BinOp::I64GeU => {
+                let (a, b) = (a.i64(), b.i64());
+                Val::I32(((a as u64) >= (b as u64)) as i32)
+            }
+            BinOp::I64Add => {
+                let (a, b) = (a.i64(), b.i64());
+                Val::I64(a.wrapping_add(b))
+            }
+            BinOp::I64Sub => {
+                let (a, b) = (a.i64(), b.i64());
+                Val::I64(a.wrapping_sub(b))
+            }
+            BinOp::I64Mul => {
+                let (a, b) = (a.i64(), b.i64());
+                Val::I64(a.wrapping_mul(b))
+            }
+            BinOp::I64DivS => {
+                let (a, b) = (a.i64(), b.i64());
+                if b == 0 {
+                    return Err(DIVIDE_BY_ZERO);
+                }
+                Val::I64(a.checked_div(b).ok_or(INTEGER_OVERFLOW)?)
+            }
+            BinOp::I64DivU => {
+                let (a, b) = (a.i64(), b.i64());
+                Val::I64((a as u64).checked_div(b as u64).ok_or(DIVIDE_BY_ZERO)? as i64)
+            }
+            BinOp::I64RemS => {
+                let (a, b) = (a.i64(), b.i64());
+                if b == 0 {
+                    return Err(DIVIDE_BY_ZERO);
+                }
+                Val::I64(a.wrapping_rem(b))
+            }
+            BinOp::I64RemU => {
+                let (a, b) = (a.i64(), b.i64());
+                Val::I64((a as u64).checked_rem(b as u64).ok_or(DIVIDE_BY_ZERO)? as i64)
+            }
+            BinOp::I64And => {
+                let (a, b) = (a.i64(), b.i64());
+                Val::I64(a & b)
+            }
+            BinOp::I64Or => {
+                let (a, b) = (a.i64(), b.i64());
+                Val::I64(a | b)
+            }
+            BinOp::I64Xor => {
+                let (a, b) = (a.i64(), b.i64());
+                Val::I64(a ^ b)
+            }
+            BinOp::I64Shl => {
+                let (a, b) = (a.i64(), b.i64());
+                Val::I64(a.wrapping_shl(b as u32))
+            }
+            BinOp::I64ShrS => {
+                let (a, b) = (a.i64(), b.i64());
+                Val::I64(a.wrapping_shr(b as u32))
+            }
+            BinOp::I64ShrU => {
+                let (a, b) = (a.i64(), b.i64());
+                Val::I64((a as u64).wrapping_shr(b as u32) as i64)
+            }
+            BinOp::I64Rotl => {
+                let (a, b) = (a.i64(), b.i64());
+                Val::I64(a.rotate_left(b as u32))
+            }
+            BinOp::I64Rotr => {
+                let (a, b) = (a.i64(), b.i64());
+                Val::I64(a.rotate_right(b as u32))
+            }
+            BinOp::F32Eq => {
+                let (a, b) = (a.f32(), b.f32());
+                Val::I32((a == b) as i32)
+            }
+            BinOp::F32Ne => {
+                let (a, b) = (a.f32(), b.f32());
+                Val::I32((a != b) as i32)
+            }
+            BinOp::F32Lt => {
+                let (a, b) = (a.f32(), b.f32());
+                Val::I32((a < b) as i32)
+            }
+            BinOp::F32Gt => {
+                let (a, b) = (a.f32(), b.f32());
+                Val::I32((a > b) as i32)
+            }
+            BinOp::F32Le => {
+                let (a, b) = (a.f32(), b.f32());
+                Val::I32((a <= b) as i32)
+            }
+            BinOp::F32Ge => {
+                let (a, b) = (a.f32(), b.f32());
+                Val::I32((a >= b) as i32)
+            }
+            BinOp::F32Add => {
+                let (a, b) = (a.f32(), b.f32());
+                Val::F32(a + b)
+            }
+            BinOp::F32Sub => {
+                let (a, b) = (a.f32(), b.f32());
+                Val::F32(a - b)
+            }
+            BinOp::F32Mul => {
+                let (a, b) = (a.f32(), b.f32());
+                Val::F32(a * b)
+            }
+            BinOp::F32Div => {
+                let (a, b) = (a.f32(), b.f32());
+                Val::F32(a / b)
+            }
+            BinOp::F32Min => {
+                let (a, b) = (a.f32(), b.f32());
+                Val::F32(f32_min(a, b))
+            }
+            BinOp::F32Max => {
+                let (a, b) = (a.f32(), b.f32());
+                Val::F32(f32_max(a, b))
+            }
+            BinOp::F32Copysign => {
+                let (a, b) = (a.f32(), b.f32());
+                Val::F32(a.copysign(b))
+            }
+            BinOp::F64Eq => {
+                let (a, b) = (a.f64(), b.f64());
+                Val::I32((a == b) as i32)
+            }
+            BinOp::F64Ne => {
+                let (a, b) = (a.f64(), b.f64());
+                Val::I32((a != b) as i32)
+            }
+            BinOp::F64Lt => {
+                let (a, b) = (a.f64(), b.f64());
+                Val::I32((a < b) as i32)
+            }
+            BinOp::F64Gt => {
+                let (a, b) = (a.f64(), b.f64());
+                Val::I32((a > b) as i32)
+            }
+            BinOp::F64Le => {
+                let (a, b) = (a.f64(), b.f64());
+                Val::I32((a <= b) as i32)
+            }
+            BinOp::F64Ge => {
+                let (a, b) = (a.f64(), b.f64());
+                Val::I32((a >= b) as i32)
+            }
+            BinOp::F64Add => {
+                let (a, b) = (a.f64(), b.f64());
+                Val::F64(a + b)
+            }
+            BinOp::F64Sub => {
+                let (a, b) = (a.f64(), b.f64());
+                Val::F64(a - b)
+            }
+            BinOp::F64Mul => {
+                let (a, b) = (a.f64(), b.f64());
+                Val::F64(a * b)
+            }
+            BinOp::F64Div => {
+                let (a, b) = (a.f64(), b.f64());
+                Val::F64(a / b)
+            }
+            BinOp::F64Min => {
+                let (a, b) = (a.f64(), b.f64());
+                Val::F64(f64_min(a, b))
+            }
+            BinOp::F64Max => {
+                let (a, b) = (a.f64(), b.f64());
+                Val::F64(f64_max(a, b))
+            }
+            BinOp::F64Copysign => {
+                let (a, b) = (a.f64(), b.f64());
+                Val::F64(a.copysign(b))
+            }
+        })
+    }
+}
+
+/// Truncates toward zero, trapping unless `lower < value < upper`.
+fn trunc(value: f64, lower: f64, upper: f64) -> Result<f64, &'static str> {
+    if value.is_nan() {
+        return Err(INVALID_CONVERSION);
+    }
+    if value <= lower || value >= upper {
+        return Err(INTEGER_OVERFLOW);
+    }
+    Ok(value.trunc())
+}
+
+macro_rules! float_min_max {
+    ($min:ident, $max:ident, $ty:ty) => {
+        fn $min(a: $ty, b: $ty) -> $ty {
+            if a.is_nan() || b.is_nan() {
+                <$ty>::NAN
+            } else if a == b {
+                // Distinguishes -0.0 from 0.0.
+                if a.is_sign_negative() { a } else { b }
+            } else {
+                a.min(b)
+            }
+        }
+
+        fn $max(a: $ty, b: $ty) -> $ty {
+            if a.is_nan() || b.is_nan() {
+                <$ty>::NAN
+            } else if a == b {
+                if a.is_sign_positive() { a } else { b }
+            } else {
+                a.max(b)
+            }
+        }
+    };
+}
+
+float_min_max!(f32_min, f32_max, f32);
+float_min_max!(f64_min, f64_max, f64);
+
+/// Classifies a numeric instruction by the operation after the type prefix, e.g. `add` in `i64.add`.
+fn numeric_class(name: &str) -> OpClass {
+    let op = name.split_once('.').map(|(_, op)| op).unwrap_or(name);
+    match op {
+        "eqz" | "eq" | "ne" | "lt" | "lt_s" | "lt_u" | "gt" | "gt_s" | "gt_u" | "le" | "le_s" | "le_u" | "ge"
+        | "ge_s" | "ge_u" => OpClass::Comparison,
+        "clz" | "ctz" | "popcnt" | "and" | "or" | "xor" | "shl" | "shr_s" | "shr_u" | "rotl" | "rotr" => {
+            OpClass::Bitwise
+        }
+        "div_s" | "div_u" | "rem_s" | "rem_u" => OpClass::Arithmetic,
+        // `f32.trunc` rounds; the conversions are spelled `trunc_f32_s` and so on.
+        _ if op.contains('_') => OpClass::Conversion,
+        _ => OpClass::Arithmetic,
+    }
+}
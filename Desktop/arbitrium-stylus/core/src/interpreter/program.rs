@@ -0,0 +1,508 @@
+use super::ops::{BinOp, LoadOp, StoreOp, UnOp};
+use super::Val;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use wasmparser::{
+    BlockType, CompositeInnerType, ConstExpr, DataKind, ElementItems, ElementKind, ExternalKind, Operator, Parser,
+    Payload, TypeRef, ValType,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub params: Vec<ValType>,
+    pub results: Vec<ValType>,
+}
+
+/// A decoded instruction. Structured control flow carries the positions of its
+/// matching `else`/`end` so branches are resolved without scanning.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instr {
+    Unreachable,
+    Nop,
+    Block { end: usize, params: u32, results: u32 },
+    Loop { params: u32, results: u32 },
+    If { else_pc: Option<usize>, end: usize, params: u32, results: u32 },
+    Else { end: usize },
+    End,
+    Br(u32),
+    BrIf(u32),
+    BrTable { targets: Box<[u32]>, default: u32 },
+    Return,
+    Call(u32),
+    CallIndirect { type_index: u32 },
+    Drop,
+    Select,
+    LocalGet(u32),
+    LocalSet(u32),
+    LocalTee(u32),
+    GlobalGet(u32),
+    GlobalSet(u32),
+    Load(LoadOp, u64),
+    Store(StoreOp, u64),
+    MemorySize,
+    MemoryGrow,
+    MemoryCopy,
+    MemoryFill,
+    MemoryInit(u32),
+    DataDrop(u32),
+    Const(Val),
+    Unary(UnOp),
+    Binary(BinOp),
+}
+
+impl Instr {
+    /// The instruction's text-format mnemonic, e.g. `local.get` or `i64.add`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Instr::Unreachable => "unreachable",
+            Instr::Nop => "nop",
+            Instr::Block { .. } => "block",
+            Instr::Loop { .. } => "loop",
+            Instr::If { .. } => "if",
+            Instr::Else { .. } => "else",
+            Instr::End => "end",
+            Instr::Br(_) => "br",
+            Instr::BrIf(_) => "br_if",
+            Instr::BrTable { .. } => "br_table",
+            Instr::Return => "return",
+            Instr::Call(_) => "call",
+            Instr::CallIndirect { .. } => "call_indirect",
+            Instr::Drop => "drop",
+            Instr::Select => "select",
+            Instr::LocalGet(_) => "local.get",
+            Instr::LocalSet(_) => "local.set",
+            Instr::LocalTee(_) => "local.tee",
+            Instr::GlobalGet(_) => "global.get",
+            Instr::GlobalSet(_) => "global.set",
+            Instr::Load(op, _) => op.name(),
+            Instr::Store(op, _) => op.name(),
+            Instr::MemorySize => "memory.size",
+            Instr::MemoryGrow => "memory.grow",
+            Instr::MemoryCopy => "memory.copy",
+            Instr::MemoryFill => "memory.fill",
+            Instr::MemoryInit(_) => "memory.init",
+            Instr::DataDrop(_) => "data.drop",
+            Instr::Const(Val::I32(_)) => "i32.const",
+            Instr::Const(Val::I64(_)) => "i64.const",
+            Instr::Const(Val::F32(_)) => "f32.const",
+            Instr::Const(Val::F64(_)) => "f64.const",
+            Instr::Unary(op) => op.name(),
+            Instr::Binary(op) => op.name(),
+        }
+    }
+
+    /// Whether gas is settled at this instruction: where control can branch, call or return.
+    /// These are the points where wasmer's metering charges and checks its limit.
+    pub fn is_metering_point(&self) -> bool {
+        matches!(
+            self,
+            Instr::Loop { .. }
+                | Instr::If { .. }
+                | Instr::Else { .. }
+                | Instr::End
+                | Instr::Br(_)
+                | Instr::BrIf(_)
+                | Instr::BrTable { .. }
+                | Instr::Return
+                | Instr::Call(_)
+                | Instr::CallIndirect { .. }
+        )
+    }
+}
+
+impl std::fmt::Display for Instr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())?;
+        match self {
+            Instr::Br(depth) | Instr::BrIf(depth) => write!(f, " {}", depth),
+            Instr::Call(index) => write!(f, " {}", index),
+            Instr::CallIndirect { type_index } => write!(f, " (type {})", type_index),
+            Instr::LocalGet(index) | Instr::LocalSet(index) | Instr::LocalTee(index) => write!(f, " {}", index),
+            Instr::GlobalGet(index) | Instr::GlobalSet(index) => write!(f, " {}", index),
+            Instr::Load(_, offset) | Instr::Store(_, offset) if *offset > 0 => write!(f, " offset={}", offset),
+            Instr::Const(value) => write!(f, " {}", value),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub index: u32,
+    pub type_index: u32,
+    /// `(module, name)` for imported functions, which have no body.
+    pub import: Option<(String, String)>,
+    /// Declared locals, not including parameters.
+    pub locals: Vec<ValType>,
+    pub body: Vec<Instr>,
+    /// Module byte offset of each instruction in `body`.
+    pub offsets: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ConstInit {
+    Value(Val),
+    Global(u32),
+    FuncRef(Option<u32>),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct GlobalDef {
+    pub init: ConstInit,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ElementSegment {
+    pub offset: Option<ConstInit>,
+    pub functions: Vec<Option<u32>>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct DataSegment {
+    pub offset: Option<ConstInit>,
+    pub bytes: Vec<u8>,
+}
+
+/// A validated module decoded into a form the interpreter can execute and inspect.
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    pub types: Vec<Signature>,
+    pub functions: Vec<Function>,
+    pub exports: HashMap<String, u32>,
+    pub(crate) globals: Vec<GlobalDef>,
+    pub(crate) memory: Option<(u64, Option<u64>)>,
+    pub(crate) table: Option<(u64, Option<u64>)>,
+    pub(crate) elements: Vec<ElementSegment>,
+    pub(crate) data: Vec<DataSegment>,
+    pub(crate) start: Option<u32>,
+}
+
+impl Program {
+    /// Decodes `wasm` for the interpreter. Contracts importing anything but functions, or using
+    /// instructions it does not implement, can still be run with `execute_function`.
+    pub fn parse(wasm: &[u8]) -> Result<Self> {
+        Self::decode(wasm)
+            .map_err(|e| anyhow!("{}; the interpreter cannot run this contract, but execute_function still can", e))
+    }
+
+    fn decode(wasm: &[u8]) -> Result<Self> {
+        wasmparser::validate(wasm).map_err(|e| anyhow!("Failed to validate module: {}", e))?;
+
+        let mut program = Program::default();
+        let mut defined = Vec::new();
+        let mut bodies = 0usize;
+        for payload in Parser::new(0).parse_all(wasm) {
+            match payload? {
+                Payload::TypeSection(reader) => {
+                    for group in reader {
+                        for ty in group?.into_types() {
+                            let signature = match &ty.composite_type.inner {
+                                CompositeInnerType::Func(func) => Signature {
+                                    params: func.params().to_vec(),
+                                    results: func.results().to_vec(),
+                                },
+                                _ => return Err(anyhow!("Unsupported non-function type")),
+                            };
+                            program.types.push(signature);
+                        }
+                    }
+                }
+                Payload::ImportSection(reader) => {
+                    for import in reader.into_imports() {
+                        let import = import?;
+                        match import.ty {
+                            TypeRef::Func(type_index) | TypeRef::FuncExact(type_index) => {
+                                program.functions.push(Function {
+                                    index: program.functions.len() as u32,
+                                    type_index,
+                                    import: Some((import.module.to_string(), import.name.to_string())),
+                                    locals: Vec::new(),
+                                    body: Vec::new(),
+                                    offsets: Vec::new(),
+                                });
+                            }
+                            _ => {
+                                return Err(anyhow!(
+                                    "Unsupported import {}::{}: only functions can be imported",
+                                    import.module,
+                                    import.name
+                                ))
+                            }
+                        }
+                    }
+                }
+                Payload::FunctionSection(reader) => {
+                    for type_index in reader {
+                        let type_index = type_index?;
+                        defined.push(program.functions.len());
+                        program.functions.push(Function {
+                            index: program.functions.len() as u32,
+                            type_index,
+                            import: None,
+                            locals: Vec::new(),
+                            body: Vec::new(),
+                            offsets: Vec::new(),
+                        });
+                    }
+                }
+                Payload::TableSection(reader) => {
+                    for table in reader {
+                        let table = table?;
+                        program.table.get_or_insert((table.ty.initial, table.ty.maximum));
+                    }
+                }
+                Payload::MemorySection(reader) => {
+                    for memory in reader {
+                        let memory = memory?;
+                        program.memory.get_or_insert((memory.initial, memory.maximum));
+                    }
+                }
+                Payload::GlobalSection(reader) => {
+                    for global in reader {
+                        let global = global?;
+                        program.globals.push(GlobalDef {
+                            init: const_init(&global.init_expr)?,
+                        });
+                    }
+                }
+                Payload::ExportSection(reader) => {
+                    for export in reader {
+                        let export = export?;
+                        if export.kind == ExternalKind::Func {
+                            program.exports.insert(export.name.to_string(), export.index);
+                        }
+                    }
+                }
+                Payload::StartSection { func, .. } => program.start = Some(func),
+                Payload::ElementSection(reader) => {
+                    for element in reader {
+                        let element = element?;
+                        let offset = match &element.kind {
+                            ElementKind::Active { offset_expr, .. } => Some(const_init(offset_expr)?),
+                            _ => None,
+                        };
+                        let mut functions = Vec::new();
+                        match element.items {
+                            ElementItems::Functions(items) => {
+                                for index in items {
+                                    functions.push(Some(index?));
+                                }
+                            }
+                            ElementItems::Expressions(_, items) => {
+                                for expr in items {
+                                    match const_init(&expr?)? {
+                                        ConstInit::FuncRef(index) => functions.push(index),
+                                        _ => return Err(anyhow!("Unsupported element expression")),
+                                    }
+                                }
+                            }
+                        }
+                        program.elements.push(ElementSegment { offset, functions });
+                    }
+                }
+                Payload::DataSection(reader) => {
+                    for data in reader {
+                        let data = data?;
+                        let offset = match &data.kind {
+                            DataKind::Active { offset_expr, .. } => Some(const_init(offset_expr)?),
+                            DataKind::Passive => None,
+                        };
+                        program.data.push(DataSegment {
+                            offset,
+                            bytes: data.data.to_vec(),
+                        });
+                    }
+                }
+                Payload::CodeSectionEntry(body) => {
+                    let index = *defined
+                        .get(bodies)
+                        .ok_or_else(|| anyhow!("Code section has more bodies than declared functions"))?;
+                    bodies += 1;
+
+                    let mut locals = Vec::new();
+                    for local in body.get_locals_reader()? {
+                        let (count, ty) = local?;
+                        locals.extend(std::iter::repeat_n(ty, count as usize));
+                    }
+                    let (code, offsets) = program.decode_body(body.get_operators_reader()?)?;
+                    let function = &mut program.functions[index];
+                    function.locals = locals;
+                    function.body = code;
+                    function.offsets = offsets;
+                }
+                _ => {}
+            }
+        }
+        Ok(program)
+    }
+
+    pub fn export(&self, name: &str) -> Option<u32> {
+        self.exports.get(name).copied()
+    }
+
+    /// The name `index` is exported under, if any.
+    pub fn export_name(&self, index: u32) -> Option<&str> {
+        self.exports
+            .iter()
+            .find(|(_, &export)| export == index)
+            .map(|(name, _)| name.as_str())
+    }
+
+    pub fn function(&self, index: u32) -> Option<&Function> {
+        self.functions.get(index as usize)
+    }
+
+    pub fn signature(&self, func_index: u32) -> &Signature {
+        &self.types[self.functions[func_index as usize].type_index as usize]
+    }
+
+    /// The function and instruction at a module byte offset.
+    pub fn locate(&self, module_offset: u64) -> Option<(u32, usize)> {
+        self.functions.iter().find_map(|function| {
+            function
+                .offsets
+                .binary_search(&module_offset)
+                .ok()
+                .map(|pc| (function.index, pc))
+        })
+    }
+
+    fn block_arity(&self, ty: BlockType) -> (u32, u32) {
+        match ty {
+            BlockType::Empty => (0, 0),
+            BlockType::Type(_) => (0, 1),
+            BlockType::FuncType(index) => {
+                let signature = &self.types[index as usize];
+                (signature.params.len() as u32, signature.results.len() as u32)
+            }
+        }
+    }
+
+    fn decode_body(&self, reader: wasmparser::OperatorsReader<'_>) -> Result<(Vec<Instr>, Vec<u64>)> {
+        let mut code = Vec::new();
+        let mut offsets = Vec::new();
+        let mut open: Vec<usize> = Vec::new();
+
+        for item in reader.into_iter_with_offsets() {
+            let (op, offset) = item?;
+            let pc = code.len();
+            let instr = match op {
+                Operator::Unreachable => Instr::Unreachable,
+                Operator::Nop => Instr::Nop,
+                Operator::Block { blockty } => {
+                    open.push(pc);
+                    let (params, results) = self.block_arity(blockty);
+                    Instr::Block { end: 0, params, results }
+                }
+                Operator::Loop { blockty } => {
+                    open.push(pc);
+                    let (params, results) = self.block_arity(blockty);
+                    Instr::Loop { params, results }
+                }
+                Operator::If { blockty } => {
+                    open.push(pc);
+                    let (params, results) = self.block_arity(blockty);
+                    Instr::If { else_pc: None, end: 0, params, results }
+                }
+                Operator::Else => {
+                    if let Some(Instr::If { else_pc, .. }) = open.last().map(|&start| &mut code[start]) {
+                        *else_pc = Some(pc);
+                    }
+                    Instr::Else { end: 0 }
+                }
+                Operator::End => {
+                    if let Some(start) = open.pop() {
+                        let mut else_at = None;
+                        match &mut code[start] {
+                            Instr::Block { end, .. } => *end = pc,
+                            Instr::If { else_pc, end, .. } => {
+                                *end = pc;
+                                else_at = *else_pc;
+                            }
+                            _ => {}
+                        }
+                        if let Some(else_pc) = else_at {
+                            code[else_pc] = Instr::Else { end: pc };
+                        }
+                    }
+                    Instr::End
+                }
+                Operator::Br { relative_depth } => Instr::Br(relative_depth),
+                Operator::BrIf { relative_depth } => Instr::BrIf(relative_depth),
+                Operator::BrTable { targets } => Instr::BrTable {
+                    default: targets.default(),
+                    targets: targets.targets().collect::<Result<Vec<_>, _>>()?.into_boxed_slice(),
+                },
+                Operator::Return => Instr::Return,
+                Operator::Call { function_index } => Instr::Call(function_index),
+                Operator::CallIndirect { type_index, .. } => Instr::CallIndirect { type_index },
+                Operator::Drop => Instr::Drop,
+                Operator::Select | Operator::TypedSelect { .. } => Instr::Select,
+                Operator::LocalGet { local_index } => Instr::LocalGet(local_index),
+                Operator::LocalSet { local_index } => Instr::LocalSet(local_index),
+                Operator::LocalTee { local_index } => Instr::LocalTee(local_index),
+                Operator::GlobalGet { global_index } => Instr::GlobalGet(global_index),
+                Operator::GlobalSet { global_index } => Instr::GlobalSet(global_index),
+                Operator::I32Load { memarg } => Instr::Load(LoadOp::I32Load, memarg.offset),
+                Operator::I64Load { memarg } => Instr::Load(LoadOp::I64Load, memarg.offset),
+                Operator::F32Load { memarg } => Instr::Load(LoadOp::F32Load, memarg.offset),
+                Operator::F64Load { memarg } => Instr::Load(LoadOp::F64Load, memarg.offset),
+                Operator::I32Load8S { memarg } => Instr::Load(LoadOp::I32Load8S, memarg.offset),
+                Operator::I32Load8U { memarg } => Instr::Load(LoadOp::I32Load8U, memarg.offset),
+                Operator::I32Load16S { memarg } => Instr::Load(LoadOp::I32Load16S, memarg.offset),
+                Operator::I32Load16U { memarg } => Instr::Load(LoadOp::I32Load16U, memarg.offset),
+                Operator::I64Load8S { memarg } => Instr::Load(LoadOp::I64Load8S, memarg.offset),
+                Operator::I64Load8U { memarg } => Instr::Load(LoadOp::I64Load8U, memarg.offset),
+                Operator::I64Load16S { memarg } => Instr::Load(LoadOp::I64Load16S, memarg.offset),
+                Operator::I64Load16U { memarg } => Instr::Load(LoadOp::I64Load16U, memarg.offset),
+                Operator::I64Load32S { memarg } => Instr::Load(LoadOp::I64Load32S, memarg.offset),
+                Operator::I64Load32U { memarg } => Instr::Load(LoadOp::I64Load32U, memarg.offset),
+                Operator::I32Store { memarg } => Instr::Store(StoreOp::I32Store, memarg.offset),
+                Operator::I64Store { memarg } => Instr::Store(StoreOp::I64Store, memarg.offset),
+                Operator::F32Store { memarg } => Instr::Store(StoreOp::F32Store, memarg.offset),
+                Operator::F64Store { memarg } => Instr::Store(StoreOp::F64Store, memarg.offset),
+                Operator::I32Store8 { memarg } => Instr::Store(StoreOp::I32Store8, memarg.offset),
+                Operator::I32Store16 { memarg } => Instr::Store(StoreOp::I32Store16, memarg.offset),
+                Operator::I64Store8 { memarg } => Instr::Store(StoreOp::I64Store8, memarg.offset),
+                Operator::I64Store16 { memarg } => Instr::Store(StoreOp::I64Store16, memarg.offset),
+                Operator::I64Store32 { memarg } => Instr::Store(StoreOp::I64Store32, memarg.offset),
+                Operator::MemorySize { .. } => Instr::MemorySize,
+                Operator::MemoryGrow { .. } => Instr::MemoryGrow,
+                Operator::MemoryCopy { .. } => Instr::MemoryCopy,
+                Operator::MemoryFill { .. } => Instr::MemoryFill,
+                Operator::MemoryInit { data_index, .. } => Instr::MemoryInit(data_index),
+                Operator::DataDrop { data_index } => Instr::DataDrop(data_index),
+                Operator::I32Const { value } => Instr::Const(Val::I32(value)),
+                Operator::I64Const { value } => Instr::Const(Val::I64(value)),
+                Operator::F32Const { value } => Instr::Const(Val::F32(f32::from_bits(value.bits()))),
+                Operator::F64Const { value } => Instr::Const(Val::F64(f64::from_bits(value.bits()))),
+                other => match (UnOp::from_operator(&other), BinOp::from_operator(&other)) {
+                    (Some(op), _) => Instr::Unary(op),
+                    (_, Some(op)) => Instr::Binary(op),
+                    _ => return Err(anyhow!("Unsupported instruction at offset 0x{:x}: {:?}", offset, other)),
+                },
+            };
+            code.push(instr);
+            offsets.push(offset as u64);
+        }
+        Ok((code, offsets))
+    }
+}
+
+fn const_init(expr: &ConstExpr<'_>) -> Result<ConstInit> {
+    let mut reader = expr.get_operators_reader();
+    let init = match reader.read()? {
+        Operator::I32Const { value } => ConstInit::Value(Val::I32(value)),
+        Operator::I64Const { value } => ConstInit::Value(Val::I64(value)),
+        Operator::F32Const { value } => ConstInit::Value(Val::F32(f32::from_bits(value.bits()))),
+        Operator::F64Const { value } => ConstInit::Value(Val::F64(f64::from_bits(value.bits()))),
+        Operator::GlobalGet { global_index } => ConstInit::Global(global_index),
+        Operator::RefFunc { function_index } => ConstInit::FuncRef(Some(function_index)),
+        Operator::RefNull { .. } => ConstInit::FuncRef(None),
+        other => return Err(anyhow!("Unsupported constant expression: {:?}", other)),
+    };
+    match reader.read()? {
+        Operator::End => Ok(init),
+        other => Err(anyhow!("Unsupported constant expression: {:?}", other)),
+    }
+}
@@ -1,8 +1,61 @@
+pub mod abi;
+pub mod backtrace;
+pub mod call_tree;
+pub mod console;
+pub mod coverage;
+pub mod debugger;
+pub mod error;
+pub mod fees;
+pub mod fuzz;
+pub mod host;
+pub mod host_trace;
+pub mod imports;
+pub mod inspector;
+pub mod interpreter;
+pub mod metering;
+pub mod panic;
+pub mod precompiles;
+pub mod profile;
+pub mod recording;
+pub mod source_map;
+pub mod symbols;
+pub mod world;
+
+use crate::coverage::Coverage;
+use crate::inspector::InspectorSlot;
+use crate::interpreter::{Machine, Tape};
+use crate::profile::Profiler;
+use crate::world::Snapshot;
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use wasmer::{imports, Function, Instance, Module, Store, Value};
-use wasmer_wasi::{WasiState, WasiEnv};
+use std::sync::{Arc, Mutex, MutexGuard};
+use wasmer::{ExternType, FunctionEnv, Module, Store};
+
+pub use backtrace::{Backtrace, BacktraceFrame};
+pub use call_tree::{CallNode, CallStep};
+pub use console::{ConsoleLine, ConsoleStream};
+pub use coverage::{BranchCoverage, CoverageCount, CoverageReport, FileCoverage, FunctionCoverage, LineCoverage};
+pub use debugger::{Breakpoint, Debugger, Location, StopReason};
+pub use error::{StateChange, StaticCallViolation, Trap};
+pub use fees::{L1Cost, L1Pricing};
+pub use fuzz::{Failure, FailureKind, FuzzConfig, FuzzInput, FuzzReport, FuzzTarget, Fuzzer, Invariant};
+pub use host::{CallFrame, CustomImport, ExecutionContext, GuestMemory, HostEnv};
+pub use host_trace::HostCallTrace;
+pub use imports::{MissingImport, StubPolicy, UnresolvedImports};
+pub use inspector::Inspector;
+pub use interpreter::{Program, Val};
+pub use panic::ContractPanic;
+pub use precompiles::{L2ToL1Message, Precompiles};
+pub use profile::{
+    AnnotatedFile, AnnotatedLine, BlockProfile, ClassCount, FunctionProfile, GasBreakdown, GasProfile, HostFunctionGas,
+    LineGas, LoopProfile, OpClass, OpcodeCount, ProfileFormat, SourceAnnotation, StackSample,
+};
+pub use recording::{HostCall, Recording, StorageAccess, StorageOp, Trace};
+pub use source_map::{SourceFrame, SourceLocation, SourceMap};
+pub use symbols::Symbols;
+pub use world::{Address, Bytes32, Log, World};
+pub use wasmer::{RuntimeError, Type, Value};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecutionResult {
@@ -10,99 +63,605 @@ pub struct ExecutionResult {
     pub gas_used: u64,
     pub call_trace: Vec<String>,
     pub memory_usage: u64,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct GasProfile {
-    pub function_name: String,
-    pub gas_used: u64,
-    pub instruction_count: u64,
-    pub call_depth: u32,
+    pub output: Vec<u8>,
+    pub logs: Vec<Log>,
+    pub l1_cost: L1Cost,
+    pub console: Vec<ConsoleLine>,
+    /// Every host import the call made, nested contract calls included, in order.
+    pub host_calls: Vec<HostCallTrace>,
 }
 
 pub struct StylusRuntime {
     store: Store,
     module: Module,
+    wasm: Arc<Vec<u8>>,
+    symbols: Arc<Symbols>,
+    program: Option<Arc<Program>>,
+    world: Arc<Mutex<World>>,
+    context: ExecutionContext,
+    l1_pricing: L1Pricing,
     gas_table: HashMap<String, u64>,
     instrumentation_enabled: bool,
+    static_mode: bool,
+    recording_enabled: bool,
+    recording: Option<Recording>,
+    stub_policy: StubPolicy,
+    custom_imports: Arc<Vec<CustomImport>>,
+    constructed: bool,
+    deployment: Option<ExecutionResult>,
+    inspector: InspectorSlot,
+    coverage: Option<Coverage>,
 }
 
+/// Exports treated as a contract's one-shot constructor.
+const CONSTRUCTOR_EXPORTS: &[&str] = &["constructor", "init", "initialize"];
+
 impl StylusRuntime {
     pub fn new(wasm_bytes: &[u8]) -> Result<Self> {
-        let store = Store::default();
-        let module = Module::new(&store, wasm_bytes)
-            .map_err(|e| anyhow!("Failed to create module: {}", e))?;
-        
+        Self::with_context(wasm_bytes, ExecutionContext::default())
+    }
+
+    /// Deploys the contract at `ctx.contract_address` and runs its constructor once with
+    /// `init_calldata`. Storage written by the constructor persists for the whole session.
+    ///
+    /// A `constructor`, `init` or `initialize` export is preferred; otherwise the calldata
+    /// is dispatched through `user_entrypoint`, which is how the Stylus SDK routes constructors.
+    pub fn deploy(wasm: &[u8], init_calldata: &[u8], ctx: ExecutionContext) -> Result<Self> {
+        let mut runtime = Self::with_context(wasm, ctx)?;
+        runtime.construct(init_calldata)?;
+        Ok(runtime)
+    }
+
+    /// Runs the constructor as `deploy` does, for a runtime whose world was set up first,
+    /// such as funding the sender of a deployment that sends `value`. The value moves from
+    /// the sender to the contract; if the constructor fails, the world is left as it was.
+    pub fn construct(&mut self, init_calldata: &[u8]) -> Result<()> {
+        if self.constructed {
+            return Err(anyhow!("Constructor has already run"));
+        }
+        let snapshot = {
+            let mut world = self.world();
+            let snapshot = world.snapshot();
+            world.transfer(&self.context.sender, &self.context.contract_address, self.context.value)?;
+            world.account_mut(&self.context.sender).nonce += 1;
+            snapshot
+        };
+
+        match self.run_constructor(init_calldata) {
+            Ok(Some(result)) => {
+                self.constructed = true;
+                self.deployment = Some(result);
+                Ok(())
+            }
+            Ok(None) => {
+                self.constructed = true;
+                Ok(())
+            }
+            Err(e) => {
+                self.world().revert_to(snapshot);
+                Err(e)
+            }
+        }
+    }
+
+    /// The constructor's result, or `None` if there is no constructor and nothing to pass it.
+    fn run_constructor(&mut self, init_calldata: &[u8]) -> Result<Option<ExecutionResult>> {
+        let constructor = CONSTRUCTOR_EXPORTS.iter().copied().find(|name| self.has_export(name));
+        let result = match constructor {
+            Some(name) => self.invoke(name, &[], init_calldata.to_vec(), init_calldata)?,
+            None if self.has_export("user_entrypoint") && !init_calldata.is_empty() => {
+                let len = Value::I32(init_calldata.len() as i32);
+                self.invoke("user_entrypoint", &[len], init_calldata.to_vec(), init_calldata)?
+            }
+            None if init_calldata.is_empty() => return Ok(None),
+            None => return Err(anyhow!("Contract has no constructor to receive init calldata")),
+        };
+        if result.return_value != 0 {
+            let reason = abi::decode_revert(&result.output)
+                .unwrap_or_else(|| format!("{} bytes of revert data", result.output.len()));
+            return Err(anyhow!("Constructor reverted: {}", reason));
+        }
+        Ok(Some(result))
+    }
+
+    fn with_context(wasm_bytes: &[u8], context: ExecutionContext) -> Result<Self> {
         let mut gas_table = HashMap::new();
         gas_table.insert("call".to_string(), 100);
         gas_table.insert("memory_grow".to_string(), 1000);
         gas_table.insert("local_get".to_string(), 1);
         gas_table.insert("local_set".to_string(), 1);
+        gas_table.insert("storage_read".to_string(), 200);
+        gas_table.insert("storage_write".to_string(), 5000);
+        gas_table.insert("call_contract".to_string(), 700);
+        gas_table.insert("create".to_string(), 32000);
+        gas_table.insert("log".to_string(), 375);
+        gas_table.insert("keccak".to_string(), 30);
+        
+        let mut world = World::new(gas_table.clone());
+        let contract = world.compile(wasm_bytes)?;
+        let store = Store::new(contract.engine.clone());
+        let module = contract.module.clone();
+        let wasm = contract.wasm.clone();
+        let symbols = contract.symbols.clone();
+        
+        world.install(&context.contract_address, contract);
         
         Ok(Self {
             store,
             module,
+            wasm,
+            symbols,
+            program: None,
+            world: Arc::new(Mutex::new(world)),
+            context,
+            l1_pricing: L1Pricing::default(),
             gas_table,
             instrumentation_enabled: true,
+            static_mode: false,
+            recording_enabled: false,
+            recording: None,
+            stub_policy: StubPolicy::default(),
+            custom_imports: Arc::new(Vec::new()),
+            constructed: false,
+            deployment: None,
+            inspector: InspectorSlot::default(),
+            coverage: None,
         })
     }
 
     pub fn execute_function(&mut self, fn_name: &str, args: &[i64]) -> Result<ExecutionResult> {
-        let wasi_env = WasiState::new("stylus-runtime").finalize(&mut self.store)?;
-        let import_object = wasi_env.import_object(&mut self.store, &self.module)?;
+        self.ensure_callable(fn_name)?;
+        self.dispatch(fn_name, args, Vec::new(), &transaction_calldata(fn_name, args))
+    }
+
+    /// Fails for a constructor export once the constructor has run.
+    fn ensure_callable(&self, fn_name: &str) -> Result<()> {
+        if self.constructed && CONSTRUCTOR_EXPORTS.contains(&fn_name) {
+            return Err(anyhow!("Constructor '{}' has already run", fn_name));
+        }
+        Ok(())
+    }
+
+    /// Sends `calldata` to the contract through `user_entrypoint`, as a transaction would.
+    /// A non-zero return value means the contract reverted with `output` as its revert data.
+    pub fn execute_calldata(&mut self, calldata: &[u8]) -> Result<ExecutionResult> {
+        self.dispatch("user_entrypoint", &[calldata.len() as i64], calldata.to_vec(), calldata)
+    }
+
+    fn dispatch(&mut self, fn_name: &str, args: &[i64], calldata: Vec<u8>, tx_calldata: &[u8]) -> Result<ExecutionResult> {
+        if self.recording_enabled {
+            return self.record(fn_name, args, calldata, tx_calldata);
+        }
+        if self.inspector.wants_instructions() || self.coverage.is_some() {
+            return self.interpret(fn_name, args, calldata, tx_calldata);
+        }
+        let values = self.export_args(fn_name, args);
+        self.invoke(fn_name, &values, calldata, tx_calldata)
+    }
+
+    /// `args` converted to the parameter types of export `fn_name`, or to i64 if it has none.
+    fn export_args(&self, fn_name: &str, args: &[i64]) -> Vec<Value> {
+        let params = self.module.exports().find(|export| export.name() == fn_name).and_then(|export| match export.ty() {
+            ExternType::Function(ty) => Some(ty.params().to_vec()),
+            _ => None,
+        });
+        args.iter()
+            .enumerate()
+            .map(|(i, &arg)| match params.as_ref().and_then(|params| params.get(i)) {
+                Some(Type::I32) => Value::I32(arg as i32),
+                _ => Value::I64(arg),
+            })
+            .collect()
+    }
+
+    /// Runs the call in the interpreter, keeping a `Recording` of it even if it traps.
+    fn record(&mut self, fn_name: &str, args: &[i64], calldata: Vec<u8>, tx_calldata: &[u8]) -> Result<ExecutionResult> {
+        let l1_cost = self.estimate_l1_cost(tx_calldata);
+        let snapshot = self.world().snapshot();
+        let (mut machine, args) = self.interpreter_call(fn_name, args, calldata, Tape::Recording(Trace::default()))?;
+        let outcome = machine.run();
+        self.keep_recording(fn_name, args, &mut machine, &outcome);
+        self.interpreter_result(fn_name, &mut machine, outcome, l1_cost, snapshot)
+    }
+
+    /// Keeps the recording of a finished interpreter call for `take_recording`.
+    fn keep_recording(
+        &mut self,
+        fn_name: &str,
+        args: Vec<Val>,
+        machine: &mut Machine,
+        outcome: &std::result::Result<Vec<Val>, RuntimeError>,
+    ) {
+        let trace = machine.take_trace().unwrap_or_default();
+        let env = machine.env();
+        self.recording = Some(Recording {
+            wasm: self.wasm.to_vec(),
+            function: fn_name.to_string(),
+            args,
+            context: env.context.clone(),
+            frame: env.frame.clone(),
+            gas_table: env.gas_table.clone(),
+            trace,
+            outcome: outcome.as_ref().cloned().map_err(RuntimeError::message),
+        });
+    }
+
+    /// Runs the call in the interpreter so every instruction can be inspected or counted for coverage.
+    fn interpret(&mut self, fn_name: &str, args: &[i64], calldata: Vec<u8>, tx_calldata: &[u8]) -> Result<ExecutionResult> {
+        let l1_cost = self.estimate_l1_cost(tx_calldata);
+        let snapshot = self.world().snapshot();
+        let (mut machine, _) = self.interpreter_call(fn_name, args, calldata, Tape::Off)?;
+        let outcome = machine.run();
+        self.interpreter_result(fn_name, &mut machine, outcome, l1_cost, snapshot)
+    }
+
+    /// Turns a finished interpreter call into the result `execute_function` returns, rolling
+    /// the world back to `snapshot` if the call trapped or reverted.
+    fn interpreter_result(
+        &self,
+        fn_name: &str,
+        machine: &mut Machine,
+        outcome: std::result::Result<Vec<Val>, RuntimeError>,
+        l1_cost: L1Cost,
+        snapshot: Snapshot,
+    ) -> Result<ExecutionResult> {
+        let frame = &machine.env().frame;
+        let results = match outcome {
+            Ok(results) => results,
+            Err(e) => {
+                self.world().revert_to(snapshot);
+                self.inspector.inspect(|inspector| {
+                    inspector.on_trap(frame, &e.message());
+                    inspector.on_call_exit(frame, false, &[]);
+                });
+                let console = machine.console().to_vec();
+                let backtrace = machine.backtrace();
+                return Err(match e.downcast::<StaticCallViolation>() {
+                    Ok(violation) => violation.into(),
+                    Err(e) => Trap::new(e.message(), console, backtrace).into(),
+                });
+            }
+        };
+
+        let return_value = results.first().map(|value| value.as_i64()).unwrap_or(0);
+        if reverted(fn_name, return_value) {
+            self.world().revert_to(snapshot);
+        }
+        let mut call_trace = Vec::new();
+        if self.instrumentation_enabled {
+            call_trace.push(format!("Calling function: {}", fn_name));
+        }
+        self.inspector.inspect(|inspector| inspector.on_call_exit(frame, true, &machine.env().output));
+        let gas_used = machine.gas_used();
+        let memory_usage = machine.memory().len() as u64;
+        let env = machine.env_mut();
+        Ok(ExecutionResult {
+            return_value,
+            gas_used,
+            call_trace,
+            memory_usage,
+            output: std::mem::take(&mut env.output),
+            logs: std::mem::take(&mut env.logs),
+            l1_cost,
+            console: std::mem::take(&mut env.console),
+            host_calls: std::mem::take(&mut env.host_calls),
+        })
+    }
+
+    fn invoke(&mut self, fn_name: &str, values: &[Value], calldata: Vec<u8>, tx_calldata: &[u8]) -> Result<ExecutionResult> {
+        let l1_cost = self.estimate_l1_cost(tx_calldata);
         
-        let instance = Instance::new(&mut self.store, &self.module, &import_object)?;
+        let frame = self.top_level_frame(calldata)?;
+        let env = self.host_env(frame.clone());
+        let snapshot = self.world().snapshot();
+        let (instance, host_env) = match host::instantiate(&mut self.store, &self.module, env) {
+            Ok(instantiated) => instantiated,
+            Err(e) => {
+                self.world().revert_to(snapshot);
+                return Err(e);
+            }
+        };
         
         let func = instance.exports.get_function(fn_name)
             .map_err(|_| anyhow!("Function '{}' not found", fn_name))?;
         
-        let values: Vec<Value> = args.iter().map(|&a| Value::I64(a)).collect();
-        
-        let start_gas = 0u64;
         let mut call_trace = Vec::new();
         
         if self.instrumentation_enabled {
             call_trace.push(format!("Calling function: {}", fn_name));
         }
         
-        let result = func.call(&mut self.store, &values)
-            .map_err(|e| anyhow!("Function execution failed: {}", e))?;
+        self.inspector.inspect(|inspector| inspector.on_call_enter(&frame));
+        let outcome = func.call(&mut self.store, values);
+        let gas_used = metering::gas_used(&mut self.store, &instance, self.context.gas_limit);
+        let host = host_env.as_mut(&mut self.store);
+        host.flush_console();
+        let console = std::mem::take(&mut host.console);
+        
+        let result = match outcome {
+            Ok(result) => result,
+            Err(e) => {
+                self.world().revert_to(snapshot);
+                let message = if gas_used.is_none() { host::OUT_OF_GAS.to_string() } else { e.message() };
+                self.inspector.inspect(|inspector| {
+                    inspector.on_trap(&frame, &message);
+                    inspector.on_call_exit(&frame, false, &[]);
+                });
+                return Err(match e.downcast::<StaticCallViolation>() {
+                    Ok(violation) => violation.into(),
+                    Err(e) => {
+                        let backtrace = Backtrace::capture(&e, &self.symbols);
+                        Trap::new(message, console, backtrace).into()
+                    }
+                });
+            }
+        };
         
         let return_value = match result.get(0) {
             Some(Value::I64(val)) => *val,
             Some(Value::I32(val)) => *val as i64,
             _ => 0,
         };
+        if reverted(fn_name, return_value) {
+            self.world().revert_to(snapshot);
+        }
         
-        let gas_used = self.estimate_gas(fn_name, args.len());
+        let host = host_env.as_mut(&mut self.store);
+        let gas_used = gas_used.unwrap_or(self.context.gas_limit);
+        let output = std::mem::take(&mut host.output);
+        let logs = std::mem::take(&mut host.logs);
+        let host_calls = std::mem::take(&mut host.host_calls);
+        let memory = host.memory.clone();
+        let memory_usage = memory.map_or(0, |memory| memory.view(&self.store).data_size());
+        self.inspector.inspect(|inspector| inspector.on_call_exit(&frame, true, &output));
         
         Ok(ExecutionResult {
             return_value,
             gas_used,
             call_trace,
-            memory_usage: 1024, // Simplified
+            memory_usage,
+            output,
+            logs,
+            l1_cost,
+            console,
+            host_calls,
         })
     }
 
     pub fn profile_function(&mut self, fn_name: &str, args: &[i64]) -> Result<GasProfile> {
-        let result = self.execute_function(fn_name, args)?;
-        
-        Ok(GasProfile {
-            function_name: fn_name.to_string(),
-            gas_used: result.gas_used,
-            instruction_count: result.gas_used / 10, // Simplified mapping
-            call_depth: 1,
-        })
+        self.execute_with_profile(fn_name, args).map(|(_, profile)| profile)
+    }
+
+    /// Executes `fn_name` once in the interpreter, counting every instruction by opcode,
+    /// class and function along with the gas each was charged. The call reports the same gas
+    /// as `execute_function`, and is recorded and inspected as it would be.
+    pub fn execute_with_profile(&mut self, fn_name: &str, args: &[i64]) -> Result<(ExecutionResult, GasProfile)> {
+        self.ensure_callable(fn_name)?;
+        let l1_cost = self.estimate_l1_cost(&transaction_calldata(fn_name, args));
+        let tape = if self.recording_enabled { Tape::Recording(Trace::default()) } else { Tape::Off };
+        let snapshot = self.world().snapshot();
+        let (mut machine, args) = self.interpreter_call(fn_name, args, Vec::new(), tape)?;
+        let mut profiler = Profiler::default();
+        let outcome = profiler.run(&mut machine);
+        let profile = profiler.finish(fn_name, &machine);
+        if self.recording_enabled {
+            self.keep_recording(fn_name, args, &mut machine, &outcome);
+        }
+        let result = self.interpreter_result(fn_name, &mut machine, outcome, l1_cost, snapshot)?;
+        Ok((result, profile))
+    }
+
+    /// Every import the contract needs that the runtime cannot provide, found before linking.
+    pub fn missing_imports(&mut self) -> Result<Vec<MissingImport>> {
+        let frame = CallFrame::top_level(&self.context, Vec::new());
+        let env = self.host_env(frame);
+        let env = FunctionEnv::new(&mut self.store, env);
+        let import_object = host::link(&mut self.store, &self.module, &env)?;
+        Ok(imports::find_missing(&self.module, &import_object, &self.custom_imports))
     }
 
-    fn estimate_gas(&self, fn_name: &str, arg_count: usize) -> u64 {
-        let base_gas = self.gas_table.get("call").unwrap_or(&100);
-        base_gas + (arg_count as u64 * 10)
+    /// Pauses before the first instruction of `fn_name`, to be driven through the returned `Debugger`.
+    /// The call runs in the interpreter against the same world and host functions as `execute_function`.
+    pub fn debug(&mut self, fn_name: &str, args: &[i64]) -> Result<Debugger> {
+        self.ensure_callable(fn_name)?;
+        let (machine, _) = self.interpreter_call(fn_name, args, Vec::new(), Tape::Off)?;
+        Ok(Debugger::new(machine))
+    }
+
+    /// An interpreter instance paused before `fn_name`, with the arguments it was called with.
+    fn interpreter_call(&mut self, fn_name: &str, args: &[i64], calldata: Vec<u8>, tape: Tape) -> Result<(Machine, Vec<Val>)> {
+        let program = self.program()?;
+        let func = program
+            .export(fn_name)
+            .ok_or_else(|| anyhow!("Function '{}' not found", fn_name))?;
+        let params = &program.signature(func).params;
+        if params.len() != args.len() {
+            return Err(anyhow!("Function '{}' takes {} argument(s), got {}", fn_name, params.len(), args.len()));
+        }
+        let args: Vec<Val> = args.iter().zip(params).map(|(&arg, &ty)| Val::from_i64(arg, ty)).collect();
+
+        let frame = self.top_level_frame(calldata)?;
+        let mut machine = Machine::with_tape(program, self.symbols.clone(), self.host_env(frame), tape)?;
+        machine.call(func, &args)?;
+        self.inspector.inspect(|inspector| inspector.on_call_enter(&machine.env().frame));
+        Ok((machine, args))
+    }
+
+    /// The contract decoded for the interpreter, parsed on first use.
+    pub fn program(&mut self) -> Result<Arc<Program>> {
+        if let Some(program) = &self.program {
+            return Ok(program.clone());
+        }
+        let program = Arc::new(Program::parse(&self.wasm)?);
+        self.program = Some(program.clone());
+        Ok(program)
+    }
+
+    fn top_level_frame(&self, calldata: Vec<u8>) -> Result<CallFrame> {
+        let mut frame = CallFrame::top_level(&self.context, calldata);
+        if self.static_mode {
+            if frame.value > 0 {
+                return Err(StaticCallViolation {
+                    operation: StateChange::ValueTransfer,
+                    address: frame.address,
+                }
+                .into());
+            }
+            frame.is_static = true;
+        }
+        Ok(frame)
+    }
+
+    fn host_env(&self, frame: CallFrame) -> HostEnv {
+        let mut env = HostEnv::new(self.world.clone(), self.context.clone(), self.gas_table.clone(), frame);
+        env.stub_policy = self.stub_policy;
+        env.custom_imports = self.custom_imports.clone();
+        env.inspector = self.inspector.clone();
+        env.coverage = self.coverage.clone();
+        env
+    }
+
+    fn estimate_l1_cost(&self, calldata: &[u8]) -> L1Cost {
+        let mut world = self.world();
+        let l1_base_fee = world.precompiles.arb_gas_info.l1_base_fee_estimate;
+        let l1_cost = self.l1_pricing.estimate(calldata, l1_base_fee, self.context.block_basefee);
+        world.precompiles.arb_gas_info.current_tx_l1_fees = l1_cost.fee_wei;
+        l1_cost
     }
 
     pub fn enable_instrumentation(&mut self, enabled: bool) {
         self.instrumentation_enabled = enabled;
     }
+
+    /// Read-only mode: storage writes, logs, value transfers and contract creation
+    /// fail with `StaticCallViolation`, as they would inside a STATICCALL.
+    pub fn enable_static_mode(&mut self, enabled: bool) {
+        self.static_mode = enabled;
+    }
+
+    pub fn static_mode(&self) -> bool {
+        self.static_mode
+    }
+
+    /// Records every following `execute_function` call so it can be replayed with
+    /// `Recording::replay`. Recorded calls run in the interpreter and report the same gas as other calls.
+    pub fn enable_recording(&mut self, enabled: bool) {
+        self.recording_enabled = enabled;
+    }
+
+    /// Counts executed instructions and branch arms of every later call, which then runs
+    /// in the interpreter along with the calls it makes. Gas is reported, and the gas limit
+    /// enforced, exactly as without coverage.
+    /// Disabling coverage discards the counts.
+    pub fn enable_coverage(&mut self, enabled: bool) {
+        let code_hash = abi::keccak256(&self.wasm);
+        self.coverage = enabled.then(|| self.coverage.take().unwrap_or_else(|| Coverage::new(code_hash)));
+    }
+
+    /// Coverage of every call since it was enabled, or `None` if it is not.
+    pub fn coverage(&mut self) -> Option<CoverageReport> {
+        let coverage = self.coverage.clone()?;
+        let program = self.program().ok()?;
+        let report = coverage.report(&program, &self.symbols);
+        Some(report)
+    }
+
+    /// How much has been covered since coverage was enabled, for fuzzing feedback.
+    pub(crate) fn coverage_features(&self) -> usize {
+        self.coverage.as_ref().map_or(0, Coverage::features)
+    }
+
+    /// The recording of the last `execute_function` call, if recording was enabled.
+    pub fn take_recording(&mut self) -> Option<Recording> {
+        self.recording.take()
+    }
+
+    /// The constructor's execution result, if `deploy` ran one.
+    pub fn deployment(&self) -> Option<&ExecutionResult> {
+        self.deployment.as_ref()
+    }
+
+    fn has_export(&self, name: &str) -> bool {
+        self.module.exports().any(|export| export.name() == name)
+    }
+
+    /// Links an extra host import into every contract instance, including nested calls.
+    /// Each call adds `gas_cost` to the execution's gas.
+    pub fn register_host_function<F>(
+        &mut self,
+        module: &str,
+        name: &str,
+        params: &[Type],
+        results: &[Type],
+        gas_cost: u64,
+        handler: F,
+    ) where
+        F: Fn(&mut dyn GuestMemory, &[Value]) -> std::result::Result<Vec<Value>, RuntimeError> + Send + Sync + 'static,
+    {
+        Arc::make_mut(&mut self.custom_imports).push(CustomImport {
+            module: module.to_string(),
+            name: name.to_string(),
+            params: params.to_vec(),
+            results: results.to_vec(),
+            gas_cost,
+            handler: Arc::new(handler),
+        });
+    }
+
+    /// Sends every later call's events to `inspector`, replacing any previous one.
+    pub fn set_inspector(&mut self, inspector: Box<dyn Inspector>) {
+        self.inspector.replace(Some(inspector));
+    }
+
+    pub fn take_inspector(&mut self) -> Option<Box<dyn Inspector>> {
+        self.inspector.replace(None)
+    }
+
+    pub fn set_stub_policy(&mut self, policy: StubPolicy) {
+        self.stub_policy = policy;
+    }
+
+    pub fn world(&self) -> MutexGuard<'_, World> {
+        host::lock_world(&self.world)
+    }
+
+    pub fn symbols(&self) -> &Symbols {
+        &self.symbols
+    }
+
+    pub fn context(&self) -> &ExecutionContext {
+        &self.context
+    }
+
+    pub fn context_mut(&mut self) -> &mut ExecutionContext {
+        &mut self.context
+    }
+
+    pub fn l1_pricing_mut(&mut self) -> &mut L1Pricing {
+        &mut self.l1_pricing
+    }
+
+    pub fn set_l1_base_fee(&mut self, wei: u128) {
+        self.world().precompiles.arb_gas_info.l1_base_fee_estimate = wei;
+    }
+}
+
+/// Whether a call that returned `return_value` reverted: `user_entrypoint` signals a revert
+/// with a non-zero status, as a transaction would.
+fn reverted(fn_name: &str, return_value: i64) -> bool {
+    fn_name == "user_entrypoint" && return_value != 0
+}
+
+/// The calldata a transaction invoking `fn_name(int64, ...)` would carry.
+fn transaction_calldata(fn_name: &str, args: &[i64]) -> Vec<u8> {
+    let signature = format!("{}({})", fn_name, vec!["int64"; args.len()].join(","));
+    let mut calldata = abi::selector(&signature).to_vec();
+    for &arg in args {
+        let mut word = if arg < 0 { [0xffu8; 32] } else { [0u8; 32] };
+        word[24..].copy_from_slice(&arg.to_be_bytes());
+        calldata.extend_from_slice(&word);
+    }
+    calldata
 }
 
 #[cfg(test)]
@@ -123,4 +682,192 @@ mod tests {
         let runtime = StylusRuntime::new(&wasm);
         assert!(runtime.is_ok());
     }
+
+    #[test]
+    fn test_custom_host_function() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (import "debug" "sum_bytes" (func $sum (param i32 i32) (result i64)))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "\01\02\03")
+                (func (export "sum") (result i64)
+                    (call $sum (i32.const 0) (i32.const 3)))
+            )
+        "#).unwrap();
+        
+        let mut runtime = StylusRuntime::new(&wasm).unwrap();
+        // Two constants, the call and the closing `end`, metered from the gas table.
+        let baseline = 2 + runtime.gas_table["call"] + 1;
+        runtime.register_host_function("debug", "sum_bytes", &[Type::I32, Type::I32], &[Type::I64], 25, |memory, args| {
+            let bytes = memory.read(args[0].unwrap_i32() as u32, args[1].unwrap_i32() as usize)?;
+            Ok(vec![Value::I64(bytes.iter().map(|&b| b as i64).sum())])
+        });
+        
+        let result = runtime.execute_function("sum", &[]).unwrap();
+        assert_eq!(result.return_value, 6);
+        assert_eq!(result.gas_used, baseline + 25);
+    }
+
+    #[test]
+    fn test_console_output_is_captured() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (import "console" "log_txt" (func $log_txt (param i32 i32)))
+                (import "console" "tee_i64" (func $tee_i64 (param i64) (result i64)))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "hello\nworld")
+                (func (export "greet") (result i64)
+                    (call $log_txt (i32.const 0) (i32.const 11))
+                    (call $tee_i64 (i64.const 42)))
+                (func (export "fail") (result i64)
+                    (call $log_txt (i32.const 0) (i32.const 5))
+                    unreachable)
+            )
+        "#).unwrap();
+        
+        let mut runtime = StylusRuntime::new(&wasm).unwrap();
+        let result = runtime.execute_function("greet", &[]).unwrap();
+        assert_eq!(result.return_value, 42);
+        let text: Vec<_> = result.console.iter().map(|line| line.text.as_str()).collect();
+        assert_eq!(text, ["hello", "world", "42"]);
+        assert!(result.console.iter().all(|line| line.stream == ConsoleStream::Log));
+        
+        let err = runtime.execute_function("fail", &[]).unwrap_err();
+        let trap = err.downcast_ref::<Trap>().unwrap();
+        assert_eq!(trap.console.len(), 1);
+        assert_eq!(trap.console[0].to_string(), "[console] hello");
+    }
+
+    #[test]
+    fn test_contract_panic_is_parsed_from_the_trap() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (import "console" "log_txt" (func $log_txt (param i32 i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "panicked at src/lib.rs:17:5:\ninsufficient funds")
+                (func (export "withdraw") (result i64)
+                    (call $log_txt (i32.const 0) (i32.const 47))
+                    unreachable)
+            )
+        "#).unwrap();
+
+        let mut runtime = StylusRuntime::new(&wasm).unwrap();
+        let err = runtime.execute_function("withdraw", &[]).unwrap_err();
+        let panic = err.downcast_ref::<Trap>().unwrap().panic.clone().unwrap();
+        assert_eq!(panic.message, "insufficient funds");
+        assert_eq!(panic.file.as_deref(), Some("src/lib.rs"));
+        assert_eq!((panic.line, panic.column), (Some(17), Some(5)));
+    }
+
+    #[test]
+    fn test_deploy_runs_constructor_once() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (import "vm_hooks" "read_args" (func $read_args (param i32)))
+                (import "vm_hooks" "storage_store_bytes32" (func $store (param i32 i32)))
+                (memory (export "memory") 1)
+                (func (export "constructor")
+                    (call $read_args (i32.const 32))
+                    (call $store (i32.const 0) (i32.const 32)))
+                (func (export "noop") (result i64)
+                    i64.const 0)
+            )
+        "#).unwrap();
+        
+        let ctx = ExecutionContext::default();
+        let init = abi::encode_u64(42);
+        let mut runtime = StylusRuntime::deploy(&wasm, &init, ctx.clone()).unwrap();
+        
+        assert_eq!(runtime.world().storage_load(&ctx.contract_address, &[0u8; 32]), init);
+        assert!(runtime.execute_function("noop", &[]).is_ok());
+        assert!(runtime.execute_function("constructor", &[]).is_err());
+        assert!(runtime.debug("constructor", &[]).is_err());
+        assert!(runtime.execute_with_profile("constructor", &[]).is_err());
+        assert_eq!(runtime.world().storage_load(&ctx.contract_address, &[0u8; 32]), init);
+    }
+
+    #[test]
+    fn test_deploy_moves_value_and_rolls_back_failed_constructors() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (import "vm_hooks" "read_args" (func $read_args (param i32)))
+                (import "vm_hooks" "storage_store_bytes32" (func $store (param i32 i32)))
+                (memory (export "memory") 1)
+                (func (export "constructor") (result i32)
+                    (call $read_args (i32.const 32))
+                    (call $store (i32.const 0) (i32.const 32))
+                    (i32.load8_u (i32.const 63)))
+            )
+        "#).unwrap();
+
+        let ctx = ExecutionContext { value: 50, ..ExecutionContext::default() };
+        let unfunded = StylusRuntime::deploy(&wasm, &abi::encode_u64(256), ctx.clone());
+        assert!(unfunded.err().unwrap().to_string().contains("Insufficient balance"));
+
+        let mut runtime = StylusRuntime::new(&wasm).unwrap();
+        *runtime.context_mut() = ctx.clone();
+        runtime.world().set_balance(&ctx.sender, 80);
+        let reverted = runtime.construct(&abi::encode_u64(1)).unwrap_err();
+        assert!(reverted.to_string().contains("Constructor reverted"));
+        assert_eq!(runtime.world().balance(&ctx.sender), 80);
+        assert_eq!(runtime.world().storage_load(&ctx.contract_address, &[0u8; 32]), [0u8; 32]);
+
+        runtime.construct(&abi::encode_u64(256)).unwrap();
+        let world = runtime.world();
+        assert_eq!((world.balance(&ctx.sender), world.balance(&ctx.contract_address)), (30, 50));
+        assert_eq!(world.storage_load(&ctx.contract_address, &[0u8; 32]), abi::encode_u64(256));
+    }
+
+    #[test]
+    fn test_reverted_and_trapped_calls_roll_back_state() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (import "vm_hooks" "storage_store_bytes32" (func $store (param i32 i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 32) "\01")
+                (func (export "user_entrypoint") (param i32) (result i32)
+                    (call $store (i32.const 0) (i32.const 32))
+                    (local.get 0))
+                (func (export "store_and_trap")
+                    (call $store (i32.const 0) (i32.const 32))
+                    unreachable)
+            )
+        "#).unwrap();
+
+        let mut runtime = StylusRuntime::new(&wasm).unwrap();
+        let address = runtime.context().contract_address;
+        for recording in [false, true] {
+            runtime.enable_recording(recording);
+            assert_eq!(runtime.execute_calldata(&[0]).unwrap().return_value, 1);
+            assert_eq!(runtime.world().storage_load(&address, &[0u8; 32]), [0u8; 32]);
+            assert!(runtime.execute_function("store_and_trap", &[]).is_err());
+            assert_eq!(runtime.world().storage_load(&address, &[0u8; 32]), [0u8; 32]);
+        }
+
+        assert_eq!(runtime.execute_calldata(&[]).unwrap().return_value, 0);
+        let mut stored = [0u8; 32];
+        stored[0] = 1;
+        assert_eq!(runtime.world().storage_load(&address, &[0u8; 32]), stored);
+    }
+
+    #[test]
+    fn test_construct_without_constructor_moves_value_once() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (func (export "noop") (result i64)
+                    i64.const 0)
+            )
+        "#).unwrap();
+
+        let ctx = ExecutionContext { value: 50, ..ExecutionContext::default() };
+        let mut runtime = StylusRuntime::new(&wasm).unwrap();
+        *runtime.context_mut() = ctx.clone();
+        runtime.world().set_balance(&ctx.sender, 80);
+        runtime.construct(&[]).unwrap();
+        assert!(runtime.construct(&[]).unwrap_err().to_string().contains("already run"));
+
+        let world = runtime.world();
+        assert_eq!((world.balance(&ctx.sender), world.balance(&ctx.contract_address)), (30, 50));
+        assert_eq!(world.account(&ctx.sender).map(|account| account.nonce), Some(1));
+    }
 }
\ No newline at end of file
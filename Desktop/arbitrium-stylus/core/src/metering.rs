@@ -0,0 +1,89 @@
+//! Instruction gas for contracts run by wasmer, charged from the same gas table as the interpreter.
+//!
+//! The metering middleware charges each straight-line run of instructions when it reaches a
+//! branch, call, `loop`, `if`, `else` or `end`, and traps if the run does not fit in the gas
+//! left. The interpreter checks its limit at the same instructions, so both engines report
+//! the same gas and run out of it at the same point.
+
+use crate::host::HostEnv;
+use std::collections::HashMap;
+use std::sync::Arc;
+use wasmer::wasmparser::Operator;
+use wasmer::{AsStoreMut, CompilerConfig, Cranelift, Engine, EngineBuilder, Instance};
+use wasmer_middlewares::metering::{get_remaining_points, set_remaining_points, MeteringPoints};
+use wasmer_middlewares::Metering;
+
+/// An engine that meters the one module compiled with it. The middleware keeps per-module
+/// state, so every contract gets its own.
+pub(crate) fn engine(gas_table: &HashMap<String, u64>) -> Engine {
+    let gas_table = gas_table.clone();
+    let metering = Metering::new(u64::MAX, move |operator: &Operator| {
+        gas_table.get(&gas_key(operator)).copied().unwrap_or(1)
+    });
+    let mut compiler = Cranelift::default();
+    compiler.push_middleware(Arc::new(metering));
+    EngineBuilder::new(compiler).engine()
+}
+
+/// The gas table key of an operator, named as the interpreter names the instruction:
+/// `I64TruncSatF32S` is charged as `i64_trunc_sat_f32_s`, like `i64.trunc_sat_f32_s`.
+fn gas_key(operator: &Operator) -> String {
+    let debug = format!("{:?}", operator);
+    let variant = debug.split(|c: char| !c.is_ascii_alphanumeric()).next().unwrap_or_default();
+    if variant == "TypedSelect" {
+        return "select".to_string();
+    }
+    let mut key = String::with_capacity(variant.len() + 4);
+    for (i, c) in variant.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                key.push('_');
+            }
+            key.push(c.to_ascii_lowercase());
+        } else {
+            key.push(c);
+        }
+    }
+    key
+}
+
+/// Gas the instance has used out of `gas_limit`, host charges included,
+/// or `None` if it ran out.
+pub(crate) fn gas_used(store: &mut impl AsStoreMut, instance: &Instance, gas_limit: u64) -> Option<u64> {
+    match get_remaining_points(store, instance) {
+        MeteringPoints::Remaining(points) => Some(gas_limit.saturating_sub(points)),
+        MeteringPoints::Exhausted => None,
+    }
+}
+
+/// Starts the instance's metering from `gas_limit`, after its start function has run.
+pub(crate) fn reset(store: &mut impl AsStoreMut, instance: &Instance, gas_limit: u64) {
+    set_remaining_points(store, instance, gas_limit);
+}
+
+/// Brings the instruction gas metered so far into `env` before a host call.
+pub(crate) fn load(env: &mut HostEnv, store: &mut impl AsStoreMut) {
+    let Some(instance) = &env.instance else { return };
+    let used = gas_used(store, instance, env.context.gas_limit).unwrap_or(env.context.gas_limit);
+    env.instruction_gas = used.saturating_sub(env.host_gas);
+}
+
+/// Leaves the instance only the gas `env` has left once a host call has charged its own.
+pub(crate) fn store(env: &HostEnv, store: &mut impl AsStoreMut) {
+    if let Some(instance) = &env.instance {
+        set_remaining_points(store, instance, env.gas_left());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gas_keys_match_interpreter_mnemonics() {
+        assert_eq!(gas_key(&Operator::Call { function_index: 3 }), "call");
+        assert_eq!(gas_key(&Operator::LocalGet { local_index: 0 }), "local_get");
+        assert_eq!(gas_key(&Operator::I64TruncSatF32S), "i64_trunc_sat_f32_s");
+        assert_eq!(gas_key(&Operator::I32Const { value: -1 }), "i32_const");
+    }
+}
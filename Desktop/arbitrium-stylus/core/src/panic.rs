@@ -0,0 +1,116 @@
+use crate::console::ConsoleLine;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A Rust panic reported by a contract before it trapped.
+///
+/// Contracts built with std print the panic to stderr and `no_std` contracts with a
+/// debug panic handler print it through the `console` import, so both are recovered
+/// from the captured console output.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContractPanic {
+    pub message: String,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+}
+
+impl fmt::Display for ContractPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.file, self.line, self.column) {
+            (Some(file), Some(line), Some(column)) => {
+                write!(f, "panicked at {}:{}:{}: {}", file, line, column, self.message)
+            }
+            (Some(file), Some(line), None) => write!(f, "panicked at {}:{}: {}", file, line, self.message),
+            (Some(file), None, _) => write!(f, "panicked at {}: {}", file, self.message),
+            _ => write!(f, "panicked: {}", self.message),
+        }
+    }
+}
+
+const MARKER: &str = "panicked at ";
+
+/// Finds the last panic printed to the console, in either the current
+/// (`panicked at file:line:col:` followed by the message) or the pre-1.73
+/// (`panicked at 'message', file:line:col`) format.
+pub fn find_panic(console: &[ConsoleLine]) -> Option<ContractPanic> {
+    console.iter().enumerate().rev().find_map(|(i, line)| {
+        let start = line.text.find(MARKER)? + MARKER.len();
+        let rest = line.text[start..].trim_end();
+
+        if let Some((message, location)) = rest.strip_prefix('\'').and_then(|r| r.rsplit_once("', ")) {
+            return Some(with_location(message.to_string(), location));
+        }
+
+        if let Some(location) = rest.strip_suffix(':') {
+            let message = console[i + 1..]
+                .iter()
+                .take_while(|next| next.stream == line.stream && !next.text.starts_with("note: "))
+                .map(|next| next.text.as_str())
+                .collect::<Vec<_>>()
+                .join("\n");
+            return Some(with_location(message, location));
+        }
+
+        match rest.split_once(": ") {
+            Some((location, message)) => Some(with_location(message.to_string(), location)),
+            None => Some(with_location(String::new(), rest)),
+        }
+    })
+}
+
+fn with_location(message: String, location: &str) -> ContractPanic {
+    let mut parts = location.rsplitn(3, ':');
+    let (column, line, file) = (parts.next(), parts.next(), parts.next());
+    match (file, line.and_then(|l| l.parse().ok()), column.and_then(|c| c.parse().ok())) {
+        (Some(file), Some(line), Some(column)) => ContractPanic {
+            message,
+            file: Some(file.to_string()),
+            line: Some(line),
+            column: Some(column),
+        },
+        _ => ContractPanic {
+            message,
+            file: Some(location.to_string()).filter(|l| !l.is_empty()),
+            line: None,
+            column: None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::ConsoleStream;
+
+    fn lines(stream: ConsoleStream, text: &str) -> Vec<ConsoleLine> {
+        text.lines()
+            .map(|line| ConsoleLine {
+                stream,
+                address: [0u8; 20],
+                text: line.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_panic_formats() {
+        let current = lines(
+            ConsoleStream::Stderr,
+            "thread 'main' panicked at src/lib.rs:42:9:\nbalance underflow\nnote: run with `RUST_BACKTRACE=1`",
+        );
+        let panic = find_panic(&current).unwrap();
+        assert_eq!(panic.message, "balance underflow");
+        assert_eq!(panic.file.as_deref(), Some("src/lib.rs"));
+        assert_eq!((panic.line, panic.column), (Some(42), Some(9)));
+        assert_eq!(panic.to_string(), "panicked at src/lib.rs:42:9: balance underflow");
+
+        let legacy = lines(ConsoleStream::Log, "panicked at 'index out of bounds', src/vault.rs:7:13");
+        let panic = find_panic(&legacy).unwrap();
+        assert_eq!(panic.message, "index out of bounds");
+        assert_eq!(panic.file.as_deref(), Some("src/vault.rs"));
+        assert_eq!(panic.line, Some(7));
+
+        assert!(find_panic(&lines(ConsoleStream::Stdout, "all good")).is_none());
+    }
+}
@@ -0,0 +1,408 @@
+use crate::abi;
+use crate::fees::L1_GAS_PER_BYTE;
+use crate::world::{address_from_u64, Account, Address, Bytes32};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub const ARB_SYS: Address = address_from_u64(0x64);
+pub const ARB_GAS_INFO: Address = address_from_u64(0x6c);
+pub const ARB_WASM: Address = address_from_u64(0x71);
+
+// ArbSys
+const ARB_BLOCK_NUMBER: [u8; 4] = [0xa3, 0xb1, 0xb3, 0x1d];
+const ARB_BLOCK_HASH: [u8; 4] = [0x2b, 0x40, 0x7a, 0x82];
+const ARB_CHAIN_ID: [u8; 4] = [0xd1, 0x27, 0xf5, 0x4a];
+const ARB_OS_VERSION: [u8; 4] = [0x05, 0x10, 0x38, 0xf2];
+const GET_STORAGE_GAS_AVAILABLE: [u8; 4] = [0xa9, 0x45, 0x97, 0xff];
+const IS_TOP_LEVEL_CALL: [u8; 4] = [0x08, 0xbd, 0x62, 0x4c];
+const SEND_TX_TO_L1: [u8; 4] = [0x92, 0x8c, 0x16, 0x9a];
+const WITHDRAW_ETH: [u8; 4] = [0x25, 0xe1, 0x60, 0x63];
+const WAS_MY_CALLERS_ADDRESS_ALIASED: [u8; 4] = [0x17, 0x5a, 0x26, 0x0b];
+const MY_CALLERS_ADDRESS_WITHOUT_ALIASING: [u8; 4] = [0xd7, 0x45, 0x23, 0xb3];
+
+// ArbGasInfo
+const GET_PRICES_IN_WEI: [u8; 4] = [0x41, 0xb2, 0x47, 0xa8];
+const GET_PRICES_IN_ARB_GAS: [u8; 4] = [0x02, 0x19, 0x9f, 0x34];
+const GET_L1_BASE_FEE_ESTIMATE: [u8; 4] = [0xf5, 0xd6, 0xde, 0xd7];
+const GET_L1_GAS_PRICE_ESTIMATE: [u8; 4] = [0x05, 0x5f, 0x36, 0x2f];
+const GET_MINIMUM_GAS_PRICE: [u8; 4] = [0xf9, 0x18, 0x37, 0x9a];
+const GET_GAS_BACKLOG: [u8; 4] = [0x1d, 0x5b, 0x5c, 0x20];
+const GET_PRICING_INERTIA: [u8; 4] = [0x3d, 0xfb, 0x45, 0xb9];
+const GET_CURRENT_TX_L1_GAS_FEES: [u8; 4] = [0xc6, 0xf7, 0xde, 0x0e];
+const GET_L1_REWARD_RATE: [u8; 4] = [0x8a, 0x5b, 0x1d, 0x28];
+const GET_GAS_ACCOUNTING_PARAMS: [u8; 4] = [0x61, 0x2a, 0xf1, 0x78];
+
+// ArbWasm
+const STYLUS_VERSION: [u8; 4] = [0xa9, 0x96, 0xe0, 0xc2];
+const INK_PRICE: [u8; 4] = [0xd1, 0xc1, 0x7a, 0xbc];
+const MAX_STACK_DEPTH: [u8; 4] = [0x8c, 0xcf, 0xaa, 0x70];
+const FREE_PAGES: [u8; 4] = [0x44, 0x90, 0xc1, 0x9d];
+const PAGE_GAS: [u8; 4] = [0x7a, 0xf4, 0xba, 0x49];
+const PAGE_LIMIT: [u8; 4] = [0x97, 0x86, 0xf9, 0x6e];
+const EXPIRY_DAYS: [u8; 4] = [0x30, 0x9f, 0x65, 0x55];
+const KEEPALIVE_DAYS: [u8; 4] = [0x0a, 0x93, 0x64, 0x55];
+const BLOCK_CACHE_SIZE: [u8; 4] = [0x7a, 0xf6, 0xe8, 0x19];
+const CODEHASH_VERSION: [u8; 4] = [0xd7, 0x0c, 0x0c, 0xa7];
+const PROGRAM_VERSION: [u8; 4] = [0xcc, 0x8f, 0x4e, 0x88];
+const ACTIVATE_PROGRAM: [u8; 4] = [0x58, 0xc7, 0x80, 0xc2];
+
+/// Signatures of the methods above, for decoding calls to them in traces.
+pub const SIGNATURES: &[&str] = &[
+    "arbBlockNumber()",
+    "arbBlockHash(uint256)",
+    "arbChainID()",
+    "arbOSVersion()",
+    "getStorageGasAvailable()",
+    "isTopLevelCall()",
+    "sendTxToL1(address,bytes)",
+    "withdrawEth(address)",
+    "wasMyCallersAddressAliased()",
+    "myCallersAddressWithoutAliasing()",
+    "getPricesInWei()",
+    "getPricesInArbGas()",
+    "getL1BaseFeeEstimate()",
+    "getL1GasPriceEstimate()",
+    "getMinimumGasPrice()",
+    "getGasBacklog()",
+    "getPricingInertia()",
+    "getCurrentTxL1GasFees()",
+    "getL1RewardRate()",
+    "getGasAccountingParams()",
+    "stylusVersion()",
+    "inkPrice()",
+    "maxStackDepth()",
+    "freePages()",
+    "pageGas()",
+    "pageLimit()",
+    "expiryDays()",
+    "keepaliveDays()",
+    "blockCacheSize()",
+    "codehashVersion(bytes32)",
+    "programVersion(address)",
+    "activateProgram(address)",
+];
+
+/// Gas Nitro charges for allocating a new storage slot.
+const STORAGE_WRITE_COST: u128 = 20_000;
+/// Size Nitro assumes for a simple transaction when pricing L1 posting.
+const ASSUMED_SIMPLE_TX_SIZE: u128 = 140;
+
+pub type PrecompileResult = Result<Vec<u8>, Vec<u8>>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct L2ToL1Message {
+    pub id: u64,
+    pub sender: Address,
+    pub destination: Address,
+    pub value: u128,
+    pub data: Vec<u8>,
+    pub l2_block: u64,
+    pub timestamp: u64,
+}
+
+pub struct PrecompileCall<'a> {
+    pub caller: Address,
+    pub calldata: &'a [u8],
+    pub value: u128,
+    pub depth: u32,
+    pub timestamp: u64,
+    pub is_static: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ArbSys {
+    pub block_number: u64,
+    pub chain_id: u64,
+    /// ArbOS version; `arbOSVersion()` reports it offset by 55 like Nitro does.
+    pub arbos_version: u64,
+    pub storage_gas_available: u64,
+    pub block_hashes: HashMap<u64, Bytes32>,
+    pub outbox: Vec<L2ToL1Message>,
+}
+
+impl Default for ArbSys {
+    fn default() -> Self {
+        Self {
+            block_number: 1,
+            chain_id: 421614,
+            arbos_version: 31,
+            storage_gas_available: 0,
+            block_hashes: HashMap::new(),
+            outbox: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ArbGasInfo {
+    pub l2_base_fee: u128,
+    pub minimum_gas_price: u128,
+    pub l1_base_fee_estimate: u128,
+    pub gas_backlog: u64,
+    pub pricing_inertia: u64,
+    pub l1_reward_rate: u64,
+    pub current_tx_l1_fees: u128,
+    pub speed_limit_per_second: u64,
+    pub gas_pool_max: u64,
+    pub max_tx_gas_limit: u64,
+}
+
+impl Default for ArbGasInfo {
+    fn default() -> Self {
+        Self {
+            l2_base_fee: 100_000_000,
+            minimum_gas_price: 10_000_000,
+            l1_base_fee_estimate: 1_000_000_000,
+            gas_backlog: 0,
+            pricing_inertia: 102,
+            l1_reward_rate: 0,
+            current_tx_l1_fees: 0,
+            speed_limit_per_second: 7_000_000,
+            gas_pool_max: 32_000_000,
+            max_tx_gas_limit: 32_000_000,
+        }
+    }
+}
+
+impl ArbGasInfo {
+    pub fn l1_price_per_byte(&self) -> u128 {
+        self.l1_base_fee_estimate * L1_GAS_PER_BYTE as u128
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ArbWasm {
+    pub stylus_version: u16,
+    pub ink_price: u32,
+    pub max_stack_depth: u32,
+    pub free_pages: u16,
+    pub page_gas: u16,
+    pub page_limit: u16,
+    pub expiry_days: u16,
+    pub keepalive_days: u16,
+    pub block_cache_size: u16,
+    pub activation_data_fee: u128,
+}
+
+impl Default for ArbWasm {
+    fn default() -> Self {
+        Self {
+            stylus_version: 1,
+            ink_price: 10_000,
+            max_stack_depth: 262_144,
+            free_pages: 2,
+            page_gas: 1_000,
+            page_limit: 128,
+            expiry_days: 365,
+            keepalive_days: 31,
+            block_cache_size: 32,
+            activation_data_fee: 0,
+        }
+    }
+}
+
+/// Local stand-ins for the Arbitrum precompiles contracts call most often.
+#[derive(Debug, Clone, Default)]
+pub struct Precompiles {
+    pub arb_sys: ArbSys,
+    pub arb_gas_info: ArbGasInfo,
+    pub arb_wasm: ArbWasm,
+}
+
+impl Precompiles {
+    pub fn is_precompile(address: &Address) -> bool {
+        [ARB_SYS, ARB_GAS_INFO, ARB_WASM].contains(address)
+    }
+
+    pub(crate) fn call(
+        &mut self,
+        address: &Address,
+        call: PrecompileCall,
+        accounts: &HashMap<Address, Account>,
+    ) -> PrecompileResult {
+        if call.calldata.len() < 4 {
+            return Err(Vec::new());
+        }
+        let selector: [u8; 4] = call.calldata[..4].try_into().unwrap();
+        let args = &call.calldata[4..];
+
+        if *address == ARB_SYS {
+            self.call_arb_sys(selector, args, &call)
+        } else if *address == ARB_GAS_INFO {
+            self.call_arb_gas_info(selector)
+        } else if *address == ARB_WASM {
+            self.call_arb_wasm(selector, args, accounts)
+        } else {
+            Err(Vec::new())
+        }
+    }
+
+    fn call_arb_sys(&mut self, selector: [u8; 4], args: &[u8], call: &PrecompileCall) -> PrecompileResult {
+        let sys = &mut self.arb_sys;
+        let words = match selector {
+            ARB_BLOCK_NUMBER => vec![abi::encode_u64(sys.block_number)],
+            ARB_BLOCK_HASH => {
+                let number = abi::decode_u64(args, 0).ok_or_else(|| abi::encode_revert("invalid block number"))?;
+                if number >= sys.block_number || number + 256 < sys.block_number {
+                    return Err(abi::encode_revert("invalid block number for ArbBlockHash"));
+                }
+                let hash = sys
+                    .block_hashes
+                    .get(&number)
+                    .copied()
+                    .unwrap_or_else(|| abi::keccak256(&number.to_be_bytes()));
+                vec![hash]
+            }
+            ARB_CHAIN_ID => vec![abi::encode_u64(sys.chain_id)],
+            ARB_OS_VERSION => vec![abi::encode_u64(55 + sys.arbos_version)],
+            GET_STORAGE_GAS_AVAILABLE => vec![abi::encode_u64(sys.storage_gas_available)],
+            IS_TOP_LEVEL_CALL => vec![abi::encode_bool(call.depth <= 1)],
+            WAS_MY_CALLERS_ADDRESS_ALIASED => vec![abi::encode_bool(false)],
+            MY_CALLERS_ADDRESS_WITHOUT_ALIASING => vec![abi::encode_address(&call.caller)],
+            SEND_TX_TO_L1 | WITHDRAW_ETH => {
+                if call.is_static {
+                    return Err(abi::encode_revert("Static call violation: ArbSys outbox write in read-only mode"));
+                }
+                let destination = abi::decode_address(args, 0).ok_or_else(|| abi::encode_revert("invalid destination"))?;
+                let data = if selector == SEND_TX_TO_L1 {
+                    abi::decode_bytes(args, 1).ok_or_else(|| abi::encode_revert("invalid calldata"))?
+                } else {
+                    Vec::new()
+                };
+                let id = sys.outbox.len() as u64;
+                sys.outbox.push(L2ToL1Message {
+                    id,
+                    sender: call.caller,
+                    destination,
+                    value: call.value,
+                    data,
+                    l2_block: sys.block_number,
+                    timestamp: call.timestamp,
+                });
+                vec![abi::encode_u64(id)]
+            }
+            _ => return Err(abi::encode_revert("ArbSys: unsupported method")),
+        };
+        Ok(abi::encode_words(&words))
+    }
+
+    fn call_arb_gas_info(&mut self, selector: [u8; 4]) -> PrecompileResult {
+        let info = &self.arb_gas_info;
+        let per_byte = info.l1_price_per_byte();
+        let per_l2_tx = per_byte * ASSUMED_SIMPLE_TX_SIZE;
+        let l2_price = info.l2_base_fee.max(1);
+        let words = match selector {
+            GET_PRICES_IN_WEI => vec![
+                abi::encode_u128(per_l2_tx),
+                abi::encode_u128(per_byte),
+                abi::encode_u128(info.l2_base_fee * STORAGE_WRITE_COST),
+                abi::encode_u128(info.minimum_gas_price),
+                abi::encode_u128(info.l2_base_fee.saturating_sub(info.minimum_gas_price)),
+                abi::encode_u128(info.l2_base_fee),
+            ],
+            GET_PRICES_IN_ARB_GAS => vec![
+                abi::encode_u128(per_l2_tx / l2_price),
+                abi::encode_u128(per_byte / l2_price),
+                abi::encode_u128(STORAGE_WRITE_COST),
+            ],
+            GET_L1_BASE_FEE_ESTIMATE | GET_L1_GAS_PRICE_ESTIMATE => vec![abi::encode_u128(info.l1_base_fee_estimate)],
+            GET_MINIMUM_GAS_PRICE => vec![abi::encode_u128(info.minimum_gas_price)],
+            GET_GAS_BACKLOG => vec![abi::encode_u64(info.gas_backlog)],
+            GET_PRICING_INERTIA => vec![abi::encode_u64(info.pricing_inertia)],
+            GET_L1_REWARD_RATE => vec![abi::encode_u64(info.l1_reward_rate)],
+            GET_CURRENT_TX_L1_GAS_FEES => vec![abi::encode_u128(info.current_tx_l1_fees)],
+            GET_GAS_ACCOUNTING_PARAMS => vec![
+                abi::encode_u64(info.speed_limit_per_second),
+                abi::encode_u64(info.gas_pool_max),
+                abi::encode_u64(info.max_tx_gas_limit),
+            ],
+            _ => return Err(abi::encode_revert("ArbGasInfo: unsupported method")),
+        };
+        Ok(abi::encode_words(&words))
+    }
+
+    fn call_arb_wasm(
+        &mut self,
+        selector: [u8; 4],
+        args: &[u8],
+        accounts: &HashMap<Address, Account>,
+    ) -> PrecompileResult {
+        let wasm = &self.arb_wasm;
+        let not_activated = || abi::encode_revert("ProgramNotActivated");
+        let words = match selector {
+            STYLUS_VERSION => vec![abi::encode_u64(wasm.stylus_version as u64)],
+            INK_PRICE => vec![abi::encode_u64(wasm.ink_price as u64)],
+            MAX_STACK_DEPTH => vec![abi::encode_u64(wasm.max_stack_depth as u64)],
+            FREE_PAGES => vec![abi::encode_u64(wasm.free_pages as u64)],
+            PAGE_GAS => vec![abi::encode_u64(wasm.page_gas as u64)],
+            PAGE_LIMIT => vec![abi::encode_u64(wasm.page_limit as u64)],
+            EXPIRY_DAYS => vec![abi::encode_u64(wasm.expiry_days as u64)],
+            KEEPALIVE_DAYS => vec![abi::encode_u64(wasm.keepalive_days as u64)],
+            BLOCK_CACHE_SIZE => vec![abi::encode_u64(wasm.block_cache_size as u64)],
+            CODEHASH_VERSION => {
+                let hash = abi::word(args, 0).ok_or_else(not_activated)?;
+                let known = accounts
+                    .values()
+                    .filter_map(|a| a.contract.as_ref())
+                    .any(|c| c.code_hash == hash);
+                if !known {
+                    return Err(not_activated());
+                }
+                vec![abi::encode_u64(wasm.stylus_version as u64)]
+            }
+            PROGRAM_VERSION | ACTIVATE_PROGRAM => {
+                let program = abi::decode_address(args, 0).ok_or_else(not_activated)?;
+                if accounts.get(&program).and_then(|a| a.contract.as_ref()).is_none() {
+                    return Err(not_activated());
+                }
+                let mut words = vec![abi::encode_u64(wasm.stylus_version as u64)];
+                if selector == ACTIVATE_PROGRAM {
+                    words.push(abi::encode_u128(wasm.activation_data_fee));
+                }
+                words
+            }
+            _ => return Err(abi::encode_revert("ArbWasm: unsupported method")),
+        };
+        Ok(abi::encode_words(&words))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arb_sys_block_number_and_outbox() {
+        let mut precompiles = Precompiles::default();
+        precompiles.arb_sys.block_number = 42;
+        let accounts = HashMap::new();
+        let caller = address_from_u64(0x1000);
+
+        let call = PrecompileCall { caller, calldata: &ARB_BLOCK_NUMBER, value: 0, depth: 1, timestamp: 0, is_static: false };
+        let result = precompiles.call(&ARB_SYS, call, &accounts).unwrap();
+        assert_eq!(abi::decode_u64(&result, 0), Some(42));
+
+        let destination = address_from_u64(0xbeef);
+        let mut calldata = SEND_TX_TO_L1.to_vec();
+        calldata.extend_from_slice(&abi::encode_address(&destination));
+        calldata.extend_from_slice(&abi::encode_u64(64));
+        calldata.extend_from_slice(&abi::encode_bytes(b"hello l1")[32..]);
+        let call = PrecompileCall { caller, calldata: &calldata, value: 7, depth: 1, timestamp: 99, is_static: false };
+        precompiles.call(&ARB_SYS, call, &accounts).unwrap();
+
+        let message = &precompiles.arb_sys.outbox[0];
+        assert_eq!(message.sender, caller);
+        assert_eq!(message.destination, destination);
+        assert_eq!(message.value, 7);
+        assert_eq!(message.data, b"hello l1");
+        assert_eq!(message.l2_block, 42);
+    }
+
+    #[test]
+    fn test_signatures_match_selectors() {
+        let selectors: Vec<[u8; 4]> = SIGNATURES.iter().map(|signature| abi::selector(signature)).collect();
+        for selector in [ARB_BLOCK_NUMBER, SEND_TX_TO_L1, GET_PRICES_IN_WEI, CODEHASH_VERSION, ACTIVATE_PROGRAM] {
+            assert!(selectors.contains(&selector));
+        }
+    }
+}
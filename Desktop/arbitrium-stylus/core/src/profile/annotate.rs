@@ -0,0 +1,108 @@
+//! Per-line gas grouped by source file, for annotated listings and editor gutters.
+
+use super::GasProfile;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnnotatedLine {
+    pub line: u32,
+    pub count: u64,
+    pub gas: u64,
+    /// Share of the call's total gas.
+    pub percent: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnnotatedFile {
+    pub path: String,
+    pub gas: u64,
+    pub percent: f64,
+    /// Lines that executed, in line order.
+    pub lines: Vec<AnnotatedLine>,
+}
+
+/// The source-line view of a profile, most expensive file first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceAnnotation {
+    pub function_name: String,
+    pub gas_used: u64,
+    /// Gas spent in code without line info, such as the standard library built without debug info.
+    pub unattributed_gas: u64,
+    pub files: Vec<AnnotatedFile>,
+}
+
+impl SourceAnnotation {
+    pub fn new(profile: &GasProfile) -> Self {
+        let percent = |gas: u64| {
+            if profile.gas_used == 0 { 0.0 } else { gas as f64 * 100.0 / profile.gas_used as f64 }
+        };
+
+        let mut files: Vec<AnnotatedFile> = Vec::new();
+        for line in &profile.lines {
+            if files.last().is_none_or(|file| file.path != line.file) {
+                files.push(AnnotatedFile { path: line.file.clone(), gas: 0, percent: 0.0, lines: Vec::new() });
+            }
+            let file = files.last_mut().expect("file was just pushed");
+            file.gas += line.gas;
+            file.lines.push(AnnotatedLine {
+                line: line.line,
+                count: line.count,
+                gas: line.gas,
+                percent: percent(line.gas),
+            });
+        }
+        for file in &mut files {
+            file.percent = percent(file.gas);
+        }
+        files.sort_by(|a, b| b.gas.cmp(&a.gas).then_with(|| a.path.cmp(&b.path)));
+
+        let attributed: u64 = files.iter().map(|file| file.gas).sum();
+        SourceAnnotation {
+            function_name: profile.function_name.clone(),
+            gas_used: profile.gas_used,
+            unattributed_gas: profile.gas_used.saturating_sub(attributed),
+            files,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("annotations serialize")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::{GasBreakdown, LineGas};
+
+    #[test]
+    fn test_annotation_groups_lines_by_file() {
+        let line = |file: &str, line, gas| LineGas { file: file.to_string(), line, count: 1, gas };
+        let profile = GasProfile {
+            function_name: "run".to_string(),
+            gas_used: 200,
+            instruction_count: 4,
+            call_depth: 1,
+            breakdown: GasBreakdown::default(),
+            opcodes: Vec::new(),
+            classes: Vec::new(),
+            functions: Vec::new(),
+            stacks: Vec::new(),
+            lines: vec![line("/work/src/lib.rs", 3, 20), line("/work/src/lib.rs", 9, 30), line("/work/src/storage.rs", 4, 100)],
+            blocks: Vec::new(),
+            loops: Vec::new(),
+        };
+
+        let annotation = SourceAnnotation::new(&profile);
+        assert_eq!(annotation.unattributed_gas, 50);
+        let paths: Vec<&str> = annotation.files.iter().map(|file| file.path.as_str()).collect();
+        assert_eq!(paths, ["/work/src/storage.rs", "/work/src/lib.rs"]);
+        assert_eq!(annotation.files[1].gas, 50);
+        assert_eq!(annotation.files[1].percent, 25.0);
+        assert_eq!(annotation.files[1].lines[1].line, 9);
+        assert_eq!(annotation.files[1].lines[1].percent, 15.0);
+
+        let json: SourceAnnotation = serde_json::from_str(&annotation.to_json()).unwrap();
+        assert_eq!(json, annotation);
+    }
+}
@@ -0,0 +1,230 @@
+//! Basic-block execution counts and loop iteration counts.
+
+use crate::interpreter::{Function, Instr, Machine};
+use crate::source_map::SourceLocation;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A straight-line run of instructions that is only entered at its first instruction.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockProfile {
+    pub function: String,
+    /// Module offset of the block's first instruction.
+    pub offset: u64,
+    /// Instructions in the block; calls do not end a block.
+    pub length: usize,
+    /// Times the block was entered.
+    pub count: u64,
+    /// Gas of the block's own instructions, host calls included.
+    pub gas: u64,
+    pub location: Option<SourceLocation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoopProfile {
+    pub function: String,
+    /// Module offset of the `loop` instruction.
+    pub offset: u64,
+    /// Times execution reached the loop from outside.
+    pub entries: u64,
+    /// Times the loop body started, the first pass of each entry included.
+    pub iterations: u64,
+    /// Gas spent inside the loop, including functions called from it.
+    pub gas: u64,
+    pub location: Option<SourceLocation>,
+}
+
+/// Where the blocks and loops of one function begin.
+struct Layout {
+    /// Sorted body indices of each block's first instruction.
+    starts: Vec<usize>,
+    /// `(loop, end)` body indices of every loop, outermost first.
+    loops: Vec<(usize, usize)>,
+}
+
+impl Layout {
+    fn new(function: &Function) -> Self {
+        let mut starts = vec![0];
+        let mut loops = Vec::new();
+        for (pc, instr) in function.body.iter().enumerate() {
+            match instr {
+                // Branches end a block, and past an `end` is where branches out of a block land.
+                Instr::End | Instr::Br(_) | Instr::BrIf(_) | Instr::BrTable { .. } | Instr::Return | Instr::Unreachable => {
+                    starts.push(pc + 1)
+                }
+                Instr::Loop { .. } => {
+                    starts.push(pc + 1);
+                    loops.push((pc, matching_end(&function.body, pc)));
+                }
+                // A skipped arm continues past its `end`, which the `end` case already starts.
+                &Instr::If { else_pc, .. } => {
+                    starts.push(pc + 1);
+                    starts.extend(else_pc.map(|pc| pc + 1));
+                }
+                Instr::Else { .. } => starts.push(pc + 1),
+                _ => {}
+            }
+        }
+        starts.retain(|&start| start < function.body.len());
+        starts.sort_unstable();
+        starts.dedup();
+        Layout { starts, loops }
+    }
+
+    fn block(&self, pc: usize) -> usize {
+        self.starts[self.starts.partition_point(|&start| start <= pc) - 1]
+    }
+
+    fn loops_around(&self, pc: usize) -> impl Iterator<Item = usize> + '_ {
+        self.loops.iter().filter(move |&&(start, end)| start < pc && pc <= end).map(|&(start, _)| start)
+    }
+}
+
+/// Index of the `end` closing the structured instruction at `pc`.
+fn matching_end(body: &[Instr], pc: usize) -> usize {
+    let mut depth = 0usize;
+    for (index, instr) in body.iter().enumerate().skip(pc) {
+        match instr {
+            Instr::Block { .. } | Instr::Loop { .. } | Instr::If { .. } => depth += 1,
+            Instr::End => {
+                depth -= 1;
+                if depth == 0 {
+                    return index;
+                }
+            }
+            _ => {}
+        }
+    }
+    body.len()
+}
+
+#[derive(Default)]
+struct Counts {
+    count: u64,
+    gas: u64,
+}
+
+/// Counts block entries and loop iterations as the profiler steps a machine.
+#[derive(Default)]
+pub(crate) struct BlockCounter {
+    layouts: HashMap<u32, Layout>,
+    blocks: HashMap<(u32, usize), Counts>,
+    /// Keyed by the `loop` instruction; `count` is its entries.
+    loops: HashMap<(u32, usize), Counts>,
+}
+
+impl BlockCounter {
+    /// Charges an executed instruction at `pc` of `func` and the gas its step used.
+    pub(crate) fn step(&mut self, machine: &Machine, func: u32, pc: usize, gas: u64) {
+        let layout = self
+            .layouts
+            .entry(func)
+            .or_insert_with(|| Layout::new(&machine.program().functions[func as usize]));
+        let start = layout.block(pc);
+        let block = self.blocks.entry((func, start)).or_default();
+        if start == pc {
+            block.count += 1;
+        }
+        block.gas += gas;
+        for start in layout.loops_around(pc) {
+            self.loops.entry((func, start)).or_default().gas += gas;
+        }
+        if layout.loops.iter().any(|&(start, _)| start == pc) {
+            self.loops.entry((func, pc)).or_default().count += 1;
+        }
+    }
+
+    /// Charges a returned call's inclusive gas to the loops around its call site.
+    pub(crate) fn returned(&mut self, func: u32, call_pc: usize, gas: u64) {
+        let Some(layout) = self.layouts.get(&func) else { return };
+        for start in layout.loops_around(call_pc) {
+            self.loops.entry((func, start)).or_default().gas += gas;
+        }
+    }
+
+    pub(crate) fn finish(
+        self,
+        machine: &Machine,
+        name: impl Fn(u32) -> String,
+    ) -> (Vec<BlockProfile>, Vec<LoopProfile>) {
+        let program = machine.program();
+        let symbols = machine.symbols();
+        let offset = |func: u32, pc: usize| program.functions[func as usize].offsets.get(pc).copied().unwrap_or(0);
+        let location = |offset: u64| {
+            symbols.code_offset(offset).and_then(|code| symbols.source_map().location(code))
+        };
+
+        let mut blocks: Vec<BlockProfile> = self
+            .blocks
+            .iter()
+            .map(|(&(func, start), counts)| {
+                let layout = &self.layouts[&func];
+                let next = layout.starts.get(layout.starts.partition_point(|&s| s <= start)).copied();
+                let length = next.unwrap_or(program.functions[func as usize].body.len()) - start;
+                BlockProfile {
+                    function: name(func),
+                    offset: offset(func, start),
+                    length,
+                    count: counts.count,
+                    gas: counts.gas,
+                    location: location(offset(func, start)),
+                }
+            })
+            .collect();
+        blocks.sort_by(|a, b| b.gas.cmp(&a.gas).then_with(|| a.offset.cmp(&b.offset)));
+
+        let mut loops: Vec<LoopProfile> = self
+            .loops
+            .iter()
+            .map(|(&(func, start), counts)| LoopProfile {
+                function: name(func),
+                offset: offset(func, start),
+                entries: counts.count,
+                iterations: self.blocks.get(&(func, start + 1)).map_or(0, |body| body.count),
+                gas: counts.gas,
+                location: location(offset(func, start)),
+            })
+            .collect();
+        loops.sort_by(|a, b| b.gas.cmp(&a.gas).then_with(|| a.offset.cmp(&b.offset)));
+        (blocks, loops)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::StylusRuntime;
+
+    #[test]
+    fn test_loop_iterations_and_hot_blocks() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (func $inc (param $x i64) (result i64)
+                    (i64.add (local.get $x) (i64.const 1)))
+                (func (export "run") (param $n i64) (result i64) (local $i i64) (local $sum i64)
+                    (block $done
+                        (loop $next
+                            (br_if $done (i64.ge_s (local.get $i) (local.get $n)))
+                            (local.set $sum (i64.add (local.get $sum) (local.get $i)))
+                            (local.set $i (call $inc (local.get $i)))
+                            (br $next)))
+                    (local.get $sum))
+            )
+        "#).unwrap();
+        let mut runtime = StylusRuntime::new(&wasm).unwrap();
+        let (result, profile) = runtime.execute_with_profile("run", &[5]).unwrap();
+        assert_eq!(result.return_value, 10);
+
+        assert_eq!(profile.loops.len(), 1);
+        let hot = &profile.loops[0];
+        assert_eq!((hot.function.as_str(), hot.entries, hot.iterations), ("run", 1, 6));
+
+        // The check runs six times, the body that adds five; the body costs the most.
+        let run: Vec<_> = profile.blocks.iter().filter(|block| block.function == "run").collect();
+        assert_eq!(run[0].count, 5);
+        assert!(run.iter().any(|block| block.count == 6 && block.length == 4));
+        let inc = profile.functions.iter().find(|f| f.name == "inc").unwrap();
+        let body: u64 = run.iter().filter(|block| block.count > 1).map(|block| block.gas).sum();
+        assert_eq!(hot.gas, body + inc.inclusive_gas);
+        assert_eq!(profile.blocks.iter().map(|block| block.gas).sum::<u64>(), profile.gas_used);
+    }
+}
@@ -0,0 +1,332 @@
+//! Writes gas-weighted call stacks in formats standard profile viewers read.
+
+use super::{GasProfile, StackSample};
+use anyhow::{anyhow, Result};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileFormat {
+    /// One `outer;inner gas` line per stack, for `flamegraph.pl` and inferno.
+    Folded,
+    /// speedscope's JSON file format.
+    Speedscope,
+    /// An uncompressed pprof protobuf, for `go tool pprof`.
+    Pprof,
+    /// A self-contained flame graph.
+    Svg,
+}
+
+impl FromStr for ProfileFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "folded" => Ok(ProfileFormat::Folded),
+            "speedscope" => Ok(ProfileFormat::Speedscope),
+            "pprof" => Ok(ProfileFormat::Pprof),
+            "svg" => Ok(ProfileFormat::Svg),
+            _ => Err(anyhow!("Unknown profile format '{}' (expected folded, speedscope, pprof or svg)", s)),
+        }
+    }
+}
+
+pub fn export(profile: &GasProfile, format: ProfileFormat) -> Vec<u8> {
+    match format {
+        ProfileFormat::Folded => folded(profile).into_bytes(),
+        ProfileFormat::Speedscope => speedscope(profile).into_bytes(),
+        ProfileFormat::Pprof => pprof(profile),
+        ProfileFormat::Svg => flamegraph_svg(profile).into_bytes(),
+    }
+}
+
+pub fn folded(profile: &GasProfile) -> String {
+    let mut out = String::new();
+    for sample in &profile.stacks {
+        // `;` separates frames and a space the weight, so neither may appear in a name.
+        let frames: Vec<String> = sample.frames.iter().map(|frame| frame.replace([';', ' '], "_")).collect();
+        let _ = writeln!(out, "{} {}", frames.join(";"), sample.gas);
+    }
+    out
+}
+
+pub fn speedscope(profile: &GasProfile) -> String {
+    let mut frames: Vec<&str> = Vec::new();
+    let mut indices: HashMap<&str, usize> = HashMap::new();
+    let samples: Vec<Vec<usize>> = profile
+        .stacks
+        .iter()
+        .map(|sample| {
+            sample
+                .frames
+                .iter()
+                .map(|frame| {
+                    *indices.entry(frame).or_insert_with(|| {
+                        frames.push(frame);
+                        frames.len() - 1
+                    })
+                })
+                .collect()
+        })
+        .collect();
+    let weights: Vec<u64> = profile.stacks.iter().map(|sample| sample.gas).collect();
+    let total: u64 = weights.iter().sum();
+
+    json!({
+        "$schema": "https://www.speedscope.app/file-format-schema.json",
+        "name": profile.function_name,
+        "exporter": "stylus",
+        "activeProfileIndex": 0,
+        "shared": { "frames": frames.iter().map(|name| json!({ "name": name })).collect::<Vec<_>>() },
+        "profiles": [{
+            "type": "sampled",
+            "name": format!("{} (gas)", profile.function_name),
+            "unit": "none",
+            "startValue": 0,
+            "endValue": total,
+            "samples": samples,
+            "weights": weights,
+        }],
+    })
+    .to_string()
+}
+
+/// Encodes the profile as a `perftools.profiles.Profile` message with one `gas` value per sample.
+pub fn pprof(profile: &GasProfile) -> Vec<u8> {
+    let mut strings = vec![String::new()];
+    let mut string_ids: HashMap<String, u64> = HashMap::new();
+    let mut intern = |text: &str| -> u64 {
+        *string_ids.entry(text.to_string()).or_insert_with(|| {
+            strings.push(text.to_string());
+            strings.len() as u64 - 1
+        })
+    };
+    let gas = intern("gas");
+
+    // Functions and locations share ids: one location per function.
+    let mut function_ids: BTreeMap<&str, u64> = BTreeMap::new();
+    for frame in profile.stacks.iter().flat_map(|sample| &sample.frames) {
+        let next = function_ids.len() as u64 + 1;
+        function_ids.entry(frame).or_insert(next);
+    }
+
+    let mut out = Vec::new();
+    let mut value_type = Vec::new();
+    proto_uint(&mut value_type, 1, gas);
+    proto_uint(&mut value_type, 2, gas);
+    proto_bytes(&mut out, 1, &value_type);
+
+    for sample in &profile.stacks {
+        let locations: Vec<u64> = sample.frames.iter().rev().map(|frame| function_ids[frame.as_str()]).collect();
+        let mut message = Vec::new();
+        proto_packed(&mut message, 1, &locations);
+        proto_packed(&mut message, 2, &[sample.gas]);
+        proto_bytes(&mut out, 2, &message);
+    }
+    for &id in function_ids.values() {
+        let mut line = Vec::new();
+        proto_uint(&mut line, 1, id);
+        let mut location = Vec::new();
+        proto_uint(&mut location, 1, id);
+        proto_bytes(&mut location, 4, &line);
+        proto_bytes(&mut out, 4, &location);
+    }
+    for (&name, &id) in &function_ids {
+        let name = intern(name);
+        let mut function = Vec::new();
+        proto_uint(&mut function, 1, id);
+        proto_uint(&mut function, 2, name);
+        proto_uint(&mut function, 3, name);
+        proto_bytes(&mut out, 5, &function);
+    }
+    for text in &strings {
+        proto_bytes(&mut out, 6, text.as_bytes());
+    }
+    let mut period_type = Vec::new();
+    proto_uint(&mut period_type, 1, gas);
+    proto_uint(&mut period_type, 2, gas);
+    proto_bytes(&mut out, 11, &period_type);
+    proto_uint(&mut out, 12, 1);
+    out
+}
+
+fn proto_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn proto_uint(out: &mut Vec<u8>, field: u64, value: u64) {
+    proto_varint(out, field << 3);
+    proto_varint(out, value);
+}
+
+fn proto_bytes(out: &mut Vec<u8>, field: u64, data: &[u8]) {
+    proto_varint(out, (field << 3) | 2);
+    proto_varint(out, data.len() as u64);
+    out.extend_from_slice(data);
+}
+
+fn proto_packed(out: &mut Vec<u8>, field: u64, values: &[u64]) {
+    let mut data = Vec::new();
+    for &value in values {
+        proto_varint(&mut data, value);
+    }
+    proto_bytes(out, field, &data);
+}
+
+const SVG_WIDTH: f64 = 1200.0;
+const FRAME_HEIGHT: f64 = 16.0;
+const SVG_HEADER: f64 = 32.0;
+const CHAR_WIDTH: f64 = 7.0;
+
+/// A merged call tree: the gas of every stack passing through a frame.
+#[derive(Default)]
+struct FlameNode {
+    gas: u64,
+    children: BTreeMap<String, FlameNode>,
+}
+
+impl FlameNode {
+    fn insert(&mut self, sample: &StackSample) {
+        self.gas += sample.gas;
+        let mut node = self;
+        for frame in &sample.frames {
+            node = node.children.entry(frame.clone()).or_default();
+            node.gas += sample.gas;
+        }
+    }
+
+    fn depth(&self) -> usize {
+        self.children.values().map(|child| child.depth() + 1).max().unwrap_or(0)
+    }
+}
+
+/// Renders a classic bottom-up flame graph, widths proportional to inclusive gas.
+pub fn flamegraph_svg(profile: &GasProfile) -> String {
+    let mut root = FlameNode::default();
+    for sample in &profile.stacks {
+        root.insert(sample);
+    }
+    let height = SVG_HEADER + root.depth() as f64 * FRAME_HEIGHT + FRAME_HEIGHT;
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="monospace" font-size="12">"#,
+        w = SVG_WIDTH,
+        h = height
+    );
+    let _ = writeln!(out, r##"<rect width="100%" height="100%" fill="#f8f8f8"/>"##);
+    let _ = writeln!(
+        out,
+        r#"<text x="{}" y="20" text-anchor="middle" font-size="14">{} ({} gas)</text>"#,
+        SVG_WIDTH / 2.0,
+        escape(&profile.function_name),
+        root.gas
+    );
+    if root.gas > 0 {
+        let scale = SVG_WIDTH / root.gas as f64;
+        render_children(&mut out, &root, 0.0, height - FRAME_HEIGHT, scale, root.gas);
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+fn render_children(out: &mut String, node: &FlameNode, x: f64, y: f64, scale: f64, total: u64) {
+    let mut x = x;
+    for (name, child) in &node.children {
+        let width = child.gas as f64 * scale;
+        if width >= 0.5 {
+            let _ = writeln!(
+                out,
+                r#"<g><title>{} ({} gas, {:.2}%)</title><rect x="{:.2}" y="{:.2}" width="{:.2}" height="{}" fill="{}" rx="2"/>"#,
+                escape(name),
+                child.gas,
+                child.gas as f64 * 100.0 / total as f64,
+                x,
+                y - FRAME_HEIGHT,
+                width,
+                FRAME_HEIGHT - 1.0,
+                color(name)
+            );
+            let fits = ((width - 6.0) / CHAR_WIDTH) as usize;
+            if fits >= 3 {
+                let label: String = if name.chars().count() > fits {
+                    name.chars().take(fits - 2).chain("..".chars()).collect()
+                } else {
+                    name.clone()
+                };
+                let _ = writeln!(out, r#"<text x="{:.2}" y="{:.2}">{}</text>"#, x + 3.0, y - 4.0, escape(&label));
+            }
+            out.push_str("</g>\n");
+            render_children(out, child, x, y - FRAME_HEIGHT, scale, total);
+        }
+        x += width;
+    }
+}
+
+/// A stable warm colour per function name.
+fn color(name: &str) -> String {
+    let hash = name.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("rgb({},{},{})", 205 + hash % 50, 80 + (hash >> 8) % 150, (hash >> 16) % 60)
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::GasBreakdown;
+
+    #[test]
+    fn test_exports() {
+        let sample = |frames: &[&str], gas| StackSample { frames: frames.iter().map(|f| f.to_string()).collect(), gas };
+        let profile = GasProfile {
+            function_name: "run".to_string(),
+            gas_used: 160,
+            instruction_count: 12,
+            call_depth: 2,
+            breakdown: GasBreakdown::default(),
+            opcodes: Vec::new(),
+            classes: Vec::new(),
+            functions: Vec::new(),
+            stacks: vec![
+                sample(&["run"], 40),
+                sample(&["run", "Vec<u8> as Clone"], 20),
+                sample(&["run", "vm_hooks::storage_load_bytes32"], 100),
+            ],
+            lines: Vec::new(),
+            blocks: Vec::new(),
+            loops: Vec::new(),
+        };
+
+        assert_eq!(
+            folded(&profile),
+            "run 40\nrun;Vec<u8>_as_Clone 20\nrun;vm_hooks::storage_load_bytes32 100\n"
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&speedscope(&profile)).unwrap();
+        assert_eq!(json["shared"]["frames"].as_array().unwrap().len(), 3);
+        assert_eq!(json["profiles"][0]["samples"][2], json!([0, 2]));
+        assert_eq!(json["profiles"][0]["endValue"], 160);
+
+        let pprof = pprof(&profile);
+        assert_eq!(pprof[0], 0x0a);
+        assert!(pprof.windows(3).any(|window| window == b"gas"));
+
+        let svg = flamegraph_svg(&profile);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("Vec&lt;u8&gt; as Clone (20 gas, 12.50%)"));
+        assert_eq!(svg.matches("<rect x=").count(), 3);
+        assert_eq!("svg".parse::<ProfileFormat>().unwrap(), ProfileFormat::Svg);
+    }
+}
@@ -0,0 +1,546 @@
+//! Gas profiles, built by watching the interpreter execute a call one instruction at a time.
+
+pub mod annotate;
+pub mod blocks;
+pub mod export;
+
+pub use annotate::{AnnotatedFile, AnnotatedLine, SourceAnnotation};
+pub use blocks::{BlockProfile, LoopProfile};
+pub use export::ProfileFormat;
+
+use crate::interpreter::{Instr, Machine, Program, Step, Val};
+use blocks::BlockCounter;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use wasmer::RuntimeError;
+
+/// Broad groups of instructions, for telling what kind of work dominates a call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OpClass {
+    Control,
+    Call,
+    /// Calls to imported host functions, including the gas the host charged.
+    HostIo,
+    Variable,
+    Memory,
+    Constant,
+    Parametric,
+    Arithmetic,
+    Comparison,
+    Bitwise,
+    Conversion,
+}
+
+impl OpClass {
+    pub fn name(self) -> &'static str {
+        match self {
+            OpClass::Control => "control",
+            OpClass::Call => "call",
+            OpClass::HostIo => "host_io",
+            OpClass::Variable => "variable",
+            OpClass::Memory => "memory",
+            OpClass::Constant => "constant",
+            OpClass::Parametric => "parametric",
+            OpClass::Arithmetic => "arithmetic",
+            OpClass::Comparison => "comparison",
+            OpClass::Bitwise => "bitwise",
+            OpClass::Conversion => "conversion",
+        }
+    }
+
+    /// The class of `instr`; calls to imports count as host I/O.
+    pub fn of(instr: &Instr, program: &Program) -> Self {
+        match instr {
+            Instr::Unreachable
+            | Instr::Nop
+            | Instr::Block { .. }
+            | Instr::Loop { .. }
+            | Instr::If { .. }
+            | Instr::Else { .. }
+            | Instr::End
+            | Instr::Br(_)
+            | Instr::BrIf(_)
+            | Instr::BrTable { .. }
+            | Instr::Return => OpClass::Control,
+            Instr::Call(callee) if program.function(*callee).is_some_and(|f| f.import.is_some()) => OpClass::HostIo,
+            Instr::Call(_) | Instr::CallIndirect { .. } => OpClass::Call,
+            Instr::Drop | Instr::Select => OpClass::Parametric,
+            Instr::LocalGet(_)
+            | Instr::LocalSet(_)
+            | Instr::LocalTee(_)
+            | Instr::GlobalGet(_)
+            | Instr::GlobalSet(_) => OpClass::Variable,
+            Instr::Load(..)
+            | Instr::Store(..)
+            | Instr::MemorySize
+            | Instr::MemoryGrow
+            | Instr::MemoryCopy
+            | Instr::MemoryFill
+            | Instr::MemoryInit(_)
+            | Instr::DataDrop(_) => OpClass::Memory,
+            Instr::Const(_) => OpClass::Constant,
+            Instr::Unary(op) => op.class(),
+            Instr::Binary(op) => op.class(),
+        }
+    }
+}
+
+impl fmt::Display for OpClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// How often one opcode executed and the gas it was charged.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpcodeCount {
+    pub opcode: String,
+    pub class: OpClass,
+    pub count: u64,
+    pub gas: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClassCount {
+    pub class: OpClass,
+    pub count: u64,
+    pub gas: u64,
+}
+
+/// Gas and instructions attributed to one function, with and without its callees.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunctionProfile {
+    pub index: u32,
+    pub name: String,
+    pub calls: u64,
+    pub inclusive_gas: u64,
+    pub exclusive_gas: u64,
+    pub inclusive_instructions: u64,
+    pub exclusive_instructions: u64,
+}
+
+/// Exclusive gas spent with one particular call stack active.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StackSample {
+    /// Function names, outermost first.
+    pub frames: Vec<String>,
+    pub gas: u64,
+}
+
+/// Gas charged to the instructions compiled from one source line, host calls included.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LineGas {
+    pub file: String,
+    pub line: u32,
+    pub count: u64,
+    pub gas: u64,
+}
+
+/// Calls to one host function and the gas the host charged for them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HostFunctionGas {
+    pub module: String,
+    pub name: String,
+    pub calls: u64,
+    pub gas: u64,
+}
+
+/// The call's gas split by what it paid for; the three parts sum to `gas_used`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GasBreakdown {
+    /// Wasm instructions, calls into the host excluded.
+    pub compute: u64,
+    /// `memory.grow` and the host's per-page charge for new memory.
+    pub memory_growth: u64,
+    /// Gas charged by host functions: storage, logs, calls, hashing and the rest.
+    pub host_io: u64,
+    /// `host_io` by host function, most gas first.
+    pub host_functions: Vec<HostFunctionGas>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GasProfile {
+    pub function_name: String,
+    pub gas_used: u64,
+    pub instruction_count: u64,
+    pub call_depth: u32,
+    pub breakdown: GasBreakdown,
+    /// Executed opcodes, most gas first.
+    pub opcodes: Vec<OpcodeCount>,
+    /// The same executions grouped by class, most gas first.
+    pub classes: Vec<ClassCount>,
+    /// Every function that ran, imports included, most inclusive gas first.
+    pub functions: Vec<FunctionProfile>,
+    /// Gas by distinct call stack, for flame graphs.
+    pub stacks: Vec<StackSample>,
+    /// Gas by source line, sorted by file and line. Empty without DWARF line info.
+    pub lines: Vec<LineGas>,
+    /// Basic blocks that ran, most gas first.
+    pub blocks: Vec<BlockProfile>,
+    /// Loops that ran, most gas first.
+    pub loops: Vec<LoopProfile>,
+}
+
+/// A function on the profiled call stack.
+struct Activation {
+    func: u32,
+    gas: u64,
+    steps: u64,
+    node: usize,
+}
+
+/// One distinct call stack: a function reached through the stack of `parent`.
+struct StackNode {
+    parent: Option<usize>,
+    func: u32,
+    gas: u64,
+}
+
+/// Collects a `GasProfile` while driving a `Machine`.
+#[derive(Default)]
+pub(crate) struct Profiler {
+    opcodes: HashMap<(&'static str, OpClass), OpcodeCount>,
+    functions: HashMap<u32, FunctionProfile>,
+    stack: Vec<Activation>,
+    nodes: Vec<StackNode>,
+    children: HashMap<(Option<usize>, u32), usize>,
+    max_depth: usize,
+    lines: Vec<LineGas>,
+    /// Index into `lines` of each module offset seen, if it maps to a line.
+    line_at: HashMap<u64, Option<usize>>,
+    blocks: BlockCounter,
+    host_functions: HashMap<u32, HostFunctionGas>,
+    memory_growth: u64,
+}
+
+impl Profiler {
+    /// Runs the machine's pending call to completion, charging each instruction the gas
+    /// used across its step. A host call's step includes the host's own charge.
+    pub(crate) fn run(&mut self, machine: &mut Machine) -> Result<Vec<Val>, RuntimeError> {
+        if let Some(frame) = machine.frames().last() {
+            self.enter(machine, frame.func);
+        }
+        loop {
+            let position = machine.position();
+            let executing = machine.current_instr().map(|instr| {
+                let callee = match instr {
+                    &Instr::Call(callee) if machine.program().functions[callee as usize].import.is_some() => Some(callee),
+                    _ => None,
+                };
+                (instr.name(), OpClass::of(instr, machine.program()), callee)
+            });
+            let offset = machine.module_offset();
+            let (gas, host_gas, depth) = (machine.gas_used(), machine.env().host_gas, machine.frames().len());
+            let step = machine.step();
+
+            let gas = machine.gas_used().saturating_sub(gas);
+            let host_gas = machine.env().host_gas.saturating_sub(host_gas);
+            if let (Some((func, pc)), Some((name, class, callee))) = (position, executing) {
+                let count = self.opcodes.entry((name, class)).or_insert_with(|| OpcodeCount {
+                    opcode: name.to_string(),
+                    class,
+                    count: 0,
+                    gas: 0,
+                });
+                count.count += 1;
+                count.gas += gas;
+
+                // Host charges belong to the import; the call instruction itself to the caller.
+                let function = self.function(machine, func);
+                function.exclusive_gas += gas - host_gas;
+                function.exclusive_instructions += 1;
+                let node = self.stack.last().map(|activation| activation.node);
+                if let Some(node) = node {
+                    self.nodes[node].gas += gas - host_gas;
+                }
+                if let Some(callee) = callee {
+                    let import = self.function(machine, callee);
+                    import.calls += 1;
+                    import.exclusive_gas += host_gas;
+                    import.inclusive_gas += host_gas;
+                    let node = self.node(node, callee);
+                    self.nodes[node].gas += host_gas;
+                    self.host_call(machine, callee, host_gas);
+                }
+                if name == "memory.grow" {
+                    self.memory_growth += gas;
+                }
+                self.blocks.step(machine, func, pc, gas);
+                if let Some(line) = offset.and_then(|offset| self.line(machine, offset)) {
+                    self.lines[line].count += 1;
+                    self.lines[line].gas += gas;
+                }
+            }
+
+            match machine.frames().len() {
+                len if len > depth => self.enter(machine, machine.frames()[len - 1].func),
+                len if len < depth => self.exit(machine),
+                _ => {}
+            }
+            self.max_depth = self.max_depth.max(machine.frames().len());
+            if let Step::Finished(results) = step? {
+                return Ok(results);
+            }
+        }
+    }
+
+    fn function(&mut self, machine: &Machine, func: u32) -> &mut FunctionProfile {
+        self.functions.entry(func).or_insert_with(|| FunctionProfile {
+            index: func,
+            name: function_name(machine, func),
+            calls: 0,
+            inclusive_gas: 0,
+            exclusive_gas: 0,
+            inclusive_instructions: 0,
+            exclusive_instructions: 0,
+        })
+    }
+
+    fn node(&mut self, parent: Option<usize>, func: u32) -> usize {
+        let nodes = &mut self.nodes;
+        *self.children.entry((parent, func)).or_insert_with(|| {
+            nodes.push(StackNode { parent, func, gas: 0 });
+            nodes.len() - 1
+        })
+    }
+
+    fn host_call(&mut self, machine: &Machine, callee: u32, gas: u64) {
+        let Some((module, name)) = &machine.program().functions[callee as usize].import else { return };
+        if module == "vm_hooks" && matches!(name.as_str(), "pay_for_memory_grow" | "memory_grow") {
+            self.memory_growth += gas;
+            return;
+        }
+        let host = self.host_functions.entry(callee).or_insert_with(|| HostFunctionGas {
+            module: module.clone(),
+            name: name.clone(),
+            calls: 0,
+            gas: 0,
+        });
+        host.calls += 1;
+        host.gas += gas;
+    }
+
+    fn line(&mut self, machine: &Machine, module_offset: u64) -> Option<usize> {
+        let lines = &mut self.lines;
+        *self.line_at.entry(module_offset).or_insert_with(|| {
+            let symbols = machine.symbols();
+            let location = symbols.source_map().location(symbols.code_offset(module_offset)?)?;
+            if location.line == 0 {
+                return None;
+            }
+            let index = lines
+                .iter()
+                .position(|line| line.line == location.line && line.file == location.file)
+                .unwrap_or_else(|| {
+                    lines.push(LineGas { file: location.file, line: location.line, count: 0, gas: 0 });
+                    lines.len() - 1
+                });
+            Some(index)
+        })
+    }
+
+    fn enter(&mut self, machine: &Machine, func: u32) {
+        self.function(machine, func).calls += 1;
+        let node = self.node(self.stack.last().map(|activation| activation.node), func);
+        self.stack.push(Activation { func, gas: machine.gas_used(), steps: machine.steps(), node });
+    }
+
+    fn exit(&mut self, machine: &Machine) {
+        let Some(activation) = self.stack.pop() else { return };
+        if let Some(caller) = machine.frames().last() {
+            self.blocks.returned(caller.func, caller.pc - 1, machine.gas_used() - activation.gas);
+        }
+        // Recursive calls are already covered by the outermost activation.
+        if self.stack.iter().any(|outer| outer.func == activation.func) {
+            return;
+        }
+        let function = self.function(machine, activation.func);
+        function.inclusive_gas += machine.gas_used() - activation.gas;
+        function.inclusive_instructions += machine.steps() - activation.steps;
+    }
+
+    pub(crate) fn finish(self, function_name: &str, machine: &Machine) -> GasProfile {
+        let mut classes: HashMap<OpClass, ClassCount> = HashMap::new();
+        for opcode in self.opcodes.values() {
+            let class = classes.entry(opcode.class).or_insert(ClassCount {
+                class: opcode.class,
+                count: 0,
+                gas: 0,
+            });
+            class.count += opcode.count;
+            class.gas += opcode.gas;
+        }
+
+        let mut opcodes: Vec<OpcodeCount> = self.opcodes.into_values().collect();
+        opcodes.sort_by(|a, b| b.gas.cmp(&a.gas).then_with(|| a.opcode.cmp(&b.opcode)));
+        let mut classes: Vec<ClassCount> = classes.into_values().collect();
+        classes.sort_by(|a, b| b.gas.cmp(&a.gas).then_with(|| a.class.cmp(&b.class)));
+        let mut stacks: Vec<StackSample> = self
+            .nodes
+            .iter()
+            .filter(|node| node.gas > 0)
+            .map(|node| {
+                let mut frames = Vec::new();
+                let mut current = Some(node);
+                while let Some(node) = current {
+                    frames.push(self.functions[&node.func].name.clone());
+                    current = node.parent.map(|parent| &self.nodes[parent]);
+                }
+                frames.reverse();
+                StackSample { frames, gas: node.gas }
+            })
+            .collect();
+        stacks.sort_by(|a, b| a.frames.cmp(&b.frames));
+        let (blocks, loops) = self.blocks.finish(machine, |func| self.functions[&func].name.clone());
+        let mut functions: Vec<FunctionProfile> = self.functions.into_values().collect();
+        functions.sort_by(|a, b| b.inclusive_gas.cmp(&a.inclusive_gas).then_with(|| a.index.cmp(&b.index)));
+        let mut host_functions: Vec<HostFunctionGas> = self.host_functions.into_values().collect();
+        host_functions.sort_by(|a, b| b.gas.cmp(&a.gas).then_with(|| a.name.cmp(&b.name)));
+        let host_io = host_functions.iter().map(|host| host.gas).sum();
+        let breakdown = GasBreakdown {
+            compute: machine.gas_used().saturating_sub(host_io + self.memory_growth),
+            memory_growth: self.memory_growth,
+            host_io,
+            host_functions,
+        };
+        let mut lines = self.lines;
+        lines.sort_by(|a, b| a.file.cmp(&b.file).then_with(|| a.line.cmp(&b.line)));
+
+        GasProfile {
+            function_name: function_name.to_string(),
+            gas_used: machine.gas_used(),
+            instruction_count: opcodes.iter().map(|opcode| opcode.count).sum(),
+            call_depth: self.max_depth.max(1) as u32,
+            breakdown,
+            opcodes,
+            classes,
+            functions,
+            stacks,
+            lines,
+            blocks,
+            loops,
+        }
+    }
+}
+
+/// A function's demangled symbol, export name or import name, falling back to its index.
+fn function_name(machine: &Machine, func: u32) -> String {
+    let program = machine.program();
+    if let Some((module, name)) = program.function(func).and_then(|function| function.import.as_ref()) {
+        return format!("{}::{}", module, name);
+    }
+    machine
+        .symbols()
+        .function_name(func)
+        .or_else(|| program.export_name(func).map(str::to_string))
+        .unwrap_or_else(|| format!("func[{}]", func))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StylusRuntime;
+
+    #[test]
+    fn test_opcode_histogram() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (import "vm_hooks" "storage_load_bytes32" (func $load (param i32 i32)))
+                (memory (export "memory") 1)
+                (func $square (param $x i64) (result i64)
+                    (i64.mul (local.get $x) (local.get $x)))
+                (func (export "run") (param $n i64) (result i64)
+                    (call $load (i32.const 0) (i32.const 32))
+                    (i64.add (call $square (local.get $n)) (i64.load (i32.const 32))))
+            )
+        "#).unwrap();
+        let mut runtime = StylusRuntime::new(&wasm).unwrap();
+        let profile = runtime.profile_function("run", &[3]).unwrap();
+
+        let opcode = |name: &str| profile.opcodes.iter().find(|o| o.opcode == name).unwrap().clone();
+        assert_eq!(opcode("local.get").count, 3);
+        assert_eq!(opcode("i64.mul").class, OpClass::Arithmetic);
+        assert_eq!(opcode("i64.load").class, OpClass::Memory);
+        let calls = profile.opcodes.iter().filter(|o| o.opcode == "call");
+        assert_eq!(calls.count(), 2);
+        assert_eq!(profile.call_depth, 2);
+
+        // The storage load's host gas lands on the host I/O class, not on wasm calls.
+        let class = |class: OpClass| profile.classes.iter().find(|c| c.class == class).unwrap().clone();
+        assert_eq!(class(OpClass::HostIo).count, 1);
+        assert!(class(OpClass::HostIo).gas > class(OpClass::Call).gas);
+        assert_eq!(profile.classes.iter().map(|c| c.gas).sum::<u64>(), profile.gas_used);
+        assert_eq!(profile.instruction_count, profile.opcodes.iter().map(|o| o.count).sum::<u64>());
+    }
+
+    #[test]
+    fn test_function_attribution() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (import "vm_hooks" "native_keccak256" (func $keccak (param i32 i32 i32)))
+                (memory (export "memory") 1)
+                (func $hash
+                    (call $keccak (i32.const 0) (i32.const 32) (i32.const 64)))
+                (func $fact (param $n i64) (result i64)
+                    (if (result i64) (i64.le_s (local.get $n) (i64.const 1))
+                        (then (i64.const 1))
+                        (else (i64.mul (local.get $n) (call $fact (i64.sub (local.get $n) (i64.const 1)))))))
+                (func (export "run") (param $n i64) (result i64)
+                    (call $hash)
+                    (call $hash)
+                    (call $fact (local.get $n)))
+            )
+        "#).unwrap();
+        let mut runtime = StylusRuntime::new(&wasm).unwrap();
+        let (result, profile) = runtime.execute_with_profile("run", &[4]).unwrap();
+        assert_eq!(result.return_value, 24);
+        // The profile accounts for exactly the gas the call reports, which wasmer reports too.
+        assert_eq!(result.gas_used, runtime.execute_function("run", &[4]).unwrap().gas_used);
+        assert_eq!(profile.gas_used, result.gas_used);
+
+        let function = |name: &str| profile.functions.iter().find(|f| f.name == name).unwrap().clone();
+        let (run, hash, fact) = (function("run"), function("hash"), function("fact"));
+        let keccak = function("vm_hooks::native_keccak256");
+        assert_eq!((run.calls, hash.calls, fact.calls, keccak.calls), (1, 2, 4, 2));
+        assert_eq!(profile.functions[0], run);
+        assert_eq!(run.inclusive_gas, profile.gas_used);
+        assert_eq!(run.inclusive_instructions, profile.instruction_count);
+
+        // Exclusive gas partitions the whole call; recursion is not counted twice.
+        assert_eq!(profile.functions.iter().map(|f| f.exclusive_gas).sum::<u64>(), profile.gas_used);
+        assert_eq!(hash.inclusive_gas, hash.exclusive_gas + keccak.inclusive_gas);
+        assert!(fact.inclusive_gas < run.inclusive_gas);
+        assert!(fact.exclusive_gas <= fact.inclusive_gas);
+    }
+
+    #[test]
+    fn test_gas_breakdown() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (import "vm_hooks" "pay_for_memory_grow" (func $pay (param i32)))
+                (import "vm_hooks" "storage_store_bytes32" (func $store (param i32 i32)))
+                (import "vm_hooks" "native_keccak256" (func $keccak (param i32 i32 i32)))
+                (memory (export "memory") 1)
+                (func (export "run") (param $n i64) (result i64)
+                    (call $pay (i32.const 1))
+                    (drop (memory.grow (i32.const 1)))
+                    (call $keccak (i32.const 0) (i32.const 32) (i32.const 64))
+                    (call $store (i32.const 0) (i32.const 64))
+                    (call $store (i32.const 32) (i32.const 64))
+                    (local.get $n))
+            )
+        "#).unwrap();
+        let mut runtime = StylusRuntime::new(&wasm).unwrap();
+        let profile = runtime.profile_function("run", &[1]).unwrap();
+        let breakdown = &profile.breakdown;
+        assert_eq!(breakdown.compute + breakdown.memory_growth + breakdown.host_io, profile.gas_used);
+        let grow = profile.opcodes.iter().find(|o| o.opcode == "memory.grow").unwrap();
+        assert_eq!(breakdown.memory_growth, grow.gas + 1000);
+
+        let hosts: Vec<(&str, u64)> = breakdown.host_functions.iter().map(|h| (h.name.as_str(), h.calls)).collect();
+        assert_eq!(hosts, [("storage_store_bytes32", 2), ("native_keccak256", 1)]);
+        assert_eq!(breakdown.host_functions.iter().map(|h| h.gas).sum::<u64>(), breakdown.host_io);
+    }
+}
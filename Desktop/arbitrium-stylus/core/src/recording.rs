@@ -0,0 +1,218 @@
+use crate::console::ConsoleLine;
+use crate::debugger::Debugger;
+use crate::host::{CallFrame, ExecutionContext, HostEnv};
+use crate::interpreter::{Machine, Program, Tape, Val};
+use crate::symbols::Symbols;
+use crate::world::{Bytes32, Log, World};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// One host function call and everything it did to the contract's state.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HostCall {
+    /// Number of instructions executed before the call.
+    pub step: u64,
+    /// Instruction gas used before the call, which replay checks to catch a diverging gas table.
+    pub instruction_gas: u64,
+    pub module: String,
+    pub name: String,
+    pub args: Vec<Val>,
+    pub results: Vec<Val>,
+    /// Bytes the host wrote into linear memory, as `(address, data)`.
+    pub writes: Vec<(u32, Vec<u8>)>,
+    /// Total host gas charged once the call returned.
+    pub host_gas: u64,
+    /// The new return data, when the call replaced it.
+    pub output: Option<Vec<u8>>,
+    pub logs: Vec<Log>,
+    pub console: Vec<ConsoleLine>,
+    /// Set when the call trapped.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StorageOp {
+    Load,
+    Store,
+}
+
+/// A storage slot the call read or wrote.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageAccess {
+    pub step: u64,
+    pub op: StorageOp,
+    pub key: Bytes32,
+    pub value: Bytes32,
+}
+
+/// What a call observed from outside the contract, in order.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Trace {
+    pub host_calls: Vec<HostCall>,
+    pub storage: Vec<StorageAccess>,
+    /// Number of instructions executed, including any start function.
+    pub steps: u64,
+    /// Instruction and host gas used once the last instruction ran.
+    pub gas_used: u64,
+}
+
+impl Trace {
+    /// Storage as the contract saw it before instruction `step`, sorted by key.
+    pub fn storage_at(&self, step: u64) -> Vec<(Bytes32, Bytes32)> {
+        let mut slots = BTreeMap::new();
+        for access in self.storage.iter().take_while(|access| access.step < step) {
+            slots.insert(access.key, access.value);
+        }
+        slots.into_iter().collect()
+    }
+}
+
+/// A complete, deterministic record of one call, replayable without the world it ran against.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+    pub wasm: Vec<u8>,
+    pub function: String,
+    pub args: Vec<Val>,
+    pub context: ExecutionContext,
+    pub frame: CallFrame,
+    pub gas_table: HashMap<String, u64>,
+    pub trace: Trace,
+    /// The call's results, or its trap message.
+    pub outcome: std::result::Result<Vec<Val>, String>,
+}
+
+impl Recording {
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_vec(self)?;
+        std::fs::write(path.as_ref(), json)
+            .map_err(|e| anyhow!("Failed to write recording {}: {}", path.as_ref().display(), e))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let json = std::fs::read(path.as_ref())
+            .map_err(|e| anyhow!("Failed to read recording {}: {}", path.as_ref().display(), e))?;
+        serde_json::from_slice(&json).map_err(|e| anyhow!("Failed to parse recording: {}", e))
+    }
+
+    /// Number of instructions the recorded call executed, including any start function.
+    pub fn steps(&self) -> u64 {
+        self.trace.steps
+    }
+
+    /// Replays the call in a debugger that can also step backwards. Host calls are answered
+    /// from the trace, so no world state or custom imports are needed.
+    pub fn replay(&self) -> Result<Debugger> {
+        let program = Arc::new(Program::parse(&self.wasm)?);
+        let symbols = Arc::new(Symbols::parse(&self.wasm));
+        let func = program
+            .export(&self.function)
+            .ok_or_else(|| anyhow!("Function '{}' not found", self.function))?;
+
+        let world = World::new(self.gas_table.clone());
+        let env = HostEnv::new(
+            Arc::new(Mutex::new(world)),
+            self.context.clone(),
+            self.gas_table.clone(),
+            self.frame.clone(),
+        );
+        let tape = Tape::Replaying { trace: Arc::new(self.trace.clone()), cursor: 0 };
+        let mut machine = Machine::with_tape(program, symbols, env, tape)?;
+        machine.call(func, &self.args)?;
+        Ok(Debugger::replaying(machine))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{StopReason, StylusRuntime};
+
+    #[test]
+    fn test_record_and_replay_backwards() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (import "vm_hooks" "storage_load_bytes32" (func $load (param i32 i32)))
+                (import "vm_hooks" "storage_store_bytes32" (func $store (param i32 i32)))
+                (memory (export "memory") 1)
+                (func (export "bump") (param $n i64) (result i64)
+                    (call $load (i32.const 0) (i32.const 32))
+                    (i64.store (i32.const 56)
+                        (i64.add (i64.load (i32.const 56)) (local.get $n)))
+                    (call $store (i32.const 0) (i32.const 32))
+                    (i64.load (i32.const 56)))
+            )
+        "#).unwrap();
+        let mut runtime = StylusRuntime::new(&wasm).unwrap();
+        let address = runtime.context().contract_address;
+        let mut seeded = [0u8; 32];
+        seeded[24] = 5;
+        runtime.world().storage_store(&address, [0u8; 32], seeded);
+
+        runtime.enable_recording(true);
+        let result = runtime.execute_function("bump", &[2]).unwrap();
+        let recording = runtime.take_recording().unwrap();
+        assert_eq!(result.return_value, 7);
+        assert_eq!(recording.outcome, Ok(vec![Val::I64(7)]));
+        assert!(recording.steps() > 0);
+        assert_eq!(recording.trace.gas_used, result.gas_used);
+        assert!(recording.trace.host_calls[1].instruction_gas > recording.trace.host_calls[0].instruction_gas);
+        assert_eq!(recording.trace.storage[0].op, StorageOp::Load);
+        assert_eq!(recording.trace.storage[0].value, seeded);
+
+        // A recorded call reports the same gas as the same call run normally.
+        runtime.enable_recording(false);
+        assert_eq!(runtime.execute_function("bump", &[2]).unwrap().gas_used, result.gas_used);
+
+        let path = std::env::temp_dir().join("stylus-recording-test.json");
+        recording.save(&path).unwrap();
+        let recording = Recording::load(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        let mut debugger = recording.replay().unwrap();
+        let first = debugger.location().unwrap();
+        assert_eq!(debugger.resume(), StopReason::Finished(vec![Val::I64(7)]));
+        assert_eq!(debugger.gas_used(), result.gas_used);
+        assert_eq!(debugger.storage().len(), 1);
+
+        assert_eq!(debugger.step_back().unwrap(), StopReason::Step);
+        assert_eq!(debugger.location().unwrap().instruction, "end");
+        while debugger.steps() > 0 {
+            debugger.step_back().unwrap();
+        }
+        assert_eq!(debugger.location().unwrap(), first);
+        assert!(debugger.storage().is_empty());
+        assert!(debugger.step_back().is_err());
+
+        let id = debugger.add_breakpoint(crate::Breakpoint::Offset(first.code_offset.unwrap())).unwrap();
+        assert_eq!(debugger.resume(), StopReason::Finished(vec![Val::I64(7)]));
+        assert_eq!(debugger.reverse_resume().unwrap(), StopReason::Breakpoint(id));
+        assert_eq!(debugger.steps(), 0);
+    }
+
+    #[test]
+    fn test_non_finite_floats_round_trip_through_json() {
+        let values = vec![
+            Val::F32(f32::NAN),
+            Val::F32(f32::NEG_INFINITY),
+            Val::F64(f64::from_bits(0x7ff0_0000_0000_0001)),
+            Val::F64(f64::INFINITY),
+            Val::F64(-0.0),
+        ];
+        let json = serde_json::to_string(&values).unwrap();
+        let parsed: Vec<Val> = serde_json::from_str(&json).unwrap();
+        let bits = |values: &[Val]| -> Vec<u64> {
+            values
+                .iter()
+                .map(|value| match *value {
+                    Val::F32(f) => f.to_bits() as u64,
+                    Val::F64(f) => f.to_bits(),
+                    other => other.i64() as u64,
+                })
+                .collect()
+        };
+        assert_eq!(bits(&parsed), bits(&values));
+    }
+}
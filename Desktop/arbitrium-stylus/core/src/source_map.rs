@@ -0,0 +1,479 @@
+use crate::symbols::demangle;
+use anyhow::Result;
+use gimli::{AttributeValue, ColumnType, DebuggingInformationEntry, EndianSlice, LittleEndian, LineProgramHeader, UnitRef};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use wasmparser::{Parser, Payload};
+
+type Slice<'a> = EndianSlice<'a, LittleEndian>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: u32,
+    /// Zero when the compiler did not record a column.
+    pub column: u32,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.column > 0 {
+            write!(f, "{}:{}:{}", self.file, self.line, self.column)
+        } else {
+            write!(f, "{}:{}", self.file, self.line)
+        }
+    }
+}
+
+/// One level of the inlining chain at a code offset.
+///
+/// The innermost frame's location is the instruction itself; every outer
+/// frame's location is the call site its inlined callee was expanded at.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceFrame {
+    pub function: Option<String>,
+    pub location: Option<SourceLocation>,
+}
+
+#[derive(Debug, Clone)]
+struct Row {
+    address: u64,
+    file: usize,
+    line: u32,
+    column: u32,
+    end_sequence: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Scope {
+    begin: u64,
+    end: u64,
+    depth: isize,
+    name: Option<String>,
+    call_site: Option<(usize, u32, u32)>,
+}
+
+/// Maps code-section offsets of a wasm binary to source positions using its DWARF sections.
+///
+/// Everything is read up front so lookups are cheap and the map can be shared between threads.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: Vec<String>,
+    rows: Vec<Row>,
+    scopes: Vec<Scope>,
+}
+
+impl SourceMap {
+    /// Parses the `.debug_*` custom sections. A binary without debug info yields an empty map.
+    pub fn parse(wasm: &[u8]) -> Result<Self> {
+        let sections = debug_sections(wasm);
+        let mut map = Self::default();
+        if sections.is_empty() {
+            return Ok(map);
+        }
+
+        let dwarf = gimli::Dwarf::load(|id| -> Result<Slice<'_>, gimli::Error> {
+            let data = sections.get(id.name()).copied().unwrap_or(&[]);
+            Ok(EndianSlice::new(data, LittleEndian))
+        })?;
+
+        let mut interned = HashMap::new();
+        let mut units = dwarf.units();
+        while let Some(header) = units.next()? {
+            let unit = dwarf.unit(header)?;
+            let unit = unit.unit_ref(&dwarf);
+            map.add_rows(unit, &mut interned)?;
+            map.add_scopes(unit, &mut interned)?;
+        }
+
+        // End-of-sequence rows sort first so a sequence starting where another ends wins.
+        map.rows.sort_by_key(|row| (row.address, !row.end_sequence));
+        Ok(map)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty() && self.scopes.is_empty()
+    }
+
+    /// Every source file referenced by the line tables.
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    /// The source position of the instruction at `code_offset`.
+    pub fn location(&self, code_offset: u64) -> Option<SourceLocation> {
+        let index = self.rows.partition_point(|row| row.address <= code_offset).checked_sub(1)?;
+        let row = &self.rows[index];
+        if row.end_sequence {
+            return None;
+        }
+        Some(self.source_location(row.file, row.line, row.column))
+    }
+
+    /// Code offsets where `line` of `file` begins, for setting breakpoints.
+    ///
+    /// `file` may be a suffix of the recorded path, such as `src/lib.rs`. A line without
+    /// code resolves to the next line in the file that has some, which is returned too.
+    pub fn line_addresses(&self, file: &str, line: u32) -> Option<(u32, Vec<u64>)> {
+        let files: Vec<usize> = (0..self.files.len())
+            .filter(|&id| path_matches(&self.files[id], file))
+            .collect();
+        let starts: Vec<(u32, u64)> = self
+            .rows
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| !row.end_sequence && files.contains(&row.file) && row.line >= line)
+            .filter(|&(index, row)| {
+                // Only the first row of a run on the same line starts it.
+                index == 0 || {
+                    let previous = &self.rows[index - 1];
+                    previous.end_sequence || previous.file != row.file || previous.line != row.line
+                }
+            })
+            .map(|(_, row)| (row.line, row.address))
+            .collect();
+        let resolved = starts.iter().map(|&(line, _)| line).min()?;
+        let addresses = starts
+            .into_iter()
+            .filter(|&(line, _)| line == resolved)
+            .map(|(_, address)| address)
+            .collect();
+        Some((resolved, addresses))
+    }
+
+    /// The inlining chain at `code_offset`, innermost function first.
+    pub fn frames(&self, code_offset: u64) -> Vec<SourceFrame> {
+        let mut scopes: Vec<&Scope> = self
+            .scopes
+            .iter()
+            .filter(|scope| scope.begin <= code_offset && code_offset < scope.end)
+            .collect();
+        scopes.sort_by_key(|scope| std::cmp::Reverse(scope.depth));
+
+        let mut location = self.location(code_offset);
+        if scopes.is_empty() {
+            return location
+                .map(|location| vec![SourceFrame { function: None, location: Some(location) }])
+                .unwrap_or_default();
+        }
+
+        scopes
+            .into_iter()
+            .map(|scope| {
+                let frame = SourceFrame {
+                    function: scope.name.clone(),
+                    location: location.take(),
+                };
+                location = scope
+                    .call_site
+                    .map(|(file, line, column)| self.source_location(file, line, column));
+                frame
+            })
+            .collect()
+    }
+
+    fn source_location(&self, file: usize, line: u32, column: u32) -> SourceLocation {
+        SourceLocation {
+            file: self.files[file].clone(),
+            line,
+            column,
+        }
+    }
+
+    fn intern(&mut self, interned: &mut HashMap<String, usize>, path: String) -> usize {
+        *interned.entry(path).or_insert_with_key(|path| {
+            self.files.push(path.clone());
+            self.files.len() - 1
+        })
+    }
+
+    fn file_id<'a>(
+        &mut self,
+        unit: UnitRef<'_, Slice<'a>>,
+        header: &LineProgramHeader<Slice<'a>>,
+        index: u64,
+        interned: &mut HashMap<String, usize>,
+    ) -> usize {
+        let path = file_path(unit, header, index).unwrap_or_else(|| "<unknown>".to_string());
+        self.intern(interned, path)
+    }
+
+    fn add_rows(&mut self, unit: UnitRef<'_, Slice<'_>>, interned: &mut HashMap<String, usize>) -> Result<()> {
+        let Some(program) = unit.line_program.clone() else {
+            return Ok(());
+        };
+
+        let mut files = HashMap::new();
+        let mut rows = program.rows();
+        let mut skipping = false;
+        let mut sequence_start = true;
+        while let Some((header, row)) = rows.next_row()? {
+            // The linker points sequences of discarded functions at address 0,
+            // which is never the start of a real function body.
+            if sequence_start {
+                skipping = row.address() == 0;
+                sequence_start = false;
+            }
+            if row.end_sequence() {
+                sequence_start = true;
+            }
+            if skipping {
+                continue;
+            }
+
+            let file = match files.get(&row.file_index()) {
+                Some(&file) => file,
+                None => {
+                    let file = self.file_id(unit, header, row.file_index(), interned);
+                    files.insert(row.file_index(), file);
+                    file
+                }
+            };
+            self.rows.push(Row {
+                address: row.address(),
+                file,
+                line: row.line().map(|line| line.get() as u32).unwrap_or(0),
+                column: match row.column() {
+                    ColumnType::LeftEdge => 0,
+                    ColumnType::Column(column) => column.get() as u32,
+                },
+                end_sequence: row.end_sequence(),
+            });
+        }
+        Ok(())
+    }
+
+    fn add_scopes(&mut self, unit: UnitRef<'_, Slice<'_>>, interned: &mut HashMap<String, usize>) -> Result<()> {
+        let header = unit.line_program.as_ref().map(|program| program.header().clone());
+        let mut entries = unit.entries();
+        let mut depth = 0isize;
+        while let Some((delta, entry)) = entries.next_dfs()? {
+            depth += delta;
+            let inlined = match entry.tag() {
+                gimli::DW_TAG_subprogram => false,
+                gimli::DW_TAG_inlined_subroutine => true,
+                _ => continue,
+            };
+
+            let name = die_name(unit, entry, 0)?;
+            let call_site = match (&header, inlined) {
+                (Some(header), true) => {
+                    let file = match entry.attr_value(gimli::DW_AT_call_file)? {
+                        Some(AttributeValue::FileIndex(index)) => Some(index),
+                        value => value.and_then(|v| v.udata_value()),
+                    };
+                    let line = entry.attr_value(gimli::DW_AT_call_line)?.and_then(|v| v.udata_value());
+                    let column = entry.attr_value(gimli::DW_AT_call_column)?.and_then(|v| v.udata_value());
+                    match (file, line) {
+                        (Some(file), Some(line)) => Some((
+                            self.file_id(unit, header, file, interned),
+                            line as u32,
+                            column.unwrap_or(0) as u32,
+                        )),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+
+            let mut ranges = unit.die_ranges(entry)?;
+            while let Some(range) = ranges.next()? {
+                if range.begin == 0 || range.begin >= range.end {
+                    continue;
+                }
+                self.scopes.push(Scope {
+                    begin: range.begin,
+                    end: range.end,
+                    depth,
+                    name: name.clone(),
+                    call_site,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether one path ends with the other at a component boundary, so relative and
+/// absolute spellings of the same file match.
+fn path_matches(path: &str, query: &str) -> bool {
+    let path = path.replace('\\', "/");
+    let query = query.replace('\\', "/");
+    let (path, query) = (path.trim_start_matches("./"), query.trim_start_matches("./"));
+    path == query || path.ends_with(&format!("/{}", query)) || query.ends_with(&format!("/{}", path))
+}
+
+fn debug_sections(wasm: &[u8]) -> HashMap<&str, &[u8]> {
+    let mut sections = HashMap::new();
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload {
+            Ok(Payload::CustomSection(reader)) if reader.name().starts_with(".debug_") => {
+                sections.insert(reader.name(), reader.data());
+            }
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+    sections
+}
+
+fn file_path<'a>(unit: UnitRef<'_, Slice<'a>>, header: &LineProgramHeader<Slice<'a>>, index: u64) -> Option<String> {
+    let file = header.file(index)?;
+    let name = unit.attr_string(file.path_name()).ok()?.to_string_lossy().into_owned();
+    if name.starts_with('/') {
+        return Some(name);
+    }
+
+    let mut path = String::new();
+    if let Some(directory) = file.directory(header).and_then(|dir| unit.attr_string(dir).ok()) {
+        let directory = directory.to_string_lossy();
+        if !directory.starts_with('/') {
+            if let Some(comp_dir) = unit.comp_dir {
+                path.push_str(&comp_dir.to_string_lossy());
+                path.push('/');
+            }
+        }
+        path.push_str(&directory);
+        if !path.is_empty() && !path.ends_with('/') {
+            path.push('/');
+        }
+    }
+    path.push_str(&name);
+    Some(path)
+}
+
+/// The function name of a subprogram or inlined subroutine, following abstract origins.
+fn die_name<'a>(
+    unit: UnitRef<'_, Slice<'a>>,
+    entry: &DebuggingInformationEntry<'_, '_, Slice<'a>>,
+    hops: u8,
+) -> Result<Option<String>> {
+    for attr in [gimli::DW_AT_linkage_name, gimli::DW_AT_MIPS_linkage_name] {
+        if let Some(value) = entry.attr_value(attr)? {
+            return Ok(Some(demangle(&unit.attr_string(value)?.to_string_lossy())));
+        }
+    }
+    if let Some(value) = entry.attr_value(gimli::DW_AT_name)? {
+        return Ok(Some(unit.attr_string(value)?.to_string_lossy().into_owned()));
+    }
+    if hops < 4 {
+        for attr in [gimli::DW_AT_abstract_origin, gimli::DW_AT_specification] {
+            if let Some(AttributeValue::UnitRef(offset)) = entry.attr_value(attr)? {
+                let origin = unit.entry(offset)?;
+                return die_name(unit, &origin, hops + 1);
+            }
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gimli::write::{self, Address, DwarfUnit, EndianVec, LineProgram, LineString, Sections};
+
+    fn push_leb128(out: &mut Vec<u8>, mut value: usize) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                out.push(byte);
+                return;
+            }
+            out.push(byte | 0x80);
+        }
+    }
+
+    /// A unit for `src/lib.rs` where `outer` (0x10..0x30) inlines `inner` (0x20..0x28) at line 6.
+    fn debug_sections() -> Vec<(&'static str, Vec<u8>)> {
+        let encoding = gimli::Encoding {
+            format: gimli::Format::Dwarf32,
+            version: 4,
+            address_size: 4,
+        };
+        let mut dwarf = DwarfUnit::new(encoding);
+        let mut program = LineProgram::new(
+            encoding,
+            gimli::LineEncoding::default(),
+            LineString::String(b"/work".to_vec()),
+            LineString::String(b"src/lib.rs".to_vec()),
+            None,
+        );
+        let file = program.add_file(LineString::String(b"src/lib.rs".to_vec()), program.default_directory(), None);
+        program.begin_sequence(Some(Address::Constant(0x10)));
+        for (offset, line) in [(0x0, 5), (0x10, 7), (0x18, 6)] {
+            program.row().address_offset = offset;
+            program.row().file = file;
+            program.row().line = line;
+            program.generate_row();
+        }
+        program.end_sequence(0x20);
+        dwarf.unit.line_program = program;
+
+        let root = dwarf.unit.root();
+        dwarf.unit.get_mut(root).set(gimli::DW_AT_comp_dir, write::AttributeValue::String(b"/work".to_vec()));
+        let outer = dwarf.unit.add(root, gimli::DW_TAG_subprogram);
+        let entry = dwarf.unit.get_mut(outer);
+        entry.set(gimli::DW_AT_linkage_name, write::AttributeValue::String(b"_ZN8contract5outer17h0123456789abcdefE".to_vec()));
+        entry.set(gimli::DW_AT_low_pc, write::AttributeValue::Address(Address::Constant(0x10)));
+        entry.set(gimli::DW_AT_high_pc, write::AttributeValue::Udata(0x20));
+        let inner = dwarf.unit.add(outer, gimli::DW_TAG_inlined_subroutine);
+        let entry = dwarf.unit.get_mut(inner);
+        entry.set(gimli::DW_AT_name, write::AttributeValue::String(b"inner".to_vec()));
+        entry.set(gimli::DW_AT_low_pc, write::AttributeValue::Address(Address::Constant(0x20)));
+        entry.set(gimli::DW_AT_high_pc, write::AttributeValue::Udata(0x8));
+        entry.set(gimli::DW_AT_call_file, write::AttributeValue::FileIndex(Some(file)));
+        entry.set(gimli::DW_AT_call_line, write::AttributeValue::Udata(6));
+
+        let mut sections = Sections::new(EndianVec::new(LittleEndian));
+        dwarf.write(&mut sections).unwrap();
+        let mut out = Vec::new();
+        sections
+            .for_each(|id, data| {
+                out.push((id.name(), data.slice().to_vec()));
+                Ok::<(), gimli::Error>(())
+            })
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn test_offsets_map_to_lines_and_inlined_frames() {
+        let mut wasm = wat::parse_str("(module (func (export \"f\")))").unwrap();
+        for (name, data) in debug_sections() {
+            let mut payload = Vec::new();
+            push_leb128(&mut payload, name.len());
+            payload.extend_from_slice(name.as_bytes());
+            payload.extend_from_slice(&data);
+            wasm.push(0);
+            push_leb128(&mut wasm, payload.len());
+            wasm.extend_from_slice(&payload);
+        }
+
+        let map = SourceMap::parse(&wasm).unwrap();
+        assert_eq!(map.files(), ["/work/src/lib.rs"]);
+        assert_eq!(map.location(0x14).unwrap().to_string(), "/work/src/lib.rs:5");
+        assert_eq!(map.location(0x2c).unwrap().line, 6);
+        assert!(map.location(0x30).is_none());
+        assert!(map.location(0x08).is_none());
+
+        assert_eq!(map.line_addresses("src/lib.rs", 7), Some((7, vec![0x20])));
+        assert_eq!(map.line_addresses("lib.rs", 4), Some((5, vec![0x10])));
+        assert_eq!(map.line_addresses("/work/src/lib.rs", 6), Some((6, vec![0x28])));
+        assert!(map.line_addresses("src/main.rs", 5).is_none());
+
+        let frames = map.frames(0x24);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].function.as_deref(), Some("inner"));
+        assert_eq!(frames[0].location.as_ref().unwrap().line, 7);
+        assert_eq!(frames[1].function.as_deref(), Some("contract::outer"));
+        assert_eq!(frames[1].location.as_ref().unwrap().line, 6);
+
+        let frames = map.frames(0x12);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].location.as_ref().unwrap().line, 5);
+
+        assert!(SourceMap::parse(&wat::parse_str("(module)").unwrap()).unwrap().is_empty());
+    }
+}
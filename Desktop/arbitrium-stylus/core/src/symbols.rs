@@ -0,0 +1,111 @@
+use crate::source_map::{SourceFrame, SourceMap};
+use std::collections::HashMap;
+use wasmparser::{KnownCustom, Name, Parser, Payload};
+
+/// Function names and code layout read from a contract's wasm binary.
+#[derive(Debug, Clone, Default)]
+pub struct Symbols {
+    names: HashMap<u32, String>,
+    code_section_start: Option<u64>,
+    source_map: SourceMap,
+}
+
+impl Symbols {
+    /// Reads the name section, code section position and DWARF debug info.
+    /// Malformed or missing sections simply leave the corresponding information out.
+    pub fn parse(wasm: &[u8]) -> Self {
+        let mut symbols = Self::default();
+        for payload in Parser::new(0).parse_all(wasm) {
+            match payload {
+                Ok(Payload::CodeSectionStart { range, .. }) => {
+                    symbols.code_section_start = Some(range.start as u64);
+                }
+                Ok(Payload::CustomSection(reader)) => {
+                    if let KnownCustom::Name(names) = reader.as_known() {
+                        for name in names.into_iter().flatten() {
+                            if let Name::Function(map) = name {
+                                for naming in map.into_iter().flatten() {
+                                    symbols.names.insert(naming.index, naming.name.to_string());
+                                }
+                            }
+                        }
+                    }
+                }
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+        symbols.source_map = SourceMap::parse(wasm).unwrap_or_else(|e| {
+            tracing::warn!("Ignoring unreadable debug info: {}", e);
+            SourceMap::default()
+        });
+        symbols
+    }
+
+    /// The name as stored in the binary, usually a mangled Rust symbol.
+    pub fn raw_name(&self, func_index: u32) -> Option<&str> {
+        self.names.get(&func_index).map(String::as_str)
+    }
+
+    /// The demangled name without its trailing hash, e.g. `my_contract::Vault::deposit`.
+    pub fn function_name(&self, func_index: u32) -> Option<String> {
+        self.raw_name(func_index).map(demangle)
+    }
+
+    /// Converts a module byte offset to the code-section-relative address used by DWARF.
+    pub fn code_offset(&self, module_offset: u64) -> Option<u64> {
+        self.code_section_start.and_then(|start| module_offset.checked_sub(start))
+    }
+
+    /// The inverse of `code_offset`.
+    pub fn module_offset(&self, code_offset: u64) -> Option<u64> {
+        self.code_section_start.map(|start| start + code_offset)
+    }
+
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+
+    /// The source position and inlining chain of the instruction at `module_offset`.
+    pub fn source_frames(&self, module_offset: u64) -> Vec<SourceFrame> {
+        self.code_offset(module_offset)
+            .map(|offset| self.source_map.frames(offset))
+            .unwrap_or_default()
+    }
+}
+
+pub fn demangle(name: &str) -> String {
+    match rustc_demangle::try_demangle(name) {
+        Ok(symbol) => format!("{:#}", symbol),
+        Err(_) => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_are_demangled() {
+        let wasm = wat::parse_str(r#"
+            (module
+                (import "vm_hooks" "msg_value" (func $msg_value (param i32)))
+                (func $_ZN8contract5Vault7deposit17h0123456789abcdefE (result i64)
+                    i64.const 1)
+                (func $plain (result i64)
+                    i64.const 2)
+            )
+        "#).unwrap();
+
+        let symbols = Symbols::parse(&wasm);
+        assert_eq!(symbols.function_name(0).as_deref(), Some("msg_value"));
+        assert_eq!(symbols.raw_name(1), Some("_ZN8contract5Vault7deposit17h0123456789abcdefE"));
+        assert_eq!(symbols.function_name(1).as_deref(), Some("contract::Vault::deposit"));
+        assert_eq!(symbols.function_name(2).as_deref(), Some("plain"));
+        assert_eq!(symbols.function_name(3), None);
+
+        let start = symbols.code_section_start.unwrap();
+        assert_eq!(symbols.code_offset(start + 4), Some(4));
+        assert_eq!(symbols.code_offset(0), None);
+    }
+}
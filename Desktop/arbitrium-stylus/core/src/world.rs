@@ -0,0 +1,326 @@
+use crate::abi::{self, keccak256};
+use crate::metering;
+use crate::precompiles::{L2ToL1Message, PrecompileCall, PrecompileResult, Precompiles, ARB_GAS_INFO, ARB_SYS, ARB_WASM, SIGNATURES};
+use crate::symbols::Symbols;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use wasmer::{Engine, Module};
+
+pub type Address = [u8; 20];
+pub type Bytes32 = [u8; 32];
+
+pub const fn address_from_u64(value: u64) -> Address {
+    let bytes = value.to_be_bytes();
+    let mut address = [0u8; 20];
+    let mut i = 0;
+    while i < 8 {
+        address[12 + i] = bytes[i];
+        i += 1;
+    }
+    address
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Log {
+    pub address: Address,
+    pub topics: Vec<Bytes32>,
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone)]
+pub struct Contract {
+    pub module: Module,
+    /// The engine `module` was compiled and metered with, which its instances must run on.
+    pub engine: Engine,
+    pub wasm: Arc<Vec<u8>>,
+    pub code_hash: Bytes32,
+    pub symbols: Arc<Symbols>,
+}
+
+#[derive(Clone, Default)]
+pub struct Account {
+    pub balance: u128,
+    pub nonce: u64,
+    pub storage: HashMap<Bytes32, Bytes32>,
+    pub contract: Option<Contract>,
+}
+
+/// Saved state used to roll back a reverted call frame.
+#[derive(Clone)]
+pub struct Snapshot {
+    accounts: HashMap<Address, Account>,
+    precompiles: Precompiles,
+}
+
+/// Every account, contract and precompile that contracts can reach during a session.
+pub struct World {
+    gas_table: HashMap<String, u64>,
+    accounts: HashMap<Address, Account>,
+    pub precompiles: Precompiles,
+    labels: HashMap<Address, String>,
+    signatures: HashMap<[u8; 4], String>,
+    /// Event signatures by their full topic hash.
+    events: HashMap<Bytes32, String>,
+}
+
+impl World {
+    /// A world whose contracts are metered with `gas_table`.
+    pub fn new(gas_table: HashMap<String, u64>) -> Self {
+        let labels = [(ARB_SYS, "ArbSys"), (ARB_GAS_INFO, "ArbGasInfo"), (ARB_WASM, "ArbWasm")];
+        Self {
+            gas_table,
+            accounts: HashMap::new(),
+            precompiles: Precompiles::default(),
+            labels: labels.into_iter().map(|(address, label)| (address, label.to_string())).collect(),
+            signatures: SIGNATURES.iter().map(|signature| (abi::selector(signature), signature.to_string())).collect(),
+            events: HashMap::new(),
+        }
+    }
+
+    /// Names an address in traces, e.g. `alice` or `Token`.
+    pub fn set_label(&mut self, address: &Address, label: &str) {
+        self.labels.insert(*address, label.to_string());
+    }
+
+    pub fn label(&self, address: &Address) -> Option<&str> {
+        self.labels.get(address).map(String::as_str)
+    }
+
+    /// Registers a function or event signature, like `transfer(address,uint256)`,
+    /// so traces can decode calls to it.
+    pub fn register_signature(&mut self, signature: &str) {
+        self.signatures.insert(abi::selector(signature), signature.to_string());
+        self.events.insert(keccak256(signature.as_bytes()), signature.to_string());
+    }
+
+    pub fn signature(&self, selector: &[u8]) -> Option<&str> {
+        self.signatures.get(selector).map(String::as_str)
+    }
+
+    /// The event signature whose hash is `topic`, matched on all 32 bytes.
+    pub fn event(&self, topic: &[u8]) -> Option<&str> {
+        let topic: Bytes32 = topic.try_into().ok()?;
+        self.events.get(&topic).map(String::as_str)
+    }
+
+    pub fn account(&self, address: &Address) -> Option<&Account> {
+        self.accounts.get(address)
+    }
+
+    pub fn account_mut(&mut self, address: &Address) -> &mut Account {
+        self.accounts.entry(*address).or_default()
+    }
+
+    pub fn contract(&self, address: &Address) -> Option<&Contract> {
+        self.accounts.get(address).and_then(|a| a.contract.as_ref())
+    }
+
+    pub fn compile(&self, wasm: &[u8]) -> Result<Contract> {
+        let engine = metering::engine(&self.gas_table);
+        let module = Module::new(&engine, wasm)
+            .map_err(|e| anyhow!("Failed to create module: {}", e))?;
+        Ok(Contract {
+            module,
+            engine,
+            wasm: Arc::new(wasm.to_vec()),
+            code_hash: keccak256(wasm),
+            symbols: Arc::new(Symbols::parse(wasm)),
+        })
+    }
+
+    pub fn install(&mut self, address: &Address, contract: Contract) {
+        self.account_mut(address).contract = Some(contract);
+    }
+
+    pub fn storage_load(&self, address: &Address, key: &Bytes32) -> Bytes32 {
+        self.accounts
+            .get(address)
+            .and_then(|a| a.storage.get(key))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn storage_store(&mut self, address: &Address, key: Bytes32, value: Bytes32) {
+        let storage = &mut self.account_mut(address).storage;
+        if value == [0u8; 32] {
+            storage.remove(&key);
+        } else {
+            storage.insert(key, value);
+        }
+    }
+
+    pub fn balance(&self, address: &Address) -> u128 {
+        self.accounts.get(address).map(|a| a.balance).unwrap_or(0)
+    }
+
+    pub fn set_balance(&mut self, address: &Address, balance: u128) {
+        self.account_mut(address).balance = balance;
+    }
+
+    pub fn transfer(&mut self, from: &Address, to: &Address, value: u128) -> Result<()> {
+        if value == 0 {
+            return Ok(());
+        }
+        let available = self.balance(from);
+        if available < value {
+            return Err(anyhow!("Insufficient balance: {} < {}", available, value));
+        }
+        self.account_mut(from).balance -= value;
+        self.account_mut(to).balance += value;
+        Ok(())
+    }
+
+    pub(crate) fn call_precompile(&mut self, address: &Address, call: PrecompileCall) -> PrecompileResult {
+        self.precompiles.call(address, call, &self.accounts)
+    }
+
+    pub fn outbox(&self) -> &[L2ToL1Message] {
+        &self.precompiles.arb_sys.outbox
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            accounts: self.accounts.clone(),
+            precompiles: self.precompiles.clone(),
+        }
+    }
+
+    pub fn revert_to(&mut self, snapshot: Snapshot) {
+        self.accounts = snapshot.accounts;
+        self.precompiles = snapshot.precompiles;
+    }
+
+    /// Address of a contract created with CREATE: `keccak256(rlp([sender, nonce]))[12..]`.
+    pub fn create_address(sender: &Address, nonce: u64) -> Address {
+        let nonce_bytes: Vec<u8> = nonce
+            .to_be_bytes()
+            .iter()
+            .copied()
+            .skip_while(|&b| b == 0)
+            .collect();
+        let mut rlp = Vec::with_capacity(23 + nonce_bytes.len());
+        let nonce_len = match nonce_bytes.as_slice() {
+            [] => 1,
+            [b] if *b < 0x80 => 1,
+            bytes => 1 + bytes.len(),
+        };
+        rlp.push(0xc0 + 21 + nonce_len as u8);
+        rlp.push(0x80 + 20);
+        rlp.extend_from_slice(sender);
+        match nonce_bytes.as_slice() {
+            [] => rlp.push(0x80),
+            [b] if *b < 0x80 => rlp.push(*b),
+            bytes => {
+                rlp.push(0x80 + bytes.len() as u8);
+                rlp.extend_from_slice(bytes);
+            }
+        }
+        let hash = keccak256(&rlp);
+        hash[12..].try_into().unwrap()
+    }
+
+    /// Address of a contract created with CREATE2.
+    pub fn create2_address(sender: &Address, salt: &Bytes32, code_hash: &Bytes32) -> Address {
+        let mut preimage = Vec::with_capacity(85);
+        preimage.push(0xff);
+        preimage.extend_from_slice(sender);
+        preimage.extend_from_slice(salt);
+        preimage.extend_from_slice(code_hash);
+        let hash = keccak256(&preimage);
+        hash[12..].try_into().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::abi::{hex, parse_hex};
+
+    fn address(text: &str) -> Address {
+        parse_hex(text).unwrap().try_into().unwrap()
+    }
+
+    #[test]
+    fn test_create_address_matches_known_deployments() {
+        let sender = address("0x6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0");
+        let expected = [
+            "0xcd234a471b72ba2f1ccf0a70fcaba648a5eecd8d",
+            "0x343c43a37d37dff08ae8c4a11544c718abb4fcf8",
+            "0xf778b86fa74e846c4f0a1fbd1335fe81c00a0c91",
+            "0xfffd933a0bc612844eaf0c6fe3e5b8e9b6c1d19c",
+        ];
+        for (nonce, expected) in expected.iter().enumerate() {
+            assert_eq!(hex(&World::create_address(&sender, nonce as u64)), *expected);
+        }
+    }
+
+    #[test]
+    fn test_create_address_encodes_long_nonces_as_rlp_strings() {
+        let sender = address("0x6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0");
+        for (nonce, encoded) in [(0x7f, vec![0x7f]), (0x80, vec![0x81, 0x80]), (0x0400, vec![0x82, 0x04, 0x00])] {
+            let mut rlp = vec![0xc0 + 21 + encoded.len() as u8, 0x94];
+            rlp.extend_from_slice(&sender);
+            rlp.extend_from_slice(&encoded);
+            assert_eq!(World::create_address(&sender, nonce), keccak256(&rlp)[12..]);
+        }
+    }
+
+    #[test]
+    fn test_create2_address_matches_eip_1014_examples() {
+        let init_code_hash = keccak256(&[0x00]);
+        let examples = [
+            ("0x0000000000000000000000000000000000000000", "0x4d1a2e2bb4f88f0250f26ffff098b0b30b26bf38"),
+            ("0xdeadbeef00000000000000000000000000000000", "0xb928f69bb1d91cd65274e3c79d8986362984fda3"),
+        ];
+        for (sender, expected) in examples {
+            let created = World::create2_address(&address(sender), &[0u8; 32], &init_code_hash);
+            assert_eq!(hex(&created), expected);
+        }
+    }
+
+    #[test]
+    fn test_revert_to_restores_accounts_and_precompiles() {
+        let mut world = World::new(HashMap::new());
+        let (alice, bob) = (address_from_u64(1), address_from_u64(2));
+        world.set_balance(&alice, 100);
+        world.storage_store(&alice, [1u8; 32], [2u8; 32]);
+
+        let snapshot = world.snapshot();
+        world.transfer(&alice, &bob, 40).unwrap();
+        world.account_mut(&alice).nonce += 1;
+        world.storage_store(&alice, [1u8; 32], [0u8; 32]);
+        world.storage_store(&bob, [3u8; 32], [4u8; 32]);
+        world.precompiles.arb_sys.outbox.push(L2ToL1Message {
+            id: 0,
+            sender: alice,
+            destination: bob,
+            value: 0,
+            data: Vec::new(),
+            l2_block: 1,
+            timestamp: 0,
+        });
+        world.revert_to(snapshot);
+
+        assert_eq!((world.balance(&alice), world.balance(&bob)), (100, 0));
+        assert_eq!(world.account(&alice).unwrap().nonce, 0);
+        assert_eq!(world.storage_load(&alice, &[1u8; 32]), [2u8; 32]);
+        assert_eq!(world.storage_load(&bob, &[3u8; 32]), [0u8; 32]);
+        assert!(world.outbox().is_empty());
+    }
+
+    #[test]
+    fn test_transfer_with_insufficient_balance_changes_nothing() {
+        let mut world = World::new(HashMap::new());
+        let (alice, bob) = (address_from_u64(1), address_from_u64(2));
+        world.set_balance(&alice, 10);
+
+        let err = world.transfer(&alice, &bob, 11).unwrap_err();
+        assert_eq!(err.to_string(), "Insufficient balance: 10 < 11");
+        assert_eq!((world.balance(&alice), world.balance(&bob)), (10, 0));
+        world.transfer(&bob, &alice, 0).unwrap();
+        world.transfer(&alice, &bob, 10).unwrap();
+        assert_eq!((world.balance(&alice), world.balance(&bob)), (0, 10));
+    }
+}
@@ -1,15 +1,28 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
-use stylus_core::{ExecutionResult, GasProfile, StylusRuntime};
+use stylus_core::{
+    Backtrace, ConsoleLine, CoverageReport, ExecutionContext, ExecutionResult, GasProfile, StubPolicy, StylusRuntime, Trap,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TestResult {
     pub name: String,
+    /// The export the test called and the arguments it passed.
+    #[serde(default)]
+    pub function: String,
+    #[serde(default)]
+    pub args: Vec<i64>,
     pub passed: bool,
     pub execution_result: Option<ExecutionResult>,
     pub error: Option<String>,
     pub gas_profile: Option<GasProfile>,
+    /// Everything the contract printed, including output from a run that failed.
+    #[serde(default)]
+    pub console: Vec<ConsoleLine>,
+    /// Where the contract trapped, for failures caused by a trap.
+    #[serde(default)]
+    pub backtrace: Option<Backtrace>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,6 +32,9 @@ pub struct TestSuite {
     pub total_gas: u64,
     pub passed: usize,
     pub failed: usize,
+    /// Instruction, branch and line coverage of every test, if coverage was enabled.
+    #[serde(default)]
+    pub coverage: Option<CoverageReport>,
 }
 
 pub struct StylusRunner {
@@ -35,36 +51,72 @@ impl StylusRunner {
         })
     }
 
+    /// Starts the session from a deployed contract whose constructor has already run.
+    pub fn deploy(wasm_bytes: &[u8], init_calldata: &[u8], ctx: ExecutionContext) -> Result<Self> {
+        let runtime = StylusRuntime::deploy(wasm_bytes, init_calldata, ctx)?;
+        Ok(Self {
+            runtime,
+            test_results: Vec::new(),
+        })
+    }
+
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
         let wasm_bytes = std::fs::read(path)?;
         Self::new(&wasm_bytes)
     }
 
+    pub fn runtime_mut(&mut self) -> &mut StylusRuntime {
+        &mut self.runtime
+    }
+
+    /// Lets partially supported contracts run by stubbing imports the runtime lacks.
+    pub fn set_stub_policy(&mut self, policy: StubPolicy) {
+        self.runtime.set_stub_policy(policy);
+    }
+
+    /// Collects coverage from every following test, reported by `finalize_suite`.
+    /// Tests then run in the interpreter.
+    pub fn enable_coverage(&mut self) {
+        self.runtime.enable_coverage(true);
+    }
+
+    pub fn execute(&mut self, fn_name: &str, args: &[i64]) -> Result<ExecutionResult> {
+        self.runtime.execute_function(fn_name, args)
+    }
+
     pub fn call(&mut self, fn_name: &str, args: &[i64]) -> Result<i64> {
         let result = self.runtime.execute_function(fn_name, args)?;
         Ok(result.return_value)
     }
 
+    /// Runs the function once, instrumented, so the result and its gas profile come from the same execution.
     pub fn test(&mut self, test_name: &str, fn_name: &str, args: &[i64], expected: i64) -> TestResult {
-        match self.runtime.execute_function(fn_name, args) {
-            Ok(result) => {
+        match self.runtime.execute_with_profile(fn_name, args) {
+            Ok((result, gas_profile)) => {
                 let passed = result.return_value == expected;
-                let gas_profile = self.runtime.profile_function(fn_name, args).ok();
                 
                 TestResult {
                     name: test_name.to_string(),
+                    function: fn_name.to_string(),
+                    args: args.to_vec(),
                     passed,
+                    console: result.console.clone(),
+                    backtrace: None,
                     execution_result: Some(result),
                     error: None,
-                    gas_profile,
+                    gas_profile: Some(gas_profile),
                 }
             }
             Err(e) => TestResult {
                 name: test_name.to_string(),
+                function: fn_name.to_string(),
+                args: args.to_vec(),
                 passed: false,
                 execution_result: None,
                 error: Some(e.to_string()),
                 gas_profile: None,
+                console: console_of(&e),
+                backtrace: trap_of(&e).map(|trap| trap.backtrace.clone()),
             }
         }
     }
@@ -75,36 +127,79 @@ impl StylusRunner {
     }
 
     pub fn assert_gas_limit(&mut self, test_name: &str, fn_name: &str, args: &[i64], max_gas: u64) {
-        match self.runtime.execute_function(fn_name, args) {
-            Ok(result) => {
+        match self.runtime.execute_with_profile(fn_name, args) {
+            Ok((result, gas_profile)) => {
                 let passed = result.gas_used <= max_gas;
+                let error = if !passed {
+                    Some(format!("Gas limit exceeded: {} > {}", result.gas_used, max_gas))
+                } else {
+                    None
+                };
                 let test_result = TestResult {
                     name: format!("{} (gas limit)", test_name),
+                    function: fn_name.to_string(),
+                    args: args.to_vec(),
                     passed,
+                    console: result.console.clone(),
+                    backtrace: None,
                     execution_result: Some(result),
-                    error: if !passed {
-                        Some(format!("Gas limit exceeded: {} > {}", result.gas_used, max_gas))
-                    } else {
-                        None
-                    },
-                    gas_profile: self.runtime.profile_function(fn_name, args).ok(),
+                    error,
+                    gas_profile: Some(gas_profile),
                 };
                 self.test_results.push(test_result);
             }
             Err(e) => {
                 let test_result = TestResult {
                     name: format!("{} (gas limit)", test_name),
+                    function: fn_name.to_string(),
+                    args: args.to_vec(),
                     passed: false,
                     execution_result: None,
                     error: Some(e.to_string()),
                     gas_profile: None,
+                    console: console_of(&e),
+                    backtrace: trap_of(&e).map(|trap| trap.backtrace.clone()),
                 };
                 self.test_results.push(test_result);
             }
         }
     }
 
-    pub fn finalize_suite(&self, suite_name: &str) -> TestSuite {
+    /// Passes only if the function runs to completion without changing any state.
+    pub fn assert_view(&mut self, test_name: &str, fn_name: &str, args: &[i64]) {
+        let static_mode = self.runtime.static_mode();
+        self.runtime.enable_static_mode(true);
+        let outcome = self.runtime.execute_function(fn_name, args);
+        self.runtime.enable_static_mode(static_mode);
+
+        let test_result = match outcome {
+            Ok(result) => TestResult {
+                name: format!("{} (view)", test_name),
+                function: fn_name.to_string(),
+                args: args.to_vec(),
+                passed: true,
+                console: result.console.clone(),
+                backtrace: None,
+                execution_result: Some(result),
+                error: None,
+                gas_profile: None,
+            },
+            Err(e) => TestResult {
+                name: format!("{} (view)", test_name),
+                function: fn_name.to_string(),
+                args: args.to_vec(),
+                passed: false,
+                execution_result: None,
+                error: Some(e.to_string()),
+                gas_profile: None,
+                console: console_of(&e),
+                backtrace: trap_of(&e).map(|trap| trap.backtrace.clone()),
+            },
+        };
+        self.test_results.push(test_result);
+    }
+
+    pub fn finalize_suite(&mut self, suite_name: &str) -> TestSuite {
         let passed = self.test_results.iter().filter(|t| t.passed).count();
         let failed = self.test_results.len() - passed;
         let total_gas = self.test_results
@@ -119,6 +214,7 @@ impl StylusRunner {
             total_gas,
             passed,
             failed,
+            coverage: self.runtime.coverage(),
         }
     }
 
@@ -127,6 +223,14 @@ impl StylusRunner {
     }
 }
 
+fn trap_of(error: &anyhow::Error) -> Option<&Trap> {
+    error.downcast_ref::<Trap>()
+}
+
+fn console_of(error: &anyhow::Error) -> Vec<ConsoleLine> {
+    trap_of(error).map(|trap| trap.console.clone()).unwrap_or_default()
+}
+
 // Macro for easier test writing
 #[macro_export]
 macro_rules! stylus_test {
@@ -153,5 +257,9 @@ mod tests {
         let mut runner = StylusRunner::new(&wasm).unwrap();
         let result = runner.call("add", &[5, 3]).unwrap();
         assert_eq!(result, 8);
+
+        let test = runner.test("sum", "add", &[5, 3], 8);
+        assert!(test.passed);
+        assert_eq!((test.function.as_str(), test.args.as_slice()), ("add", &[5, 3][..]));
     }
 }
\ No newline at end of file
@@ -35,4 +35,120 @@ fn test_gas_profiling() {
     
     assert_eq!(profile.function_name, "multiply");
     assert!(profile.gas_used > 0);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_view_functions_are_side_effect_free() {
+    let wasm = wat::parse_str(r#"
+        (module
+            (import "vm_hooks" "storage_store_bytes32" (func $store (param i32 i32)))
+            (memory (export "memory") 1)
+            (func (export "get") (result i64)
+                i64.const 7)
+            (func (export "set") (result i64)
+                (call $store (i32.const 0) (i32.const 32))
+                i64.const 1)
+        )
+    "#).unwrap();
+    
+    let mut runner = StylusRunner::new(&wasm).unwrap();
+    runner.assert_view("get", "get", &[]);
+    runner.assert_view("set", "set", &[]);
+    
+    let suite = runner.finalize_suite("view_tests");
+    assert_eq!(suite.passed, 1);
+    assert_eq!(suite.failed, 1);
+    assert!(suite.tests[1].error.as_ref().unwrap().contains("Static call violation: storage write"));
+}
+
+#[test]
+fn test_view_assertions_keep_the_runtime_static_mode() {
+    let wasm = wat::parse_str(r#"
+        (module
+            (func (export "get") (result i64)
+                i64.const 7)
+        )
+    "#).unwrap();
+
+    let mut runner = StylusRunner::new(&wasm).unwrap();
+    runner.assert_view("get", "get", &[]);
+    assert!(!runner.runtime_mut().static_mode());
+
+    runner.runtime_mut().enable_static_mode(true);
+    runner.assert_view("get again", "get", &[]);
+    assert!(runner.runtime_mut().static_mode());
+}
+
+#[test]
+fn test_suite_coverage() {
+    let wasm = wat::parse_str(r#"
+        (module
+            (func (export "abs") (param i64) (result i64)
+                (if (result i64) (i64.lt_s (local.get 0) (i64.const 0))
+                    (then (i64.sub (i64.const 0) (local.get 0)))
+                    (else (local.get 0))))
+            (func (export "double") (param i64) (result i64)
+                (i64.add (local.get 0) (local.get 0)))
+        )
+    "#).unwrap();
+
+    let mut runner = StylusRunner::new(&wasm).unwrap();
+    runner.enable_coverage();
+    stylus_test!(runner, "abs_negative", "abs", &[-4], 4);
+    runner.assert_view("abs_positive", "abs", &[4]);
+
+    let coverage = runner.finalize_suite("coverage_tests").coverage.unwrap();
+    let abs = coverage.functions.iter().find(|function| function.name == "abs").unwrap();
+    assert_eq!((abs.calls, abs.covered_branches, abs.branches), (2, 2, 2));
+    assert_eq!(abs.covered_instructions, abs.instructions);
+    let double = coverage.functions.iter().find(|function| function.name == "double").unwrap();
+    assert_eq!(double.calls, 0);
+    assert!(coverage.instructions().percent() < 100.0);
+}
+
+#[test]
+fn test_test_gas_matches_plain_execution() {
+    let wasm = wat::parse_str(r#"
+        (module
+            (func (export "square") (param i64) (result i64)
+                (i64.mul (local.get 0) (local.get 0)))
+        )
+    "#).unwrap();
+
+    let mut runner = StylusRunner::new(&wasm).unwrap();
+    let plain = runner.execute("square", &[3]).unwrap().gas_used;
+    stylus_test!(runner, "square", "square", &[3], 9);
+    runner.assert_gas_limit("square", "square", &[3], plain);
+
+    let suite = runner.finalize_suite("gas_tests");
+    assert_eq!(suite.passed, 2);
+    let profiled = &suite.tests[0];
+    assert_eq!(profiled.execution_result.as_ref().unwrap().gas_used, plain);
+    assert_eq!(profiled.gas_profile.as_ref().unwrap().gas_used, plain);
+    assert_eq!(suite.total_gas, 2 * plain);
+}
+
+#[test]
+fn test_failed_tests_report_the_trap_backtrace() {
+    let wasm = wat::parse_str(r#"
+        (module
+            (func $_ZN8contract5check17h0123456789abcdefE (param $amount i64)
+                (if (i64.eqz (local.get $amount)) (then unreachable)))
+            (func $_ZN8contract8withdraw17hfedcba9876543210E (export "withdraw") (param $amount i64) (result i64)
+                (call $_ZN8contract5check17h0123456789abcdefE (local.get $amount))
+                (local.get $amount))
+        )
+    "#).unwrap();
+
+    let mut runner = StylusRunner::new(&wasm).unwrap();
+    let plain = runner.runtime_mut().execute_function("withdraw", &[0]).unwrap_err();
+    let plain = plain.downcast_ref::<stylus_core::Trap>().unwrap().backtrace.clone();
+
+    let result = runner.test("withdraw_zero", "withdraw", &[0], 0);
+    let backtrace = result.backtrace.unwrap();
+    assert_eq!(backtrace, plain);
+    assert_eq!(backtrace.frames[0].function.as_deref(), Some("contract::check"));
+    let error = result.error.unwrap();
+    assert!(error.contains(&format!("   0: {}", backtrace.frames[0])), "{}", error);
+    assert!(error.contains("contract::withdraw"), "{}", error);
+}